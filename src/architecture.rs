@@ -1,121 +1,698 @@
+mod instruction;
+mod keypad;
+#[cfg(test)]
+mod opcode_tests;
+mod program;
+mod quirks;
+mod rng;
 mod stack;
+mod state;
 mod utils;
+pub use instruction::Instruction;
+pub use keypad::KeyEvent;
+use keypad::KeyQueue;
+use program::Op;
+pub use program::Program;
+pub use quirks::{Dxy0, Platform, Quirks};
+pub use rng::RandomSource;
+use rng::Rng;
 use stack::Stack;
-use std::process;
-use utils::Hex;
+pub use stack::STACK_SIZE;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, Write};
 
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+/// The display's size in SUPER-CHIP's high-resolution mode.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+/// Instructions run per 60Hz frame unless a game or the user asks for
+/// another speed.
+pub const INSTRUCTIONS_PER_FRAME: usize = 12;
 const RAM_SIZE: usize = 0x1000;
+/// XO-CHIP's memory, the whole of what a 16-bit I can address.
+const XO_RAM_SIZE: usize = 0x10000;
+const FONT_ADDRESS: usize = 0x000;
+/// The usual hex digit sprites, 0 to F, five rows each. Every machine starts
+/// with them at `FONT_ADDRESS`, for Fx29 to point I at.
+const FONT: [u8; 16 * 5] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+const BIG_FONT_ADDRESS: usize = FONT_ADDRESS + FONT.len();
+/// SUPER-CHIP's large digits, eight pixels wide and ten rows high, for
+/// Fx30. SCHIP 1.1 only had 0 to 9; A to F are drawn in the same style.
+const BIG_FONT: [u8; 16 * 10] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x18, 0x3C, 0x66, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+/// Where ROMs are loaded, and where execution starts.
+pub const LOAD_ADDRESS: usize = 0x200;
+/// The most ROM that fits between the load address and the end of RAM.
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - LOAD_ADDRESS;
+/// The same for XO-CHIP's 64KB.
+pub const XO_MAX_ROM_SIZE: usize = XO_RAM_SIZE - LOAD_ADDRESS;
 
+/// The most ROM a machine with these quirks can load.
+pub fn max_rom_size(quirks: Quirks) -> usize {
+    if quirks.xochip {
+        XO_MAX_ROM_SIZE
+    } else {
+        MAX_ROM_SIZE
+    }
+}
+
+/// The width and height of a display from `Architecture::display`: 64x32,
+/// or 128x64 while a SUPER-CHIP program is in high resolution.
+pub fn display_size(display: &[u8]) -> (usize, usize) {
+    if display.len() == HIRES_WIDTH * HIRES_HEIGHT {
+        (HIRES_WIDTH, HIRES_HEIGHT)
+    } else {
+        (WIDTH, HEIGHT)
+    }
+}
+
+/// The display redrawn at the other resolution, for outputs whose size is
+/// fixed: each low-resolution pixel becomes a 2x2 block, or each 2x2 block
+/// of high-resolution pixels becomes one, lit if any of them is. Displays
+/// already `width` wide are returned as they are.
+pub fn fit_display(display: &[u8], width: usize) -> Cow<'_, [u8]> {
+    let (from, _) = display_size(display);
+    if from == width {
+        return Cow::Borrowed(display);
+    }
+    let height = if width == HIRES_WIDTH {
+        HIRES_HEIGHT
+    } else {
+        HEIGHT
+    };
+    let fitted = (0..width * height)
+        .map(|pixel| {
+            let (x, y) = (pixel % width, pixel / width);
+            if width > from {
+                return display[(y / 2) * from + x / 2];
+            }
+            let lit = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .any(|&(dx, dy)| display[(y * 2 + dy) * from + x * 2 + dx] != 0);
+            u8::from(lit)
+        })
+        .collect();
+    Cow::Owned(fitted)
+}
+
+/// XO-CHIP's sound: 128 one-bit samples, played on a loop for as long as
+/// the sound timer runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioPattern {
+    /// Most significant bit first.
+    pub bits: [u8; 16],
+    /// Set by Fx3A; 64 plays the pattern at 4000 bits a second.
+    pub pitch: u8,
+}
+impl AudioPattern {
+    /// Pattern bits played per second.
+    pub fn rate(&self) -> f64 {
+        4000.0 * 2f64.powf((f64::from(self.pitch) - 64.0) / 48.0)
+    }
+}
+
+/// What a call to `execute` left the machine doing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Running,
+    /// The program ran 00FD (EXIT), or faulted, and will not execute
+    /// anything else.
+    Halted,
+    /// Only from `run_frame`: the program is blocked in Fx0A, or in a jump
+    /// to itself, so nothing will change until a key does.
+    Idle,
+}
+
+/// What the machine is doing, for frontends and tools to report, so a game
+/// blocked in Fx0A can be told apart from one that has stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MachineState {
+    Running,
+    WaitingForKey,
+    /// The program ran EXIT.
+    Halted,
+    /// The program did something no interpreter could carry on from, and
+    /// stopped at that instruction.
+    Faulted(Fault),
+}
+
+/// Something the program did that no interpreter could carry on from. The
+/// machine stops at the offending instruction instead of panicking, and
+/// `fault` says why.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// RET (00EE) at `pc` with no return address on the stack.
+    StackUnderflow { pc: u16 },
+    /// CALL (2nnn) at `pc` with no room left on the stack.
+    StackOverflow { pc: u16 },
+    /// `word` at `pc` isn't an instruction, at least not with these quirks.
+    InvalidOpcode { pc: u16, word: u16 },
+}
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::StackUnderflow { pc } => {
+                write!(f, "RET at {:03X} with an empty stack", pc)
+            }
+            Fault::StackOverflow { pc } => {
+                write!(f, "CALL at {:03X} nested deeper than {}", pc, STACK_SIZE)
+            }
+            Fault::InvalidOpcode { pc, word } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", word, pc)
+            }
+        }
+    }
+}
+
+/// A copy of the CPU registers, for tools that inspect a running machine.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub dt: u8,
+    pub st: u8,
+    pub sp: usize,
+    pub stack: [u16; 16],
+}
+
+/// Pending Fx0A: the register to fill and, once one goes down, the key whose
+/// release will complete the wait.
 #[derive(Debug, Clone, Copy)]
+struct KeyWait {
+    register: usize,
+    key: Option<u8>,
+}
+
+#[derive(Debug)]
 pub struct Architecture {
-    ram: [u8; RAM_SIZE],
+    /// Big enough for XO-CHIP; other machines only use the first 4KB.
+    ram: Box<[u8; XO_RAM_SIZE]>,
     stack: Stack,
-    display: [u8; WIDTH * HEIGHT],
+    /// Rows of the current resolution's width, packed from the start, so
+    /// a low-resolution display only uses the first 64x32 bytes.
+    display: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    /// SUPER-CHIP's 128x64 mode, switched with 00FF and 00FE.
+    hires: bool,
+    /// The XO-CHIP planes that drawing, clearing and scrolling act on, as
+    /// a bitmask of the bits they take in each display byte. Fn01 sets it.
+    plane: u8,
     v: [u8; 16],
     i: u16,
     pc: u16,
     dt: u8,
     st: u8,
+    keys: [bool; 16],
+    /// Key events from the frontends, applied when the next frame starts so
+    /// every instruction in a frame sees the same keys.
+    queued_keys: KeyQueue,
+    key_wait: Option<KeyWait>,
+    halted: bool,
+    /// Why the machine stopped, when it wasn't EXIT.
+    fault: Option<Fault>,
+    /// The HP-48's RPL user flags, which Fx75 and Fx85 copy registers to
+    /// and from. SCHIP 1.1 had eight; XO-CHIP has sixteen.
+    flags: [u8; 16],
+    /// Loaded by F002; `None` until then, so the buzzer keeps its beep.
+    audio: Option<[u8; 16]>,
+    pitch: u8,
+    quirks: Quirks,
+    rng: Box<dyn RandomSource>,
 }
+// Machines share nothing, so each one can run on its own thread.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Architecture>();
+};
+
 impl Architecture {
     pub fn new() -> Self {
-        Self {
-            ram: [0; RAM_SIZE],
+        Self::with_rng(Rng::from_os())
+    }
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(Rng::new(seed))
+    }
+    /// A machine drawing Cxkk's random bytes from `rng`, like a fixed
+    /// sequence for a test.
+    pub fn with_rng(rng: impl RandomSource + 'static) -> Self {
+        let mut arch = Self {
+            ram: Box::new([0; XO_RAM_SIZE]),
             stack: Stack::new(),
-            display: [0; WIDTH * HEIGHT],
+            display: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            plane: 1,
             v: [0; 16],
             i: 0,
-            pc: 0,
+            pc: LOAD_ADDRESS as u16,
             dt: 0,
             st: 0,
-        }
+            keys: [false; 16],
+            queued_keys: KeyQueue::new(),
+            key_wait: None,
+            halted: false,
+            fault: None,
+            flags: [0; 16],
+            audio: None,
+            pitch: 64,
+            quirks: Quirks::default(),
+            rng: Box::new(rng),
+        };
+        arch.load_font(&FONT);
+        arch.ram[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+        arch
+    }
+}
+impl Default for Architecture {
+    fn default() -> Self {
+        Self::new()
     }
 }
 impl Architecture {
-    pub fn execute(self: &mut Self, rom: &Vec<u16>) -> () {
-        let instruction = rom[self.pc as usize];
-        match instruction {
-            0x00E0 => self.cls(),
-            0x00EE => self.ret(),
-            0x1000..=0x1FFF => self.jp(instruction),
-            0x2000..=0x2FFF => self.call(instruction),
-            0x3000..=0x3FFF => self.s_e_byte(instruction),
-            0x4000..=0x4FFF => self.s_n_e_byte(instruction),
-            0x5000..=0x5FFF => self.s_e_register(instruction),
-            0x6000..=0x6FFF => self.load_byte(instruction),
-            0x7000..=0x7FFF => self.add_byte(instruction),
-            0x8000..=0x8FFF => match instruction & 0xF
-            {
-                        0x0 => self.ld(instruction),
-                        0x1 => self.or(instruction),
-                        0x2 => self.and(instruction),
-                        0x3 => self.xor(instruction),
-                        0x4 => self.add(instruction),
-                        0x5 => self.sub(instruction),
-                        0x6 => self.shr(instruction),
-                        0x7 => self.subn(instruction),
-                        0xE => self.shl(instruction),
-                          _ => panic!("OpCode does not exist!"),
-            },
-            0x9000..=0x9FFF => self.s_n_e(instruction),
-            0xA000..=0xAFFF => self.ld_i(instruction),
-            0xB000..=0xBFFF => self.jp_v0(instruction),
-            0xC000..=0xCFFF => self.rnd(instruction),
-            0xD000..=0xDFFF => self.drw(instruction),
-            0xE000..=0xEFFF => match instruction & 0xFF
-            {
-                       0x9E => self.skp(instruction),
-                       0xA1 => self.sknp(instruction),
-                          _ => panic!("OpCode does not exist!"),
+    pub fn execute(&mut self) -> Outcome {
+        if self.halted {
+            return Outcome::Halted;
+        }
+        if self.key_wait.is_some() {
+            return Outcome::Running;
+        }
+        let word = self.fetch();
+        match Instruction::decode(word) {
+            Ok(instruction) => self.run(instruction),
+            Err(_) => self.invalid_opcode(word),
+        }
+        if self.halted {
+            Outcome::Halted
+        } else {
+            Outcome::Running
+        }
+    }
+    /// Runs an instruction decoded ahead of time, like the program cache's,
+    /// in place of the one at PC.
+    fn step(&mut self, instruction: Instruction) {
+        self.fetch();
+        self.run(instruction);
+    }
+    /// The instruction word at PC, moving PC past it. PC moves as the
+    /// instruction is fetched, so jumps land where they point, skips only
+    /// add one more instruction and CALL pushes the address to return to.
+    fn fetch(&mut self) -> u16 {
+        let word = self.word_at(self.pc);
+        self.pc = self.address(self.pc, 2) as u16;
+        word
+    }
+    /// Executes a fetched and decoded instruction, if this machine has it.
+    fn run(&mut self, original: Instruction) {
+        match original.with_quirks(self.quirks) {
+            Some(instruction) => self.dispatch(instruction),
+            None => self.invalid_opcode(original.encode()),
+        }
+    }
+    /// Halts on the instruction just fetched, `word`, which is no
+    /// instruction this machine has.
+    fn invalid_opcode(&mut self, word: u16) {
+        self.raise(|pc| Fault::InvalidOpcode { pc, word });
+    }
+    /// The big-endian instruction word at `address`. Addresses wrap at the
+    /// end of RAM like I does.
+    pub fn word_at(&self, address: u16) -> u16 {
+        u16::from_be_bytes([
+            self.ram[self.address(address, 0)],
+            self.ram[self.address(address, 1)],
+        ])
+    }
+    /// `address + offset`, wrapped at the end of RAM.
+    fn address(&self, address: u16, offset: usize) -> usize {
+        (usize::from(address) + offset) % self.memory_size()
+    }
+    /// 4KB, or 64KB on XO-CHIP.
+    fn memory_size(&self) -> usize {
+        if self.quirks.xochip {
+            XO_RAM_SIZE
+        } else {
+            RAM_SIZE
+        }
+    }
+    /// Runs one 60Hz frame: the queued key events, up to `budget`
+    /// instructions, then a single timer tick. The frame ends early if the
+    /// program halts or waits for a key, or if `after`, which is given the
+    /// machine and the address and opcode of each instruction once it has
+    /// run, returns false. The timers tick either way, so they keep 60Hz
+    /// whatever the budget. Frames that leave the program waiting on a key
+    /// or jumping to itself report `Idle`.
+    pub fn run_frame(
+        &mut self,
+        program: &Program,
+        budget: usize,
+        mut after: impl FnMut(&Self, u16, u16) -> bool,
+    ) -> Outcome {
+        // Taken out while it drains, since applying a key borrows the machine.
+        let mut queued_keys = std::mem::replace(&mut self.queued_keys, KeyQueue::new());
+        queued_keys.drain_frame(|event| self.set_key(event.key, event.pressed));
+        self.queued_keys = queued_keys;
+        let mut spinning = false;
+        let mut remaining = budget;
+        while remaining > 0 && !self.halted && self.key_wait.is_none() {
+            let pc = self.pc;
+            let word = self.word_at(pc);
+            // Fused ops run their first instruction, report it, then run
+            // the second straight away if execution falls through to it.
+            let next = self.address(pc, 2) as u16;
+            let (last, word) = match program.op(pc, self.ram()) {
+                Some(Op::Single(instruction)) => {
+                    self.step(instruction);
+                    (pc, word)
+                }
+                Some(Op::LoadAddI { x, kk }) if remaining >= 2 => {
+                    self.pc = next;
+                    self.load_byte(x, kk);
+                    remaining -= 1;
+                    if !after(self, pc, word) {
+                        break;
+                    }
+                    self.pc = self.address(next, 2) as u16;
+                    self.add_i(x);
+                    (next, self.word_at(next))
+                }
+                Some(Op::SkipJump { skip, nnn }) if remaining >= 2 => {
+                    self.step(skip);
+                    if self.pc != next {
+                        (pc, word)
+                    } else {
+                        remaining -= 1;
+                        if !after(self, pc, word) {
+                            break;
+                        }
+                        self.pc = self.address(next, 2) as u16;
+                        self.jp(nnn);
+                        (next, self.word_at(next))
+                    }
+                }
+                _ => {
+                    self.execute();
+                    (pc, word)
+                }
+            };
+            remaining -= 1;
+            spinning = self.pc == last;
+            if !after(self, last, word) {
+                break;
             }
-            0xF000..=0xFFFF => match instruction & 0xFF 
-            {
-                       0x07 => self.ld_reg_dt(instruction),
-                       0x0A => self.ld_wait(instruction),
-                       0x15 => self.ld_dt_reg(instruction),
-                       0x18 => self.ld_st(instruction),
-                       0x1E => self.add_i(instruction),
-                       0x29 => self.ld_loc(instruction),
-                       0x33 => self.ld_bcd(instruction),
-                       0x55 => self.store_regs(instruction),
-                       0x65 => self.read_regs(instruction),
-                          _ => panic!("OpCode does not exist!"),
+        }
+        self.tick_timers();
+        if self.halted {
+            Outcome::Halted
+        } else if spinning || self.key_wait.is_some() {
+            Outcome::Idle
+        } else {
+            Outcome::Running
+        }
+    }
+    /// Runs `rom` on a fresh machine with the default quirks, seed 0 and
+    /// no keys pressed, for test ROMs in CI. It stops after `max_cycles`
+    /// instructions, or sooner once the program halts, waits for a key or
+    /// jumps to itself, which is how test ROMs end. The machine is left as
+    /// it stopped, for its display to be checked or hashed.
+    pub fn run_headless(rom: &[u8], max_cycles: usize) -> Self {
+        let mut arch = Self::with_seed(0);
+        arch.load_rom(rom);
+        let program = Program::new(rom.to_vec());
+        let mut remaining = max_cycles;
+        while remaining > 0 {
+            let budget = remaining.min(INSTRUCTIONS_PER_FRAME);
+            let mut executed = 0;
+            let outcome = arch.run_frame(&program, budget, |_, _, _| {
+                executed += 1;
+                true
+            });
+            remaining -= executed;
+            if outcome != Outcome::Running {
+                break;
             }
-            _ => panic!("OpCode does not exist!"),
         }
-        self.pc += 1;
+        arch
+    }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    /// Copies a ROM into RAM at the load address. Anything past the end of
+    /// RAM is dropped; callers check against `max_rom_size` first, and set
+    /// the quirks before loading, since XO-CHIP has more RAM.
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        let size = rom.len().min(self.memory_size() - LOAD_ADDRESS);
+        self.ram[LOAD_ADDRESS..LOAD_ADDRESS + size].copy_from_slice(&rom[..size]);
+    }
+    /// Copies hex digit sprites (5 bytes per digit) to the font area of RAM.
+    pub fn load_font(&mut self, font: &[u8]) {
+        self.ram[FONT_ADDRESS..FONT_ADDRESS + font.len()].copy_from_slice(font);
+    }
+    /// True once the program has run EXIT, or faulted.
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+    /// What stopped the machine, if it was a fault rather than EXIT.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+    /// Stops the machine at the instruction that just ran.
+    fn raise(&mut self, fault: impl FnOnce(u16) -> Fault) {
+        let pc = self.address(self.pc, self.memory_size() - 2) as u16;
+        self.pc = pc;
+        self.fault = Some(fault(pc));
+        self.halted = true;
+    }
+    pub fn state(&self) -> MachineState {
+        if let Some(fault) = self.fault {
+            MachineState::Faulted(fault)
+        } else if self.halted {
+            MachineState::Halted
+        } else if self.key_wait.is_some() {
+            MachineState::WaitingForKey
+        } else {
+            MachineState::Running
+        }
+    }
+    /// True while an Fx0A is blocking execution until a key is released.
+    pub fn waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+    /// Which keypad keys are down, as of the start of this frame.
+    pub fn keys(&self) -> [bool; 16] {
+        self.keys
+    }
+    /// Queues a key change for the start of the next frame.
+    pub fn queue_key(&mut self, event: KeyEvent) {
+        self.queued_keys.push(event);
+    }
+    /// Changes a key at once instead of at the next frame, for callers
+    /// that run instructions one by one.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        let key = key & 0xF;
+        let was_pressed = std::mem::replace(&mut self.keys[key as usize], pressed);
+        if let Some(wait) = self.key_wait.as_mut() {
+            match wait.key {
+                None if pressed && !was_pressed => wait.key = Some(key),
+                Some(waited) if waited == key && !pressed => {
+                    self.v[wait.register] = key;
+                    self.key_wait = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    pub fn write_state(&self, out: &mut impl Write) -> io::Result<()> {
+        let word = self.word_at(self.pc);
+        match Instruction::decode(word) {
+            Ok(instruction) => writeln!(out, "last instruction: {:04X} ({})", word, instruction)?,
+            Err(_) => writeln!(out, "last instruction: {:04X}", word)?,
+        }
+        writeln!(
+            out,
+            "PC: {:04X}  I: {:04X}  DT: {:02X}  ST: {:02X}",
+            self.pc, self.i, self.dt, self.st
+        )?;
+        for (n, v) in self.v.iter().enumerate() {
+            let separator = if n % 8 == 7 { "\n" } else { "  " };
+            write!(out, "V{:X}: {:02X}{}", n, v, separator)?;
+        }
+        write!(out, "SP: {:X}  stack:", self.stack.sp())?;
+        for address in &self.stack.memory() {
+            write!(out, " {:04X}", address)?;
+        }
+        writeln!(out)
+    }
+    pub fn write_memory(&self, out: &mut impl Write) -> io::Result<()> {
+        // Same layout as `hexdump -C`: repeated rows are collapsed into "*".
+        let mut previous: Option<&[u8]> = None;
+        let mut collapsed = false;
+        for (row, bytes) in self.ram().chunks(16).enumerate() {
+            if previous == Some(bytes) {
+                if !collapsed {
+                    writeln!(out, "*")?;
+                    collapsed = true;
+                }
+                continue;
+            }
+            write!(out, "{:03X}:", row * 16)?;
+            for byte in bytes {
+                write!(out, " {:02X}", byte)?;
+            }
+            writeln!(out)?;
+            previous = Some(bytes);
+            collapsed = false;
+        }
+        Ok(())
+    }
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            dt: self.dt,
+            st: self.st,
+            sp: self.stack.sp(),
+            stack: self.stack.memory(),
+        }
+    }
+    /// The buzzer sounds for as long as the sound timer is non-zero.
+    pub fn sound_on(&self) -> bool {
+        self.st > 0
+    }
+    /// What the buzzer plays once an XO-CHIP program has loaded a pattern;
+    /// `None` means the usual beep.
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio.map(|bits| AudioPattern {
+            bits,
+            pitch: self.pitch,
+        })
+    }
+    /// The most return addresses the stack has held at once.
+    pub fn peak_stack_depth(&self) -> usize {
+        self.stack.peak()
+    }
+    /// 4KB, or 64KB on XO-CHIP.
+    pub fn ram(&self) -> &[u8] {
+        &self.ram[..self.memory_size()]
+    }
+    /// Overwrites a byte of RAM, for cheats. Addresses wrap like I does.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.ram[self.address(address, 0)] = value;
+    }
+    /// Puts the CPU in the state `registers` describes, for tools that set
+    /// up a machine before running instructions on it. The counterpart of
+    /// `registers`.
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.v = registers.v;
+        self.i = registers.i;
+        self.pc = registers.pc;
+        self.dt = registers.dt;
+        self.st = registers.st;
+        // Clamped, so the stack always takes it.
+        self.stack
+            .set(registers.stack, registers.sp.min(STACK_SIZE));
+    }
+    pub fn set_register(&mut self, register: usize, value: u8) {
+        self.v[register & 0xF] = value;
+    }
+    /// One byte per pixel, row by row: 64x32, or 128x64 in high
+    /// resolution. `display_size` tells which. A pixel is lit when its
+    /// byte isn't zero; on XO-CHIP bit 0 is the first plane and bit 1 the
+    /// second.
+    pub fn display(&self) -> &[u8] {
+        let (width, height) = self.display_size();
+        &self.display[..width * height]
+    }
+    /// The width and height of `display`.
+    pub fn display_size(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (WIDTH, HEIGHT)
+        }
+    }
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+    pub fn tick_timers(&mut self) {
+        let _span = tracing::trace_span!("timers").entered();
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
     }
 }
 impl Architecture {
-    fn cls(self: &mut Self) -> () {
+    fn cls(&mut self) {
         /*    00E0
          *
          *    Clear the display.
+         *
+         *    On XO-CHIP, only the selected planes.
          */
-        self.display = [0u8; 64 * 32];
+        for pixel in self.display.iter_mut() {
+            *pixel &= !self.plane;
+        }
     }
-    fn ret(self: Self) -> () {
+    fn ret(&mut self) {
         /*    00EE
          *
          *    Return from a subroutine.
          *
          *    The interpreter sets the program counter to the address
          *    at the top of the stack, then subtracts 1 from the stack pointer.
+         *
+         *    With nothing on the stack, the machine faults.
          */
-        todo!();
+        match self.stack.pop() {
+            Some(address) => self.pc = address,
+            None => self.raise(|pc| Fault::StackUnderflow { pc }),
+        }
     }
-    fn jp(self: &mut Self, instruction: u16) -> () {
+    fn jp(&mut self, nnn: u16) {
         /*    1nnn
          *
          *    Jump to location nnn.
          *
          *    The interpreter sets the program counter to nnn.
          */
-        self.pc = instruction & 0xFFF;
+        self.pc = nnn;
     }
-    fn call(self: &mut Self, instruction: u16) -> () {
+    fn call(&mut self, nnn: u16) {
         /*    2nnn
          *
          *    Call subroutine at nnn.
@@ -123,12 +700,16 @@ impl Architecture {
          *    The interpreter increments the stack pointer,
          *    then puts the current PC on the top of the stack.
          *    The PC is then set to nnn.
+         *
+         *    With all 16 return addresses in use, the machine faults.
          */
-        self.stack.sp += 1;
-        self.stack.push(self.pc);
-        self.pc = instruction & 0xFFF;
+        if self.stack.push(self.pc) {
+            self.pc = nnn;
+        } else {
+            self.raise(|pc| Fault::StackOverflow { pc });
+        }
     }
-    fn s_e_byte(self: &mut Self, instruction: u16) -> () {
+    fn s_e_byte(&mut self, x: usize, kk: u8) {
         /*   3xkk
          *
          *    Skip next instruction if Vx == kk.
@@ -136,13 +717,11 @@ impl Architecture {
          *    The interpreter compares register Vx to kk,
          *    and if they are equal, increments the program counter by 2.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let kk: u8 = (instruction & 0x00FF).try_into().unwrap();
         if self.v[x] == kk {
-            self.pc += 2;
+            self.skip();
         }
     }
-    fn s_n_e_byte(self: &mut Self, instruction: u16) -> () {
+    fn s_n_e_byte(&mut self, x: usize, kk: u8) {
         /*   4xkk
          *
          *    Skip next instruction if Vx != kk.
@@ -150,13 +729,11 @@ impl Architecture {
          *    The interpreter compares register Vx to kk,
          *    and if they are not equal, increments the program counter by 2.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let kk: u8 = (instruction & 0x00FF).try_into().unwrap();
         if self.v[x] != kk {
-            self.pc += 2;
+            self.skip();
         }
     }
-    fn s_e_register(self: &mut Self, instruction: u16) -> () {
+    fn s_e_register(&mut self, x: usize, y: usize) {
         /*   5xy0
          *
          *    Skip next instruction if Vx == Vy.
@@ -164,27 +741,30 @@ impl Architecture {
          *    The interpreter compares register Vx to register Vy,
          *    and if they are equal, increments the program counter by 2.
          */
-        if (instruction & 0xF) != 0x0 {
-            panic!("OpCode does not exist!")
-        };
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
         if self.v[x] == self.v[y] {
-            self.pc += 2;
+            self.skip();
         }
     }
-    fn load_byte(self: &mut Self, instruction: u16) -> () {
+    /// Steps PC over the next instruction, all four bytes of it if that
+    /// is XO-CHIP's F000 nnnn.
+    fn skip(&mut self) {
+        let size = if self.quirks.xochip && self.word_at(self.pc) == 0xF000 {
+            4
+        } else {
+            2
+        };
+        self.pc = self.address(self.pc, size) as u16;
+    }
+    fn load_byte(&mut self, x: usize, kk: u8) {
         /*   6xkk
          *
          *   Set Vx = kk.
          *
          *   The interpreter puts the value kk into register Vx.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let kk: u8 = (instruction & 0x00FF).try_into().unwrap();
         self.v[x] = kk;
     }
-    fn add_byte(self: &mut Self, instruction: u16) -> () {
+    fn add_byte(&mut self, x: usize, kk: u8) {
         /*   7xkk
          *
          *   Set Vx = Vx + kk.
@@ -192,22 +772,18 @@ impl Architecture {
          *   Adds the value kk to the value of register Vx,
          *   then stores the result in Vx.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let kk: u8 = (instruction & 0x00FF).try_into().unwrap();
-        self.v[x] += kk;
+        self.v[x] = self.v[x].wrapping_add(kk);
     }
-    fn ld(self: &mut Self, instruction: u16) -> () {
+    fn ld(&mut self, x: usize, y: usize) {
         /*   8xy0
          *
          *   Set Vx = Vy.
          *
          *   Stores the value of register Vy in register Vx.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
         self.v[x] = self.v[y];
     }
-    fn or(self: &mut Self, instruction: u16) -> () {
+    fn or(&mut self, x: usize, y: usize) {
         /* 8xy1
          *
          * Set Vx = Vx OR Vy.
@@ -217,11 +793,12 @@ impl Architecture {
          * if either bit is 1, then the same bit in the result is also 1. Otherwise,
          * it is 0.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
-        self.v[x] = self.v[x] | self.v[y];
+        self.v[x] |= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
-    fn and(self: &mut Self, instruction: u16) -> () {
+    fn and(&mut self, x: usize, y: usize) {
         /* 8xy2
          *
          * Set Vx = Vx AND Vy.
@@ -231,11 +808,12 @@ impl Architecture {
          * if if both bits are 1, then the same bit in the result is also 1.
          * Otherwise, it is 0.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
-        self.v[x] = self.v[x] & self.v[y];
+        self.v[x] &= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
-    fn xor(self: &mut Self, instruction: u16) -> () {
+    fn xor(&mut self, x: usize, y: usize) {
         /* 8xy3
          *
          * Set Vx = Vx XOR Vy.
@@ -245,11 +823,12 @@ impl Architecture {
          * two values, and if the bits are not both the same, then the corresponding
          * bit in the result is set to 1. Otherwise, it is 0.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
-        self.v[x] = self.v[x] ^ self.v[y];
+        self.v[x] ^= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
-    fn add(self: &mut Self, instruction: u16) -> () {
+    fn add(&mut self, x: usize, y: usize) {
         /* 8xy4
          *
          * Set Vx = Vx + Vy, set VF = carry.
@@ -257,36 +836,25 @@ impl Architecture {
          * The values of Vx and Vy are added together. If the result is greater than
          * 8 bits (i.e., > 255,) VF is set to 1,
          * otherwise 0. Only the lowest 8 bits of the result are kept,
-         * and stored in Vx.
-         */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
-        let sum: u16 = self.v[x] as u16 + self.v[y] as u16;
-        if sum > 0x0FF {
-            let sum: u8 = (sum >> 1 * 4).try_into().unwrap();
-            self.v[x] = sum;
-            self.v[0xF] = 1;
-        } else {
-            let sum: u8 = sum.try_into().unwrap();
-            self.v[x] = sum;
-            self.v[0xF] = 1;
-        }
+         * and stored in Vx. The flag is written last, so it wins when x is F.
+         */
+        let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+        self.v[x] = sum;
+        self.v[0xF] = carry as u8;
     }
-    fn sub(self: &mut Self, instruction: u16) -> () {
+    fn sub(&mut self, x: usize, y: usize) {
         /* 8xy5
          *
          * Set Vx = Vx - Vy, set VF = NOT borrow.
          *
-         * If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from
+         * If Vx >= Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from
          * Vx, and the results stored in Vx.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        let y: usize = ((instruction & 0x00F0) >> 1 * 4).try_into().unwrap();
-        self.v[0xF] = if self.v[x] > self.v[y] { 1 } else { 0 };
-        let subs: u8 = self.v[x] - self.v[y];
-        self.v[x] = subs;
+        let (difference, borrow) = self.v[x].overflowing_sub(self.v[y]);
+        self.v[x] = difference;
+        self.v[0xF] = !borrow as u8;
     }
-    fn shr(self: &mut Self, instruction: u16) -> () {
+    fn shr(&mut self, x: usize, y: usize) {
         /* 8xy6
          *
          * Set Vx = Vx SHR 1.
@@ -294,21 +862,27 @@ impl Architecture {
          * If the least-significant bit of Vx is 1, then VF is set to 1,
          * otherwise 0. Then Vx is divided by 2.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        self.v[0xF] = self.v[x] & 0x1;
-        self.v[x] >>= 1;
+        let source = if self.quirks.shift_uses_vx {
+            self.v[x]
+        } else {
+            self.v[y]
+        };
+        self.v[x] = source >> 1;
+        self.v[0xF] = source & 0x1;
     }
-    fn subn(self: &mut Self, instruction: u16) -> () {
+    fn subn(&mut self, x: usize, y: usize) {
         /* 8xy7
          *
          * Set Vx = Vy - Vx, set VF = NOT borrow.
          *
-         * If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from
+         * If Vy >= Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from
          * Vy, and the results stored in Vx.
          */
-        self.sub(Hex::swap_hex_digits(instruction, 1, 2));
+        let (difference, borrow) = self.v[y].overflowing_sub(self.v[x]);
+        self.v[x] = difference;
+        self.v[0xF] = !borrow as u8;
     }
-    fn shl(self: &mut Self, instruction: u16) -> () {
+    fn shl(&mut self, x: usize, y: usize) {
         /* 8xy6
          *
          * Set Vx = Vx SHL 1.
@@ -316,60 +890,1051 @@ impl Architecture {
          * If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to
          * 0. Then Vx is multiplied by 2.
          */
-        let x: usize = ((instruction & 0x0F00) >> 2 * 4).try_into().unwrap();
-        self.v[0xF] = self.v[x] >> 7;
-        self.v[x] <<= 1;
+        let source = if self.quirks.shift_uses_vx {
+            self.v[x]
+        } else {
+            self.v[y]
+        };
+        self.v[x] = source << 1;
+        self.v[0xF] = source >> 7;
     }
-    fn s_n_e(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn s_n_e(&mut self, x: usize, y: usize) {
+        /* 9xy0
+         *
+         * Skip next instruction if Vx != Vy.
+         *
+         * The values of Vx and Vy are compared, and if they are not equal, the
+         * program counter is increased by 2.
+         */
+        if self.v[x] != self.v[y] {
+            self.skip();
+        }
+    }
+    fn ld_i(&mut self, nnn: u16) {
+        /* Annn
+         *
+         * Set I = nnn.
+         *
+         * The value of register I is set to nnn.
+         */
+        self.i = nnn;
     }
-    fn ld_i(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn jp_v0(&mut self, nnn: u16) {
+        /* Bnnn
+         *
+         * Jump to location nnn + V0.
+         *
+         * The program counter is set to nnn plus the value of V0. CHIP-48
+         * and SUPER-CHIP read Vx instead, x being the top digit of nnn.
+         */
+        let x = if self.quirks.jump_uses_vx {
+            usize::from(nnn >> 8)
+        } else {
+            0
+        };
+        self.pc = nnn + u16::from(self.v[x]);
     }
-    fn jp_v0(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn rnd(&mut self, x: usize, kk: u8) {
+        /* Cxkk
+         *
+         * Set Vx = random byte AND kk.
+         *
+         * The interpreter generates a random number from 0 to 255, which is then
+         * ANDed with the value kk. The results are stored in Vx.
+         */
+        self.v[x] = self.rng.next_u8() & kk;
     }
-    fn rnd(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn drw(&mut self, x: usize, y: usize, n: u8) {
+        /* Dxyn
+         *
+         * Display n-byte sprite starting at memory location I at (Vx, Vy), set
+         * VF = collision.
+         *
+         * The interpreter reads n bytes from memory, starting at the address
+         * stored in I. These bytes are then displayed as sprites on screen at
+         * coordinates (Vx, Vy). Sprites are XORed onto the existing screen. If
+         * this causes any pixels to be erased, VF is set to 1, otherwise it is
+         * set to 0. The starting position wraps around the screen, and whatever
+         * goes past the edges is clipped, or wraps too with
+         * `Quirks::wrap_sprites`.
+         *
+         * With both XO-CHIP planes selected, the first plane's n bytes are
+         * followed by the second's.
+         */
+        let n = usize::from(n);
+        let mut rows = [0; 32];
+        for (row, bits) in rows.iter_mut().enumerate().take(n * self.planes()) {
+            *bits = u16::from(self.ram[self.address(self.i, row)]) << 8;
+        }
+        self.draw_sprite(x, y, 8, &rows[..n * self.planes()]);
     }
-    fn drw(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    /// How many XO-CHIP planes are selected, each taking a copy of the
+    /// sprite data.
+    fn planes(&self) -> usize {
+        (self.plane & 3).count_ones() as usize
     }
-    fn skp(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    /// XORs a sprite onto each selected plane at (Vx, Vy), each row `width`
+    /// pixels from the top bit down, and sets VF if that erased anything.
+    /// `rows` holds every plane's rows, one plane after the other. The
+    /// coordinates are read before VF changes, since either may be VF.
+    fn draw_sprite(&mut self, x: usize, y: usize, width: usize, rows: &[u16]) {
+        let (display_width, display_height) = self.display_size();
+        let x = usize::from(self.v[x]) % display_width;
+        let y = usize::from(self.v[y]) % display_height;
+        let wrap = self.quirks.wrap_sprites;
+        self.v[0xF] = 0;
+        let height = rows.len().checked_div(self.planes()).unwrap_or(0);
+        let planes = [1, 2].into_iter().filter(|plane| self.plane & plane != 0);
+        for (plane, rows) in planes.zip(rows.chunks(height.max(1))) {
+            for (row, bits) in rows.iter().enumerate() {
+                if y + row >= display_height && !wrap {
+                    break;
+                }
+                for column in 0..width {
+                    if bits & (0x8000 >> column) == 0 || (x + column >= display_width && !wrap) {
+                        continue;
+                    }
+                    let (pixel_x, pixel_y) =
+                        ((x + column) % display_width, (y + row) % display_height);
+                    let pixel = &mut self.display[pixel_y * display_width + pixel_x];
+                    if *pixel & plane != 0 {
+                        self.v[0xF] = 1;
+                    }
+                    *pixel ^= plane;
+                }
+            }
+        }
     }
-    fn sknp(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn skp(&mut self, x: usize) {
+        /* Ex9E
+         *
+         * Skip next instruction if key with the value of Vx is pressed.
+         *
+         * Checks the keyboard, and if the key corresponding to the value of Vx
+         * is currently in the down position, PC is increased by 2.
+         */
+        if self.keys[usize::from(self.v[x] & 0xF)] {
+            self.skip();
+        }
     }
-    fn ld_reg_dt(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn sknp(&mut self, x: usize) {
+        /* ExA1
+         *
+         * Skip next instruction if key with the value of Vx is not pressed.
+         *
+         * Checks the keyboard, and if the key corresponding to the value of Vx
+         * is currently in the up position, PC is increased by 2.
+         */
+        if !self.keys[usize::from(self.v[x] & 0xF)] {
+            self.skip();
+        }
     }
-    fn ld_wait(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_reg_dt(&mut self, x: usize) {
+        /* Fx07
+         *
+         * Set Vx = delay timer value.
+         *
+         * The value of DT is placed into Vx.
+         */
+        self.v[x] = self.dt;
     }
-    fn ld_dt_reg(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_wait(&mut self, x: usize) {
+        /* Fx0A
+         *
+         * Wait for a key press, store the value of the key in Vx.
+         *
+         * All execution stops until a key is pressed, then the value of that key
+         * is stored in Vx. Like the COSMAC VIP, the key is only registered once
+         * it is released again; the timers keep running meanwhile.
+         */
+        self.key_wait = Some(KeyWait {
+            register: x,
+            key: None,
+        });
     }
-    fn ld_st(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_dt_reg(&mut self, x: usize) {
+        /* Fx15
+         *
+         * Set delay timer = Vx.
+         *
+         * DT is set equal to the value of Vx.
+         */
+        self.dt = self.v[x];
     }
-    fn add_i(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_st(&mut self, x: usize) {
+        /* Fx18
+         *
+         * Set sound timer = Vx.
+         *
+         * ST is set equal to the value of Vx.
+         */
+        self.st = self.v[x];
     }
-    fn ld_loc(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn add_i(&mut self, x: usize) {
+        /* Fx1E
+         *
+         * Set I = I + Vx.
+         *
+         * The values of I and Vx are added, and the results are stored in I.
+         * VF is not affected.
+         */
+        self.i = self.i.wrapping_add(u16::from(self.v[x]));
     }
-    fn ld_bcd(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_loc(&mut self, x: usize) {
+        /* Fx29
+         *
+         * Set I = location of sprite for digit Vx.
+         *
+         * The value of I is set to the location for the hexadecimal sprite
+         * corresponding to the value of Vx.
+         */
+        self.i = (FONT_ADDRESS + 5 * usize::from(self.v[x] & 0xF)) as u16;
     }
-    fn store_regs(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn ld_bcd(&mut self, x: usize) {
+        /* Fx33
+         *
+         * Store BCD representation of Vx in memory locations I, I+1, and I+2.
+         *
+         * The interpreter takes the decimal value of Vx, and places the
+         * hundreds digit in memory at location in I, the tens digit at
+         * location I+1, and the ones digit at location I+2.
+         */
+        let value = self.v[x];
+        for (n, digit) in [value / 100, value / 10 % 10, value % 10]
+            .into_iter()
+            .enumerate()
+        {
+            let address = self.address(self.i, n);
+            self.ram[address] = digit;
+        }
     }
-    fn read_regs(self: &mut Self, instruction: u16) -> () {
-        todo!()
+    fn store_regs(&mut self, x: usize) {
+        /* Fx55
+         *
+         * Store registers V0 through Vx in memory starting at location I.
+         *
+         * The interpreter copies the values of registers V0 through Vx into
+         * memory, starting at the address in I. On the COSMAC VIP, I is left
+         * pointing just past the last one.
+         */
+        for n in 0..=x {
+            let address = self.address(self.i, n);
+            self.ram[address] = self.v[n];
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+    fn read_regs(&mut self, x: usize) {
+        /* Fx65
+         *
+         * Read registers V0 through Vx from memory starting at location I.
+         *
+         * The interpreter reads values from memory starting at location I
+         * into registers V0 through Vx. On the COSMAC VIP, I is left
+         * pointing just past the last one.
+         */
+        for n in 0..=x {
+            self.v[n] = self.ram[self.address(self.i, n)];
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+    fn sys(&mut self, _nnn: u16) {
+        /*    0nnn
+         *
+         *    Jump to a machine code routine at nnn.
+         *
+         *    This called machine code on the COSMAC VIP; modern interpreters
+         *    ignore it.
+         */
+    }
+    fn exit(&mut self) {
+        /*    00FD
+         *
+         *    Exit the interpreter. (SUPER-CHIP)
+         *
+         *    Execution stops here; the frontend decides what happens next.
+         */
+        self.halted = true;
+    }
+    fn scroll_down(&mut self, n: u8) {
+        /*    00Cn
+         *
+         *    Scroll the display down n rows. (SUPER-CHIP)
+         *
+         *    Rows of the current resolution, so the picture moves as far
+         *    on screen in high resolution as in low. The rows scrolled in
+         *    at the top are blank.
+         */
+        self.scroll(0, isize::from(n));
+    }
+    fn scroll_up(&mut self, n: u8) {
+        /*    00Dn
+         *
+         *    Scroll the display up n rows. (XO-CHIP)
+         */
+        self.scroll(0, -isize::from(n));
+    }
+    fn scroll_right(&mut self) {
+        /*    00FB
+         *
+         *    Scroll the display right 4 pixels. (SUPER-CHIP)
+         */
+        self.scroll(4, 0);
+    }
+    fn scroll_left(&mut self) {
+        /*    00FC
+         *
+         *    Scroll the display left 4 pixels. (SUPER-CHIP)
+         */
+        self.scroll(-4, 0);
+    }
+    /// Moves the selected planes `dx` pixels right and `dy` down, blanking
+    /// what scrolls in. Planes that aren't selected stay where they are.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.display_size();
+        let before = self.display;
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+                let moved = if (0..width as isize).contains(&from_x)
+                    && (0..height as isize).contains(&from_y)
+                {
+                    before[from_y as usize * width + from_x as usize]
+                } else {
+                    0
+                };
+                let pixel = &mut self.display[y * width + x];
+                *pixel = (*pixel & !self.plane) | (moved & self.plane);
+            }
+        }
+    }
+    fn low(&mut self) {
+        /*    00FE
+         *
+         *    Switch to the 64x32 display. (SUPER-CHIP)
+         *
+         *    Rows are laid out differently at each size, so the display is
+         *    cleared, every plane of it.
+         */
+        self.hires = false;
+        self.display.fill(0);
+    }
+    fn high(&mut self) {
+        /*    00FF
+         *
+         *    Switch to the 128x64 display. (SUPER-CHIP)
+         */
+        self.hires = true;
+        self.display.fill(0);
+    }
+    fn drw_16(&mut self, x: usize, y: usize) {
+        /*    Dxy0
+         *
+         *    Display a 16x16 sprite starting at memory location I at
+         *    (Vx, Vy), set VF = collision. (SUPER-CHIP)
+         *
+         *    Each row is two bytes, left half first, so the sprite takes
+         *    32 bytes, per plane on XO-CHIP. It is drawn like Dxyn otherwise.
+         *    `Quirks::dxy0` can instead make it draw 16 rows one byte
+         *    wide, or nothing at all.
+         */
+        let (width, bytes_per_row) = match self.quirks.dxy0 {
+            Dxy0::Nothing => return self.draw_sprite(x, y, 8, &[]),
+            Dxy0::Tall => (8, 1),
+            Dxy0::Large => (16, 2),
+        };
+        let mut rows = [0; 32];
+        for (row, bits) in rows.iter_mut().enumerate().take(16 * self.planes()) {
+            let address = self.address(self.i, bytes_per_row * row);
+            *bits = if bytes_per_row == 2 {
+                self.word_at(address as u16)
+            } else {
+                u16::from(self.ram[address]) << 8
+            };
+        }
+        self.draw_sprite(x, y, width, &rows[..16 * self.planes()]);
+    }
+    fn ld_big_loc(&mut self, x: usize) {
+        /*    Fx30
+         *
+         *    Set I = location of the large sprite for digit Vx. (SUPER-CHIP)
+         */
+        self.i = (BIG_FONT_ADDRESS + 10 * usize::from(self.v[x] & 0xF)) as u16;
+    }
+    fn save_flags(&mut self, x: usize) {
+        /*    Fx75
+         *
+         *    Store V0 through Vx in the RPL user flags. (SUPER-CHIP)
+         */
+        self.flags[..=x].copy_from_slice(&self.v[..=x]);
+    }
+    fn load_flags(&mut self, x: usize) {
+        /*    Fx85
+         *
+         *    Read V0 through Vx from the RPL user flags. (SUPER-CHIP)
+         */
+        self.v[..=x].copy_from_slice(&self.flags[..=x]);
+    }
+    fn save_range(&mut self, x: usize, y: usize) {
+        /*    5xy2
+         *
+         *    Store Vx through Vy in memory starting at location I.
+         *    (XO-CHIP)
+         *
+         *    Backwards when x is above y. I is left alone.
+         */
+        for n in 0..=x.abs_diff(y) {
+            let register = if x <= y { x + n } else { x - n };
+            let address = self.address(self.i, n);
+            self.ram[address] = self.v[register];
+        }
+    }
+    fn load_range(&mut self, x: usize, y: usize) {
+        /*    5xy3
+         *
+         *    Read Vx through Vy from memory starting at location I.
+         *    (XO-CHIP)
+         *
+         *    Backwards when x is above y. I is left alone.
+         */
+        for n in 0..=x.abs_diff(y) {
+            let register = if x <= y { x + n } else { x - n };
+            self.v[register] = self.ram[self.address(self.i, n)];
+        }
+    }
+    fn ld_i_long(&mut self) {
+        /*    F000 nnnn
+         *
+         *    Set I = nnnn. (XO-CHIP)
+         *
+         *    The only four-byte instruction: the address is the word after
+         *    the opcode, and PC steps over both.
+         */
+        self.i = self.word_at(self.pc);
+        self.pc = self.address(self.pc, 2) as u16;
+    }
+    fn plane(&mut self, x: usize) {
+        /*    Fx01
+         *
+         *    Select the planes to draw on, as a bitmask. (XO-CHIP)
+         *
+         *    1 is the first plane, 2 the second, 3 both and 0 neither.
+         */
+        self.plane = x as u8 & 3;
+    }
+    fn load_audio(&mut self) {
+        /*    F002
+         *
+         *    Load the 16-byte audio pattern at I. (XO-CHIP)
+         */
+        let mut pattern = [0; 16];
+        for (n, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.ram[self.address(self.i, n)];
+        }
+        self.audio = Some(pattern);
+    }
+    fn pitch(&mut self, x: usize) {
+        /*    Fx3A
+         *
+         *    Set the audio pattern's playback pitch = Vx. (XO-CHIP)
+         */
+        self.pitch = self.v[x];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A machine with `words` loaded at 0x200, and the same words ready to
+    /// run as a program.
+    fn load(seed: u64, words: &[u16]) -> (Architecture, Program) {
+        let rom: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        let mut arch = Architecture::with_seed(seed);
+        arch.load_rom(&rom);
+        (arch, Program::new(rom))
+    }
+
+    /// Hands out the given bytes in order, then starts over.
+    #[derive(Debug)]
+    struct Sequence(std::iter::Cycle<std::slice::Iter<'static, u8>>);
+    impl RandomSource for Sequence {
+        fn next_u8(&mut self) -> u8 {
+            *self.0.next().unwrap()
+        }
+    }
+
+    #[test]
+    fn rnd_ands_each_byte_from_the_source_with_kk() {
+        let mut arch = Architecture::with_rng(Sequence([0xAB, 0xFF, 0x00].iter().cycle()));
+        let values: Vec<u8> = (0..4)
+            .map(|_| {
+                arch.rnd(2, 0x0F);
+                arch.v[2]
+            })
+            .collect();
+        assert_eq!(values, [0x0B, 0x0F, 0x00, 0x0B]);
+    }
+
+    #[test]
+    fn rnd_masks_random_byte_with_kk() {
+        let mut arch = Architecture::with_seed(0xC8);
+        for _ in 0..256 {
+            arch.rnd(3, 0x0F);
+            assert_eq!(arch.v[3] & !0x0F, 0);
+        }
+        arch.rnd(3, 0x00);
+        assert_eq!(arch.v[3], 0);
+    }
+
+    #[test]
+    fn rnd_with_full_mask_is_not_constant() {
+        let mut arch = Architecture::with_seed(1);
+        let values: Vec<u8> = (0..16)
+            .map(|_| {
+                arch.rnd(0, 0xFF);
+                arch.v[0]
+            })
+            .collect();
+        assert!(values.iter().any(|&v| v != values[0]));
+    }
+
+    #[test]
+    fn ld_wait_registers_key_on_release() {
+        let (mut arch, _) = load(0, &[0xF30A, 0x6001]);
+        arch.execute();
+        assert!(arch.waiting_for_key());
+        arch.execute();
+        assert_eq!(arch.v[0], 0);
+
+        arch.set_key(0x7, true);
+        assert!(arch.waiting_for_key());
+        arch.set_key(0x5, false);
+        assert!(arch.waiting_for_key());
+        arch.set_key(0x7, false);
+        assert!(!arch.waiting_for_key());
+        assert_eq!(arch.v[3], 0x7);
+
+        arch.execute();
+        assert_eq!(arch.v[0], 1);
     }
 
-    fn exit() -> () {
-        process::exit(0);
+    #[test]
+    fn ld_wait_ignores_keys_held_before_the_wait() {
+        let (mut arch, _) = load(0, &[0xF10A]);
+        arch.set_key(0x2, true);
+        arch.execute();
+        arch.set_key(0x2, false);
+        assert!(arch.waiting_for_key());
+    }
+
+    #[test]
+    fn queued_keys_apply_at_the_next_frame() {
+        let mut arch = Architecture::with_seed(0);
+        let event = |key, pressed| KeyEvent { key, pressed };
+        arch.queue_key(event(0x5, true));
+        arch.queue_key(event(0x9, true));
+        assert!(!arch.keys[0x5]);
+        arch.run_frame(&Program::new(vec![0x60, 0x00]), 0, |_, _, _| true);
+        assert!(arch.keys[0x5] && arch.keys[0x9]);
+    }
+
+    #[test]
+    fn tap_within_one_frame_holds_the_key_for_a_frame() {
+        let mut arch = Architecture::with_seed(0);
+        let event = |key, pressed| KeyEvent { key, pressed };
+        arch.queue_key(event(0x5, true));
+        arch.queue_key(event(0x5, false));
+        arch.run_frame(&Program::new(vec![0x60, 0x00]), 0, |_, _, _| true);
+        assert!(arch.keys[0x5]);
+        arch.run_frame(&Program::new(vec![0x60, 0x00]), 0, |_, _, _| true);
+        assert!(!arch.keys[0x5]);
+    }
+
+    #[test]
+    fn chorded_keys_are_held_independently() {
+        let mut arch = Architecture::with_seed(0);
+        let event = |key, pressed| KeyEvent { key, pressed };
+        arch.queue_key(event(0x4, true));
+        arch.queue_key(event(0x6, true));
+        arch.queue_key(event(0x2, true));
+        arch.run_frame(&Program::new(vec![0x60, 0x00]), 0, |_, _, _| true);
+        arch.queue_key(event(0x6, false));
+        arch.run_frame(&Program::new(vec![0x60, 0x00]), 0, |_, _, _| true);
+        let held: Vec<usize> = (0..16).filter(|&k| arch.keys[k]).collect();
+        assert_eq!(held, [0x2, 0x4]);
+    }
+
+    #[test]
+    fn ld_wait_takes_the_first_key_of_a_chord() {
+        let (mut arch, rom) = load(0, &[0xF10A, 0x6000]);
+        let event = |key, pressed| KeyEvent { key, pressed };
+        arch.run_frame(&rom, 1, |_, _, _| true);
+        arch.queue_key(event(0x3, true));
+        arch.queue_key(event(0x7, true));
+        arch.run_frame(&rom, 0, |_, _, _| true);
+        arch.queue_key(event(0x7, false));
+        arch.run_frame(&rom, 0, |_, _, _| true);
+        assert!(arch.waiting_for_key());
+        arch.queue_key(event(0x3, false));
+        arch.run_frame(&rom, 0, |_, _, _| true);
+        assert!(!arch.waiting_for_key());
+        assert_eq!(arch.v[1], 0x3);
+    }
+
+    #[test]
+    fn fused_pairs_run_like_single_steps() {
+        // LD V2 + ADD I at 202, SE + JP at 208; 20E jumps to itself.
+        let words = [
+            0x6000, 0x6203, 0xF21E, 0x7001, 0x3010, 0x1200, 0x6000, 0x120E,
+        ];
+        let (mut fused, program) = load(0, &words);
+        let (mut stepped, _) = load(0, &words);
+        let (mut fused_pcs, mut stepped_pcs) = (Vec::new(), Vec::new());
+        for _ in 0..40 {
+            fused.run_frame(&program, 7, |_, pc, _| {
+                fused_pcs.push(pc);
+                true
+            });
+            for _ in 0..7 {
+                stepped_pcs.push(stepped.pc);
+                stepped.execute();
+            }
+            stepped.tick_timers();
+        }
+        assert_eq!(fused_pcs, stepped_pcs);
+        assert_eq!(fused.registers(), stepped.registers());
+    }
+
+    #[test]
+    fn stopping_inside_a_fused_pair_skips_its_second_half() {
+        let (mut arch, program) = load(0, &[0x6203, 0xF21E]);
+        arch.run_frame(&program, 2, |_, _, _| false);
+        assert_eq!((arch.pc, arch.v[2], arch.i), (0x202, 3, 0));
+    }
+
+    #[test]
+    fn rewritten_code_runs_instead_of_the_decoded_rom() {
+        // LD V2 + ADD I, with the load rewritten to LD V3.
+        let (mut arch, program) = load(0, &[0x6203, 0xF21E]);
+        arch.poke(0x200, 0x63);
+        arch.run_frame(&program, 2, |_, _, _| true);
+        assert_eq!((arch.v[2], arch.v[3], arch.i), (0, 3, 0));
+    }
+
+    #[test]
+    fn a_fused_pair_at_the_top_of_xochip_memory_wraps_to_zero() {
+        // LD V2 + ADD I at FFFC, the last instruction pair in RAM.
+        let mut rom = vec![0; 0xFFFC - LOAD_ADDRESS];
+        rom.extend([0x62, 0x03, 0xF2, 0x1E]);
+        let mut arch = Architecture::with_seed(0);
+        arch.set_quirks(Quirks::preset(Platform::XoChip));
+        arch.load_rom(&rom);
+        arch.pc = 0xFFFC;
+        let mut pcs = Vec::new();
+        arch.run_frame(&Program::new(rom), 2, |_, pc, _| {
+            pcs.push(pc);
+            true
+        });
+        assert_eq!(pcs, [0xFFFC, 0xFFFE]);
+        assert_eq!((arch.pc, arch.v[2], arch.i), (0, 3, 3));
+    }
+
+    #[test]
+    fn after_sees_each_instruction_once_it_has_run() {
+        let (mut arch, program) = load(0, &[0x6001, 0x6002, 0x6003]);
+        let mut seen = Vec::new();
+        arch.run_frame(&program, 3, |arch, pc, _| {
+            seen.push((pc, arch.registers().v[0]));
+            true
+        });
+        assert_eq!(seen, [(0x200, 1), (0x202, 2), (0x204, 3)]);
+    }
+
+    #[test]
+    fn rpl_flags_survive_a_register_clear() {
+        // Stores V0-V2 in the flags, clears them, then reads them back.
+        let words = [
+            0x6011, 0x6122, 0x6233, 0xF275, 0x6000, 0x6100, 0x6200, 0xF285,
+        ];
+        let (mut arch, program) = load(0, &words);
+        arch.set_quirks(Quirks::preset(Platform::Schip));
+        arch.run_frame(&program, words.len(), |_, _, _| true);
+        assert_eq!(arch.v[..4], [0x11, 0x22, 0x33, 0]);
+        assert_eq!(arch.flags[..4], [0x11, 0x22, 0x33, 0]);
+    }
+
+    #[test]
+    fn quirk_settings_apply_on_top_of_a_preset() {
+        let vip = Quirks::preset(Platform::Vip);
+        let quirks = vip.with("jump_uses_vx=true").unwrap();
+        assert!(quirks.jump_uses_vx && quirks.vf_reset);
+        assert_eq!(vip.with(" dxy0 = 8x16").unwrap().dxy0, Dxy0::Tall);
+        for setting in ["vf_reset", "vf_reset=yes", "turbo=true", "dxy0=8x8"] {
+            assert_eq!(vip.with(setting), None, "{}", setting);
+        }
+    }
+
+    #[test]
+    fn both_planes_take_their_own_sprite_rows() {
+        // PLANE 3, then a one-row sprite: 0x80 on the first plane and
+        // 0xC0 on the second.
+        let words = [0xF301, 0xA300, 0xD011];
+        let (mut arch, program) = load(0, &words);
+        arch.set_quirks(Quirks::preset(Platform::XoChip));
+        arch.poke(0x300, 0x80);
+        arch.poke(0x301, 0xC0);
+        arch.run_frame(&program, words.len(), |_, _, _| true);
+        assert_eq!(arch.display()[..3], [3, 2, 0]);
+    }
+
+    #[test]
+    fn audio_pattern_and_pitch_survive_a_save_state() {
+        // LD I, LONG 0300, AUDIO, LD V1, 70, PITCH V1.
+        let words = [0xF000, 0x0300, 0xF002, 0x6170, 0xF13A];
+        let (mut arch, program) = load(0, &words);
+        arch.set_quirks(Quirks::preset(Platform::XoChip));
+        arch.poke(0x30F, 0x5A);
+        assert_eq!(arch.audio_pattern(), None);
+        arch.run_frame(&program, 4, |_, _, _| true);
+        let pattern = arch.audio_pattern().unwrap();
+        assert_eq!((pattern.bits[15], pattern.pitch), (0x5A, 0x70));
+        let restored = Architecture::load_state(&arch.save_state()).unwrap();
+        assert_eq!(restored.audio_pattern(), Some(pattern));
+    }
+
+    #[test]
+    fn a_halted_xochip_machine_survives_a_save_state() {
+        // LD I, LONG FFF0, then RET with nothing to return to.
+        let words = [0xF000, 0xFFF0, 0x00EE];
+        let (mut arch, program) = load(0, &words);
+        arch.set_quirks(Quirks::preset(Platform::XoChip));
+        arch.poke(0xFFF0, 0xC8);
+        assert_eq!(arch.run_frame(&program, 4, |_, _, _| true), Outcome::Halted);
+        let restored = Architecture::load_state(&arch.save_state()).unwrap();
+        assert!(restored.halted());
+        assert_eq!(restored.fault(), Some(Fault::StackUnderflow { pc: 0x204 }));
+        assert_eq!(restored.quirks(), Quirks::preset(Platform::XoChip));
+        assert_eq!(
+            (restored.ram().len(), restored.ram()[0xFFF0]),
+            (XO_RAM_SIZE, 0xC8)
+        );
+        assert_eq!(restored.save_state(), arch.save_state());
+    }
+
+    #[test]
+    fn random_numbers_carry_on_after_a_save_state() {
+        let mut arch = Architecture::with_seed(0xC8);
+        arch.rnd(0, 0xFF);
+        let mut restored = Architecture::load_state(&arch.save_state()).unwrap();
+        for _ in 0..8 {
+            arch.rnd(1, 0xFF);
+            restored.rnd(1, 0xFF);
+            assert_eq!(restored.v[1], arch.v[1]);
+        }
+    }
+
+    #[test]
+    fn programs_can_rewrite_their_own_code() {
+        // Stores 6305 (LD V3, 05) over the LD V2 at 208, then runs it.
+        let (mut arch, program) = load(0, &[0x6063, 0x6105, 0xA208, 0xF155, 0x6201]);
+        arch.run_frame(&program, 5, |_, _, _| true);
+        assert_eq!((arch.v[2], arch.v[3]), (0, 5));
+    }
+
+    #[test]
+    fn frames_blocked_on_a_key_or_a_self_jump_are_idle() {
+        let (mut arch, busy) = load(0, &[0x7001, 0x1FFF]);
+        assert_eq!(arch.run_frame(&busy, 1, |_, _, _| true), Outcome::Running);
+
+        let (mut arch, waiting) = load(0, &[0xF00A]);
+        assert_eq!(arch.run_frame(&waiting, 10, |_, _, _| true), Outcome::Idle);
+
+        let (mut arch, spinning) = load(0, &[0x6000, 0x1202]);
+        assert_eq!(arch.run_frame(&spinning, 10, |_, _, _| true), Outcome::Idle);
+    }
+
+    #[test]
+    fn exit_halts_the_machine() {
+        let (mut arch, _) = load(0, &[0x6001, 0x00FD, 0x6002]);
+        assert_eq!(arch.execute(), Outcome::Running);
+        assert_eq!(arch.execute(), Outcome::Halted);
+        assert_eq!(arch.execute(), Outcome::Halted);
+        assert_eq!(arch.v[0], 1);
+    }
+
+    #[test]
+    fn shift_quirk_selects_source_register() {
+        let mut arch = Architecture::with_seed(0);
+        arch.v[1] = 0b0000_0100;
+        arch.v[2] = 0b1000_0001;
+        arch.shr(1, 2);
+        assert_eq!((arch.v[1], arch.v[0xF]), (0b0000_0010, 0));
+
+        arch.set_quirks(Quirks::preset(Platform::Vip));
+        arch.shr(1, 2);
+        assert_eq!((arch.v[1], arch.v[0xF]), (0b0100_0000, 1));
+        arch.shl(1, 2);
+        assert_eq!((arch.v[1], arch.v[0xF]), (0b0000_0010, 1));
+    }
+
+    #[test]
+    fn vf_reset_quirk_clears_flag_after_logic_ops() {
+        let mut arch = Architecture::with_seed(0);
+        arch.v[0xF] = 1;
+        arch.or(0, 1);
+        assert_eq!(arch.v[0xF], 1);
+
+        arch.set_quirks(Quirks::preset(Platform::Vip));
+        arch.and(0, 1);
+        assert_eq!(arch.v[0xF], 0);
+    }
+
+    #[test]
+    fn machines_run_independently_on_threads() {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let (mut arch, _) = load(7, &[0x6000, 0xC0FF, 0x7101, 0x1200]);
+                    for _ in 0..300 {
+                        arch.execute();
+                    }
+                    arch.v
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert!(results.iter().all(|v| *v == results[0]));
+    }
+
+    #[test]
+    fn decode_names_operands_and_rejects_unassigned_opcodes() {
+        let decoded = Instruction::decode(0xD12F);
+        assert_eq!(decoded, Ok(Instruction::Drw { x: 1, y: 2, n: 0xF }));
+        assert_eq!(decoded.unwrap().to_string(), "DRW V1, V2, F");
+        for word in [0x5001, 0x800F, 0x9AB1, 0xE19F, 0xF0FF] {
+            assert!(Instruction::decode(word).is_err());
+        }
+    }
+
+    /// Every instruction with every operand, built without `decode`.
+    fn every_instruction() -> Vec<Instruction> {
+        let mut all = vec![
+            Instruction::Cls,
+            Instruction::Ret,
+            Instruction::Exit,
+            Instruction::Scr,
+            Instruction::Scl,
+            Instruction::Low,
+            Instruction::High,
+            Instruction::LdILong,
+            Instruction::Audio,
+        ];
+        all.extend((0..16).map(|n| Instruction::Scd { n }));
+        all.extend((0..16).map(|n| Instruction::Scu { n }));
+        let addresses: [fn(u16) -> Instruction; 5] = [
+            |nnn| Instruction::Sys { nnn },
+            |nnn| Instruction::Jp { nnn },
+            |nnn| Instruction::Call { nnn },
+            |nnn| Instruction::LdI { nnn },
+            |nnn| Instruction::JpV0 { nnn },
+        ];
+        for make in addresses {
+            all.extend((0..0x1000).map(make));
+        }
+        let bytes: [fn(usize, u8) -> Instruction; 5] = [
+            |x, kk| Instruction::SeByte { x, kk },
+            |x, kk| Instruction::SneByte { x, kk },
+            |x, kk| Instruction::LdByte { x, kk },
+            |x, kk| Instruction::AddByte { x, kk },
+            |x, kk| Instruction::Rnd { x, kk },
+        ];
+        for make in bytes {
+            all.extend((0..16).flat_map(|x| (0..=0xFF).map(move |kk| make(x, kk))));
+        }
+        let pairs: [fn(usize, usize) -> Instruction; 13] = [
+            |x, y| Instruction::SeReg { x, y },
+            |x, y| Instruction::SaveRange { x, y },
+            |x, y| Instruction::LoadRange { x, y },
+            |x, y| Instruction::Ld { x, y },
+            |x, y| Instruction::Or { x, y },
+            |x, y| Instruction::And { x, y },
+            |x, y| Instruction::Xor { x, y },
+            |x, y| Instruction::Add { x, y },
+            |x, y| Instruction::Sub { x, y },
+            |x, y| Instruction::Shr { x, y },
+            |x, y| Instruction::Subn { x, y },
+            |x, y| Instruction::Shl { x, y },
+            |x, y| Instruction::SneReg { x, y },
+        ];
+        for make in pairs {
+            all.extend((0..16).flat_map(|x| (0..16).map(move |y| make(x, y))));
+        }
+        all.extend((0..16).flat_map(|x| {
+            (0..16).flat_map(move |y| (1..16).map(move |n| Instruction::Drw { x, y, n }))
+        }));
+        all.extend((0..16).flat_map(|x| (0..16).map(move |y| Instruction::Drw16 { x, y })));
+        let singles: [fn(usize) -> Instruction; 16] = [
+            |x| Instruction::Skp { x },
+            |x| Instruction::Sknp { x },
+            |x| Instruction::LdRegDt { x },
+            |x| Instruction::LdWait { x },
+            |x| Instruction::LdDtReg { x },
+            |x| Instruction::LdSt { x },
+            |x| Instruction::AddI { x },
+            |x| Instruction::LdF { x },
+            |x| Instruction::LdB { x },
+            |x| Instruction::StoreRegs { x },
+            |x| Instruction::ReadRegs { x },
+            |x| Instruction::LdHf { x },
+            |x| Instruction::SaveFlags { x },
+            |x| Instruction::LoadFlags { x },
+            |x| Instruction::Plane { x },
+            |x| Instruction::Pitch { x },
+        ];
+        for make in singles {
+            all.extend((0..16).map(make));
+        }
+        // SYS can't name the addresses of the special 00__ instructions.
+        all.retain(|&instruction| {
+            !matches!(
+                instruction,
+                Instruction::Sys {
+                    nnn: 0x0C0..=0x0DF | 0x0E0 | 0x0EE | 0x0FB..=0x0FF
+                }
+            )
+        });
+        all
+    }
+
+    #[test]
+    fn decode_inverts_encode() {
+        for instruction in every_instruction() {
+            assert_eq!(Instruction::decode(instruction.encode()), Ok(instruction));
+        }
+    }
+
+    #[test]
+    fn execute_fetches_past_the_last_word_of_ram_before_running_it() {
+        // LD V1, 05 in the last word of RAM.
+        let mut arch = Architecture::with_seed(0);
+        arch.pc = 0xFFE;
+        arch.ram[0xFFE..0x1000].copy_from_slice(&[0x61, 0x05]);
+        assert_eq!(arch.execute(), Outcome::Running);
+        assert_eq!((arch.pc, arch.v[1]), (0x000, 0x05));
+    }
+
+    #[test]
+    fn encode_inverts_decode() {
+        let mut valid = 0;
+        for word in 0..=u16::MAX {
+            if let Ok(instruction) = Instruction::decode(word) {
+                assert_eq!(instruction.encode(), word, "{}", instruction);
+                valid += 1;
+            }
+        }
+        assert_eq!(valid, every_instruction().len());
+    }
+
+    #[test]
+    fn mnemonics_assemble_back_to_their_opcodes() {
+        for instruction in every_instruction() {
+            let rom = crate::asm::assemble(&instruction.to_string()).unwrap();
+            assert_eq!(rom, instruction.encode().to_be_bytes(), "{}", instruction);
+        }
+    }
+
+    #[test]
+    fn rnd_is_reproducible_for_a_seed() {
+        let mut a = Architecture::with_seed(42);
+        let mut b = Architecture::with_seed(42);
+        for _ in 0..32 {
+            a.rnd(5, 0xFF);
+            b.rnd(5, 0xFF);
+            assert_eq!(a.v[5], b.v[5]);
+        }
+    }
+
+    #[test]
+    fn ret_with_an_empty_stack_faults_at_the_ret() {
+        let (mut arch, program) = load(0, &[0x6001, 0x00EE, 0x6002]);
+        let outcome = arch.run_frame(&program, 10, |_, _, _| true);
+        assert_eq!(outcome, Outcome::Halted);
+        assert_eq!(arch.fault(), Some(Fault::StackUnderflow { pc: 0x202 }));
+        assert_eq!((arch.pc, arch.v[0]), (0x202, 1));
+        assert_eq!(arch.execute(), Outcome::Halted);
+    }
+
+    /// A main program that calls routine 0, which calls routine 1, and so
+    /// on `depth` deep. Each routine adds 1 to V0 on its way back, and the
+    /// main program spins at 202 once they have all returned.
+    fn nested_calls(depth: u16) -> Vec<u16> {
+        let routine = |k: u16| 0x204 + 6 * k;
+        let mut words = vec![0x2000 | routine(0), 0x1202];
+        for k in 0..depth {
+            let call = if k + 1 < depth {
+                0x2000 | routine(k + 1)
+            } else {
+                0x6101
+            };
+            words.extend([call, 0x7001, 0x00EE]);
+        }
+        words
+    }
+
+    #[test]
+    fn calls_nest_sixteen_deep_and_unwind_in_order() {
+        let (mut arch, program) = load(0, &nested_calls(STACK_SIZE as u16));
+        arch.run_frame(&program, 1000, |_, _, _| true);
+        assert_eq!(arch.fault(), None);
+        assert_eq!(arch.peak_stack_depth(), STACK_SIZE);
+        assert_eq!(
+            (arch.pc, arch.stack.sp(), arch.v[0]),
+            (0x202, 0, STACK_SIZE as u8)
+        );
+    }
+
+    #[test]
+    fn a_call_past_sixteen_deep_faults_at_the_call() {
+        let (mut arch, program) = load(0, &nested_calls(STACK_SIZE as u16 + 1));
+        let outcome = arch.run_frame(&program, 1000, |_, _, _| true);
+        assert_eq!(outcome, Outcome::Halted);
+        let deepest = 0x204 + 6 * (STACK_SIZE as u16 - 1);
+        assert_eq!(arch.fault(), Some(Fault::StackOverflow { pc: deepest }));
+        assert_eq!((arch.pc, arch.stack.sp()), (deepest, STACK_SIZE));
+    }
+
+    #[test]
+    fn ret_returns_past_the_call() {
+        let (mut arch, program) = load(0, &[0x2206, 0x6101, 0x1204, 0x00EE]);
+        arch.run_frame(&program, 4, |_, _, _| true);
+        assert_eq!((arch.pc, arch.stack.sp(), arch.v[1]), (0x204, 0, 1));
+    }
+
+    #[test]
+    fn an_unknown_opcode_faults_instead_of_panicking() {
+        // F030, the large font, doesn't exist without SUPER-CHIP.
+        let (mut arch, program) = load(0, &[0x6001, 0xF030, 0x6002]);
+        arch.set_quirks(Quirks::preset(Platform::Vip));
+        assert_eq!(
+            arch.run_frame(&program, 10, |_, _, _| true),
+            Outcome::Halted
+        );
+        let fault = Fault::InvalidOpcode {
+            pc: 0x202,
+            word: 0xF030,
+        };
+        assert_eq!((arch.fault(), arch.pc, arch.v[0]), (Some(fault), 0x202, 1));
+        let (mut arch, _) = load(0, &[0xFFFF]);
+        assert_eq!(arch.execute(), Outcome::Halted);
+        let fault = Fault::InvalidOpcode {
+            pc: 0x200,
+            word: 0xFFFF,
+        };
+        assert_eq!((arch.fault(), arch.pc), (Some(fault), 0x200));
+        assert_eq!(arch.state(), MachineState::Faulted(fault));
     }
 }
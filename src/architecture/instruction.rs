@@ -0,0 +1,332 @@
+use super::utils::Hex;
+use super::{Architecture, Platform, Quirks};
+use std::fmt;
+
+/// The type each operand decodes to, and how far up the opcode it sits.
+/// Operand names match the `Hex` helpers that extract them.
+macro_rules! operand_type {
+    (x) => {
+        usize
+    };
+    (y) => {
+        usize
+    };
+    (n) => {
+        u8
+    };
+    (kk) => {
+        u8
+    };
+    (nnn) => {
+        u16
+    };
+}
+macro_rules! operand_shift {
+    (x) => {
+        8
+    };
+    (y) => {
+        4
+    };
+    (n) => {
+        0
+    };
+    (kk) => {
+        0
+    };
+    (nnn) => {
+        0
+    };
+}
+
+/// Generates `Instruction` and everything that needs one arm per opcode
+/// (decoding, encoding, mnemonics and the interpreter's dispatch) from a
+/// single table. Each row is
+///
+/// ```text
+/// Variant { operands } = "pattern", "mnemonic", handler, platforms;
+/// ```
+///
+/// where the pattern is written as in Cowgod's reference, the mnemonic is
+/// a format string over the operands, and the handler is the
+/// `Architecture` method that runs it. Rows are tried in order, so
+/// specific opcodes must come before the patterns that would catch them.
+macro_rules! opcodes {
+    ($(
+        $variant:ident $({ $($operand:ident),* })?
+            = $pattern:literal, $mnemonic:literal, $handler:ident, $platforms:expr;
+    )*) => {
+        /// A decoded CHIP-8 instruction, named after Cowgod's mnemonics.
+        /// Register operands are indices into V0-VF.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Instruction {
+            $(
+                #[doc = $pattern]
+                $variant $({ $($operand: operand_type!($operand)),* })?,
+            )*
+        }
+
+        impl Instruction {
+            /// Every row's pattern and mnemonic, in table order, for going
+            /// from text back to opcodes.
+            pub const SYNTAX: &'static [(&'static str, &'static str)] =
+                &[$(($pattern, $mnemonic)),*];
+
+            pub fn decode(op: u16) -> Result<Instruction, DecodeError> {
+                $({
+                    const PATTERN: (u16, u16) = pattern($pattern);
+                    if op & PATTERN.0 == PATTERN.1 {
+                        return Ok(Instruction::$variant $({ $($operand: Hex::$operand(op)),* })?);
+                    }
+                })*
+                Err(DecodeError(op))
+            }
+
+            /// The opcode for this instruction; the inverse of `decode`.
+            /// Register operands must be below 16 and `nnn` below 0x1000.
+            pub fn encode(self) -> u16 {
+                match self {
+                    $(
+                        Instruction::$variant $({ $($operand),* })? => {
+                            pattern($pattern).1
+                                $($(| (($operand as u16) << operand_shift!($operand)))*)?
+                        }
+                    )*
+                }
+            }
+
+            /// The interpreters that have this instruction, which `lint`
+            /// reports when the ROM's platform isn't known. A machine goes
+            /// by its quirks instead, through `with_quirks`.
+            pub fn platforms(self) -> &'static [Platform] {
+                match self {
+                    $(Instruction::$variant { .. } => $platforms,)*
+                }
+            }
+        }
+
+        /// Cowgod-style mnemonic, with operands in hex.
+        impl fmt::Display for Instruction {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $(Instruction::$variant $({ $($operand),* })? => write!(f, $mnemonic),)*
+                }
+            }
+        }
+
+        impl Architecture {
+            /// Runs the handler for a decoded instruction.
+            pub(super) fn dispatch(&mut self, instruction: Instruction) {
+                match instruction {
+                    $(
+                        Instruction::$variant $({ $($operand),* })? => {
+                            self.$handler($($($operand),*)?)
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+/// Mask and value for a pattern like `8xy4`: hex digits must match, while
+/// `x`, `y`, `n` and `k` stand for operand nibbles.
+const fn pattern(text: &str) -> (u16, u16) {
+    let text = text.as_bytes();
+    assert!(text.len() == 4, "opcode patterns are four nibbles");
+    let (mut mask, mut value) = (0, 0);
+    let mut i = 0;
+    while i < 4 {
+        mask <<= 4;
+        value <<= 4;
+        match text[i] {
+            b'x' | b'y' | b'n' | b'k' => {}
+            c @ b'0'..=b'9' => (mask, value) = (mask | 0xF, value | (c - b'0') as u16),
+            c @ b'A'..=b'F' => (mask, value) = (mask | 0xF, value | (c - b'A' + 10) as u16),
+            _ => panic!("opcode patterns are hex digits and x, y, n or k"),
+        }
+        i += 1;
+    }
+    (mask, value)
+}
+
+const ALL: &[Platform] = &[
+    Platform::Vip,
+    Platform::Chip48,
+    Platform::Schip,
+    Platform::XoChip,
+    Platform::Modern,
+];
+const SCHIP: &[Platform] = &[Platform::Schip, Platform::XoChip, Platform::Modern];
+/// SUPER-CHIP's additions that other interpreters don't have, which only
+/// run with `Quirks::superchip`.
+const SCHIP_ONLY: &[Platform] = &[Platform::Schip, Platform::XoChip];
+/// XO-CHIP's additions, which only run with `Quirks::xochip`.
+const XO_CHIP: &[Platform] = &[Platform::XoChip];
+
+opcodes! {
+    Cls = "00E0", "CLS", cls, ALL;
+    Ret = "00EE", "RET", ret, ALL;
+    Exit = "00FD", "EXIT", exit, SCHIP;
+    Scd { n } = "00Cn", "SCD {n:X}", scroll_down, SCHIP_ONLY;
+    Scu { n } = "00Dn", "SCU {n:X}", scroll_up, XO_CHIP;
+    Scr = "00FB", "SCR", scroll_right, SCHIP_ONLY;
+    Scl = "00FC", "SCL", scroll_left, SCHIP_ONLY;
+    Low = "00FE", "LOW", low, SCHIP_ONLY;
+    High = "00FF", "HIGH", high, SCHIP_ONLY;
+    Sys { nnn } = "0nnn", "SYS {nnn:03X}", sys, ALL;
+    Jp { nnn } = "1nnn", "JP {nnn:03X}", jp, ALL;
+    Call { nnn } = "2nnn", "CALL {nnn:03X}", call, ALL;
+    SeByte { x, kk } = "3xkk", "SE V{x:X}, {kk:02X}", s_e_byte, ALL;
+    SneByte { x, kk } = "4xkk", "SNE V{x:X}, {kk:02X}", s_n_e_byte, ALL;
+    SeReg { x, y } = "5xy0", "SE V{x:X}, V{y:X}", s_e_register, ALL;
+    SaveRange { x, y } = "5xy2", "LD [I], V{x:X}-V{y:X}", save_range, XO_CHIP;
+    LoadRange { x, y } = "5xy3", "LD V{x:X}-V{y:X}, [I]", load_range, XO_CHIP;
+    LdByte { x, kk } = "6xkk", "LD V{x:X}, {kk:02X}", load_byte, ALL;
+    AddByte { x, kk } = "7xkk", "ADD V{x:X}, {kk:02X}", add_byte, ALL;
+    Ld { x, y } = "8xy0", "LD V{x:X}, V{y:X}", ld, ALL;
+    Or { x, y } = "8xy1", "OR V{x:X}, V{y:X}", or, ALL;
+    And { x, y } = "8xy2", "AND V{x:X}, V{y:X}", and, ALL;
+    Xor { x, y } = "8xy3", "XOR V{x:X}, V{y:X}", xor, ALL;
+    Add { x, y } = "8xy4", "ADD V{x:X}, V{y:X}", add, ALL;
+    Sub { x, y } = "8xy5", "SUB V{x:X}, V{y:X}", sub, ALL;
+    Shr { x, y } = "8xy6", "SHR V{x:X}, V{y:X}", shr, ALL;
+    Subn { x, y } = "8xy7", "SUBN V{x:X}, V{y:X}", subn, ALL;
+    Shl { x, y } = "8xyE", "SHL V{x:X}, V{y:X}", shl, ALL;
+    SneReg { x, y } = "9xy0", "SNE V{x:X}, V{y:X}", s_n_e, ALL;
+    LdI { nnn } = "Annn", "LD I, {nnn:03X}", ld_i, ALL;
+    JpV0 { nnn } = "Bnnn", "JP V0, {nnn:03X}", jp_v0, ALL;
+    Rnd { x, kk } = "Cxkk", "RND V{x:X}, {kk:02X}", rnd, ALL;
+    Drw16 { x, y } = "Dxy0", "DRW V{x:X}, V{y:X}, 0", drw_16, SCHIP_ONLY;
+    Drw { x, y, n } = "Dxyn", "DRW V{x:X}, V{y:X}, {n:X}", drw, ALL;
+    Skp { x } = "Ex9E", "SKP V{x:X}", skp, ALL;
+    Sknp { x } = "ExA1", "SKNP V{x:X}", sknp, ALL;
+    LdILong = "F000", "LD I, LONG", ld_i_long, XO_CHIP;
+    Audio = "F002", "AUDIO", load_audio, XO_CHIP;
+    Plane { x } = "Fx01", "PLANE {x:X}", plane, XO_CHIP;
+    LdRegDt { x } = "Fx07", "LD V{x:X}, DT", ld_reg_dt, ALL;
+    LdWait { x } = "Fx0A", "LD V{x:X}, K", ld_wait, ALL;
+    LdDtReg { x } = "Fx15", "LD DT, V{x:X}", ld_dt_reg, ALL;
+    LdSt { x } = "Fx18", "LD ST, V{x:X}", ld_st, ALL;
+    AddI { x } = "Fx1E", "ADD I, V{x:X}", add_i, ALL;
+    LdF { x } = "Fx29", "LD F, V{x:X}", ld_loc, ALL;
+    LdHf { x } = "Fx30", "LD HF, V{x:X}", ld_big_loc, SCHIP_ONLY;
+    LdB { x } = "Fx33", "LD B, V{x:X}", ld_bcd, ALL;
+    Pitch { x } = "Fx3A", "PITCH V{x:X}", pitch, XO_CHIP;
+    StoreRegs { x } = "Fx55", "LD [I], V{x:X}", store_regs, ALL;
+    ReadRegs { x } = "Fx65", "LD V{x:X}, [I]", read_regs, ALL;
+    SaveFlags { x } = "Fx75", "LD R, V{x:X}", save_flags, SCHIP_ONLY;
+    LoadFlags { x } = "Fx85", "LD V{x:X}, R", load_flags, SCHIP_ONLY;
+}
+
+/// A word that isn't any instruction this interpreter knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError(pub u16);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.0)
+    }
+}
+
+impl Instruction {
+    /// Runs this instruction on `arch` as though it had just been fetched
+    /// from PC, without reading RAM for it: PC moves past it first, then
+    /// the opcode's handler runs under the machine's quirks. It is the
+    /// same path `run_frame` takes, so tools built on it (mutation
+    /// testers, superoptimizers, visualizers) get the interpreter's own
+    /// semantics.
+    ///
+    /// ```
+    /// use chip_n_claw::{Chip8, Instruction};
+    ///
+    /// let mut chip8 = Chip8::new();
+    /// Instruction::LdByte { x: 3, kk: 0x2A }.execute(&mut chip8);
+    /// Instruction::AddByte { x: 3, kk: 0x01 }.execute(&mut chip8);
+    /// assert_eq!(chip8.registers().v[3], 0x2B);
+    /// assert_eq!(chip8.pc(), 0x204);
+    /// ```
+    ///
+    /// If the quirks leave the machine without the instruction, as they do
+    /// XO-CHIP's additions unless `Quirks::xochip` is set, it halts with
+    /// [`Fault::InvalidOpcode`](crate::Fault::InvalidOpcode) at the
+    /// instruction instead.
+    pub fn execute(self, arch: &mut Architecture) {
+        arch.step(self);
+    }
+
+    /// The address operand of the instructions that take one.
+    pub fn address(self) -> Option<u16> {
+        match self {
+            Instruction::Sys { nnn }
+            | Instruction::Jp { nnn }
+            | Instruction::Call { nnn }
+            | Instruction::LdI { nnn }
+            | Instruction::JpV0 { nnn } => Some(nnn),
+            _ => None,
+        }
+    }
+
+    /// What a machine with `quirks` makes of this, as `without_superchip`
+    /// and `without_xochip` say.
+    pub fn with_quirks(self, quirks: Quirks) -> Option<Instruction> {
+        let mut instruction = Some(self);
+        if !quirks.xochip {
+            instruction = instruction.and_then(Instruction::without_xochip);
+        }
+        if !quirks.superchip {
+            instruction = instruction.and_then(Instruction::without_superchip);
+        }
+        instruction
+    }
+
+    /// What an interpreter without SUPER-CHIP's additions makes of this:
+    /// the 00__ ones are SYS calls, which do nothing. `None` for the ones
+    /// it doesn't know at all. Dxy0 is left to `Quirks::dxy0`.
+    pub fn without_superchip(self) -> Option<Instruction> {
+        match self {
+            Instruction::Scd { .. }
+            | Instruction::Scr
+            | Instruction::Scl
+            | Instruction::Low
+            | Instruction::High => Some(Instruction::Sys {
+                nnn: self.encode() & 0xFFF,
+            }),
+            Instruction::LdHf { .. }
+            | Instruction::SaveFlags { .. }
+            | Instruction::LoadFlags { .. } => None,
+            _ => Some(self),
+        }
+    }
+
+    /// What an interpreter without XO-CHIP's additions makes of this: 00Dn
+    /// is a SYS call, and it doesn't know the rest. Other instructions
+    /// are unchanged.
+    pub fn without_xochip(self) -> Option<Instruction> {
+        match self {
+            Instruction::Scu { .. } => Some(Instruction::Sys {
+                nnn: self.encode() & 0xFFF,
+            }),
+            Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LdILong
+            | Instruction::Audio
+            | Instruction::Plane { .. }
+            | Instruction::Pitch { .. } => None,
+            _ => Some(self),
+        }
+    }
+
+    /// True for the instructions that may skip the one after them.
+    pub fn is_skip(self) -> bool {
+        matches!(
+            self,
+            Instruction::SeByte { .. }
+                | Instruction::SneByte { .. }
+                | Instruction::SeReg { .. }
+                | Instruction::SneReg { .. }
+                | Instruction::Skp { .. }
+                | Instruction::Sknp { .. }
+        )
+    }
+}
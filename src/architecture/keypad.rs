@@ -0,0 +1,57 @@
+/// A key on the hex keypad going down or up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEvent {
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Events held between frames. Well beyond what a player can type in one
+/// frame; anything past it is dropped.
+const CAPACITY: usize = 64;
+
+/// Key events waiting for the next frame boundary, oldest first. It has a
+/// fixed size, so queuing a key never allocates.
+#[derive(Debug, Clone)]
+pub struct KeyQueue {
+    events: [KeyEvent; CAPACITY],
+    start: usize,
+    len: usize,
+}
+impl KeyQueue {
+    pub fn new() -> Self {
+        KeyQueue {
+            events: [KeyEvent {
+                key: 0,
+                pressed: false,
+            }; CAPACITY],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, event: KeyEvent) {
+        if self.len < CAPACITY {
+            self.events[(self.start + self.len) % CAPACITY] = event;
+            self.len += 1;
+        }
+    }
+
+    /// Hands `apply` the events for this frame boundary, in order. It stops
+    /// at a second event for a key that already changed, which waits for
+    /// the next frame, so a press and release arriving together still
+    /// leave the key down for one frame.
+    pub fn drain_frame(&mut self, mut apply: impl FnMut(KeyEvent)) {
+        let mut changed = [false; 16];
+        while self.len > 0 {
+            let event = self.events[self.start];
+            let key = usize::from(event.key & 0xF);
+            if changed[key] {
+                break;
+            }
+            changed[key] = true;
+            apply(event);
+            self.start = (self.start + 1) % CAPACITY;
+            self.len -= 1;
+        }
+    }
+}
@@ -0,0 +1,1449 @@
+use super::{
+    display_size, Architecture, Dxy0, Platform, Quirks, BIG_FONT_ADDRESS, FONT_ADDRESS, HEIGHT,
+    HIRES_HEIGHT, HIRES_WIDTH, WIDTH,
+};
+use std::panic::{self, AssertUnwindSafe};
+
+/// PC units taken by one instruction.
+const INSTRUCTION: u16 = 2;
+
+/// Where PC should be after the instruction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Pc {
+    #[default]
+    Next,
+    Skip,
+    At(u16),
+}
+
+/// Machine state a case starts from or ends in. Fields left out are zero
+/// before and unchanged after.
+#[derive(Default)]
+struct State {
+    v: &'static [(usize, u8)],
+    i: Option<u16>,
+    dt: Option<u8>,
+    st: Option<u8>,
+    /// Only checked afterwards.
+    pc: Pc,
+    stack: Option<&'static [u16]>,
+    /// Only used before.
+    keys: &'static [u8],
+    /// Afterwards, only the listed addresses are checked.
+    ram: &'static [(usize, u8)],
+    /// Lit pixels as (x, y).
+    pixels: Option<&'static [(usize, usize)]>,
+    /// The 128x64 display. Unlike the rest, it has to be repeated
+    /// afterwards if it shouldn't change.
+    hires: bool,
+    /// The selected XO-CHIP planes; the first one if left out before.
+    plane: Option<u8>,
+    /// Only checked afterwards.
+    waiting: bool,
+}
+
+#[derive(Default)]
+struct Case {
+    name: &'static str,
+    quirks: Quirks,
+    before: State,
+    instruction: u16,
+    after: State,
+}
+
+impl Case {
+    fn setup(&self) -> Architecture {
+        let mut arch = Architecture::with_seed(0);
+        arch.load_rom(&self.instruction.to_be_bytes());
+        arch.set_quirks(self.quirks);
+        let before = &self.before;
+        for &(x, value) in before.v {
+            arch.v[x] = value;
+        }
+        arch.i = before.i.unwrap_or(0);
+        arch.dt = before.dt.unwrap_or(0);
+        arch.st = before.st.unwrap_or(0);
+        for &address in before.stack.unwrap_or(&[]) {
+            assert!(arch.stack.push(address));
+        }
+        for &key in before.keys {
+            arch.keys[usize::from(key)] = true;
+        }
+        for &(address, byte) in before.ram {
+            arch.ram[address] = byte;
+        }
+        arch.hires = before.hires;
+        arch.plane = before.plane.unwrap_or(1);
+        let (width, _) = size(before.hires);
+        for &(x, y) in before.pixels.unwrap_or(&[]) {
+            arch.display[y * width + x] = 1;
+        }
+        arch
+    }
+
+    /// Runs the instruction and lists every way the result differs from
+    /// `after`.
+    fn run(&self) -> Vec<String> {
+        let initial = self.setup();
+        let mut arch = self.setup();
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| arch.execute())) {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            return vec![format!("panicked: {}", message)];
+        }
+
+        let after = &self.after;
+        let mut errors = Vec::new();
+        let mut check = |what: &str, expected: String, actual: String| {
+            if expected != actual {
+                errors.push(format!("{}: expected {}, got {}", what, expected, actual));
+            }
+        };
+        let mut v = initial.v;
+        for &(x, value) in after.v {
+            v[x] = value;
+        }
+        check("V", format!("{:02X?}", v), format!("{:02X?}", arch.v));
+        let i = after.i.unwrap_or(initial.i);
+        check("I", format!("{:#05X}", i), format!("{:#05X}", arch.i));
+        let dt = after.dt.unwrap_or(initial.dt);
+        check("DT", dt.to_string(), arch.dt.to_string());
+        let st = after.st.unwrap_or(initial.st);
+        check("ST", st.to_string(), arch.st.to_string());
+        let pc = match after.pc {
+            Pc::Next => initial.pc + INSTRUCTION,
+            Pc::Skip => initial.pc + 2 * INSTRUCTION,
+            Pc::At(address) => address,
+        };
+        check("PC", format!("{:#05X}", pc), format!("{:#05X}", arch.pc));
+        let stack = match after.stack {
+            Some(stack) => stack.to_vec(),
+            None => initial.stack.memory()[..initial.stack.sp()].to_vec(),
+        };
+        let actual_stack = arch.stack.memory()[..arch.stack.sp()].to_vec();
+        check(
+            "stack",
+            format!("{:03X?}", stack),
+            format!("{:03X?}", actual_stack),
+        );
+        for &(address, byte) in after.ram {
+            let what = format!("RAM[{:#05X}]", address);
+            check(&what, byte.to_string(), arch.ram[address].to_string());
+        }
+        check("hires", after.hires.to_string(), arch.hires.to_string());
+        let plane = after.plane.unwrap_or(initial.plane);
+        check("plane", plane.to_string(), arch.plane.to_string());
+        let display = match after.pixels {
+            Some(pixels) => {
+                let (width, height) = size(after.hires);
+                let mut display = vec![0; width * height];
+                for &(x, y) in pixels {
+                    display[y * width + x] = 1;
+                }
+                display
+            }
+            None => initial.display().to_vec(),
+        };
+        check("lit pixels", lit(&display), lit(arch.display()));
+        let waiting = arch.waiting_for_key();
+        check("waiting", after.waiting.to_string(), waiting.to_string());
+        errors
+    }
+}
+
+fn size(hires: bool) -> (usize, usize) {
+    if hires {
+        (HIRES_WIDTH, HIRES_HEIGHT)
+    } else {
+        (WIDTH, HEIGHT)
+    }
+}
+
+fn lit(display: &[u8]) -> String {
+    let (width, _) = display_size(display);
+    let pixels: Vec<(usize, usize)> = (0..display.len())
+        .filter(|&p| display[p] != 0)
+        .map(|p| (p % width, p / width))
+        .collect();
+    format!("{:?}", pixels)
+}
+
+fn run_all(cases: Vec<Case>) {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let failures: Vec<String> = cases
+        .iter()
+        .flat_map(|case| {
+            let errors = case.run();
+            errors
+                .into_iter()
+                .map(move |e| format!("{}: {}", case.name, e))
+        })
+        .collect();
+    panic::set_hook(hook);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+fn vip() -> Quirks {
+    Quirks::preset(Platform::Vip)
+}
+
+fn schip() -> Quirks {
+    Quirks::preset(Platform::Schip)
+}
+
+fn xochip() -> Quirks {
+    Quirks::preset(Platform::XoChip)
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "SYS is ignored",
+            instruction: 0x0123,
+            ..Default::default()
+        },
+        Case {
+            name: "CLS clears every pixel",
+            before: State {
+                pixels: Some(&[(0, 0), (63, 31)]),
+                ..Default::default()
+            },
+            instruction: 0x00E0,
+            after: State {
+                pixels: Some(&[]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "RET",
+            before: State {
+                stack: Some(&[0x010]),
+                ..Default::default()
+            },
+            instruction: 0x00EE,
+            after: State {
+                pc: Pc::At(0x010),
+                stack: Some(&[]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "CALL addr",
+            instruction: 0x2123,
+            after: State {
+                pc: Pc::At(0x123),
+                stack: Some(&[0x202]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SE Vx, byte not taken",
+            before: State {
+                v: &[(0x3, 0x41)],
+                ..Default::default()
+            },
+            instruction: 0x3342,
+            ..Default::default()
+        },
+        Case {
+            name: "SNE Vx, byte not taken",
+            before: State {
+                v: &[(0x3, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0x43FF,
+            ..Default::default()
+        },
+        Case {
+            name: "SE Vx, Vy not taken",
+            before: State {
+                v: &[(0x1, 0x01), (0x2, 0x02)],
+                ..Default::default()
+            },
+            instruction: 0x5120,
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, byte",
+            instruction: 0x6542,
+            after: State {
+                v: &[(0x5, 0x42)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD VF, byte",
+            instruction: 0x6FFF,
+            after: State {
+                v: &[(0xF, 0xFF)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, byte",
+            before: State {
+                v: &[(0xA, 0x10)],
+                ..Default::default()
+            },
+            instruction: 0x7A05,
+            after: State {
+                v: &[(0xA, 0x15)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, byte wraps without touching VF",
+            before: State {
+                v: &[(0xA, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0x7A01,
+            after: State {
+                v: &[(0xA, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, Vy",
+            before: State {
+                v: &[(0x2, 0x99)],
+                ..Default::default()
+            },
+            instruction: 0x8120,
+            after: State {
+                v: &[(0x1, 0x99)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, Vx",
+            before: State {
+                v: &[(0x1, 0x99)],
+                ..Default::default()
+            },
+            instruction: 0x8110,
+            ..Default::default()
+        },
+        Case {
+            name: "OR Vx, Vy",
+            before: State {
+                v: &[(0x1, 0xF0), (0x2, 0x0F), (0xF, 0x07)],
+                ..Default::default()
+            },
+            instruction: 0x8121,
+            after: State {
+                v: &[(0x1, 0xFF)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "OR Vx, Vy resets VF on the VIP",
+            quirks: vip(),
+            before: State {
+                v: &[(0x1, 0xF0), (0x2, 0x0F), (0xF, 0x07)],
+                ..Default::default()
+            },
+            instruction: 0x8121,
+            after: State {
+                v: &[(0x1, 0xFF), (0xF, 0x00)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "AND Vx, Vy",
+            before: State {
+                v: &[(0x1, 0xF3), (0x2, 0x3F)],
+                ..Default::default()
+            },
+            instruction: 0x8122,
+            after: State {
+                v: &[(0x1, 0x33)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "XOR Vx, Vy",
+            before: State {
+                v: &[(0x1, 0xF3), (0x2, 0x3F)],
+                ..Default::default()
+            },
+            instruction: 0x8123,
+            after: State {
+                v: &[(0x1, 0xCC)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "XOR Vx, Vx clears it",
+            before: State {
+                v: &[(0x1, 0xA5)],
+                ..Default::default()
+            },
+            instruction: 0x8113,
+            after: State {
+                v: &[(0x1, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, Vy without carry",
+            before: State {
+                v: &[(0x1, 0x10), (0x2, 0x20), (0xF, 0x05)],
+                ..Default::default()
+            },
+            instruction: 0x8124,
+            after: State {
+                v: &[(0x1, 0x30), (0xF, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, Vy with carry",
+            before: State {
+                v: &[(0x1, 0xFF), (0x2, 0x02)],
+                ..Default::default()
+            },
+            instruction: 0x8124,
+            after: State {
+                v: &[(0x1, 0x01), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, Vy at exactly 256",
+            before: State {
+                v: &[(0x1, 0x80), (0x2, 0x80)],
+                ..Default::default()
+            },
+            instruction: 0x8124,
+            after: State {
+                v: &[(0x1, 0x00), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD VF, Vy keeps the flag",
+            before: State {
+                v: &[(0x1, 0x01), (0xF, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0x8F14,
+            after: State {
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD Vx, VF reads VF before the flag",
+            before: State {
+                v: &[(0x1, 0x10), (0xF, 0x20)],
+                ..Default::default()
+            },
+            instruction: 0x81F4,
+            after: State {
+                v: &[(0x1, 0x30), (0xF, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUB Vx, Vy without borrow",
+            before: State {
+                v: &[(0x1, 0x30), (0x2, 0x10)],
+                ..Default::default()
+            },
+            instruction: 0x8125,
+            after: State {
+                v: &[(0x1, 0x20), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUB Vx, Vy with borrow",
+            before: State {
+                v: &[(0x1, 0x10), (0x2, 0x30), (0xF, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0x8125,
+            after: State {
+                v: &[(0x1, 0xE0), (0xF, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUB Vx, Vx does not borrow",
+            before: State {
+                v: &[(0x1, 0x42)],
+                ..Default::default()
+            },
+            instruction: 0x8115,
+            after: State {
+                v: &[(0x1, 0x00), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUB VF, Vy keeps the flag",
+            before: State {
+                v: &[(0x1, 0x01), (0xF, 0x05)],
+                ..Default::default()
+            },
+            instruction: 0x8F15,
+            after: State {
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SHR Vx",
+            before: State {
+                v: &[(0x1, 0x05)],
+                ..Default::default()
+            },
+            instruction: 0x8106,
+            after: State {
+                v: &[(0x1, 0x02), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SHR Vx shifts Vy in on the VIP",
+            quirks: vip(),
+            before: State {
+                v: &[(0x1, 0x05), (0x2, 0x80)],
+                ..Default::default()
+            },
+            instruction: 0x8126,
+            after: State {
+                v: &[(0x1, 0x40), (0xF, 0x00)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SHR VF keeps the flag",
+            before: State {
+                v: &[(0xF, 0x03)],
+                ..Default::default()
+            },
+            instruction: 0x8F06,
+            after: State {
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUBN Vx, Vy without borrow",
+            before: State {
+                v: &[(0x1, 0x10), (0x2, 0x30)],
+                ..Default::default()
+            },
+            instruction: 0x8127,
+            after: State {
+                v: &[(0x1, 0x20), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUBN Vx, Vy with borrow",
+            before: State {
+                v: &[(0x1, 0x30), (0x2, 0x10)],
+                ..Default::default()
+            },
+            instruction: 0x8127,
+            after: State {
+                v: &[(0x1, 0xE0), (0xF, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SUBN VF, Vy keeps the flag",
+            before: State {
+                v: &[(0x1, 0x05), (0xF, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0x8F17,
+            after: State {
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SHL Vx",
+            before: State {
+                v: &[(0x1, 0x81)],
+                ..Default::default()
+            },
+            instruction: 0x810E,
+            after: State {
+                v: &[(0x1, 0x02), (0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SHL Vx shifts Vy in on the VIP",
+            quirks: vip(),
+            before: State {
+                v: &[(0x1, 0x81), (0x2, 0x40)],
+                ..Default::default()
+            },
+            instruction: 0x812E,
+            after: State {
+                v: &[(0x1, 0x80), (0xF, 0x00)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SHL VF keeps the flag",
+            before: State {
+                v: &[(0xF, 0x80)],
+                ..Default::default()
+            },
+            instruction: 0x8F0E,
+            after: State {
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SNE Vx, Vy not taken",
+            before: State {
+                v: &[(0x1, 0x07), (0x2, 0x07)],
+                ..Default::default()
+            },
+            instruction: 0x9120,
+            ..Default::default()
+        },
+        Case {
+            name: "LD I, addr",
+            instruction: 0xAFFF,
+            after: State {
+                i: Some(0xFFF),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "RND Vx, 00",
+            before: State {
+                v: &[(0x3, 0x55)],
+                ..Default::default()
+            },
+            instruction: 0xC300,
+            after: State {
+                v: &[(0x3, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW draws a sprite",
+            before: State {
+                v: &[(0x0, 2), (0x1, 3)],
+                i: Some(0x300),
+                ram: &[(0x300, 0xC0), (0x301, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0xD012,
+            after: State {
+                pixels: Some(&[(2, 3), (3, 3), (9, 4)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW reports collisions",
+            before: State {
+                v: &[(0x0, 2), (0x1, 3)],
+                i: Some(0x300),
+                ram: &[(0x300, 0xC0)],
+                pixels: Some(&[(2, 3), (40, 20)]),
+                ..Default::default()
+            },
+            instruction: 0xD011,
+            after: State {
+                v: &[(0xF, 0x01)],
+                pixels: Some(&[(3, 3), (40, 20)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW clips at the edges",
+            before: State {
+                v: &[(0x0, 63), (0x1, 31)],
+                i: Some(0x300),
+                ram: &[(0x300, 0xFF), (0x301, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0xD012,
+            after: State {
+                pixels: Some(&[(63, 31)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW wraps the starting position",
+            before: State {
+                v: &[(0x0, 66), (0x1, 33)],
+                i: Some(0x300),
+                ram: &[(0x300, 0x80)],
+                ..Default::default()
+            },
+            instruction: 0xD011,
+            after: State {
+                pixels: Some(&[(2, 1)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW at VF, VF reads the coordinate first",
+            before: State {
+                v: &[(0xF, 5)],
+                i: Some(0x300),
+                ram: &[(0x300, 0x80)],
+                ..Default::default()
+            },
+            instruction: 0xDFF1,
+            after: State {
+                v: &[(0xF, 0x00)],
+                pixels: Some(&[(5, 5)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW with no rows draws nothing",
+            before: State {
+                pixels: Some(&[(0, 0)]),
+                v: &[(0xF, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0xD000,
+            after: State {
+                v: &[(0xF, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "DRW Vx, Vy, 0 can draw an 8x16 sprite",
+            quirks: Quirks {
+                dxy0: Dxy0::Tall,
+                ..Quirks::default()
+            },
+            before: State {
+                v: &[(0x0, 2), (0x1, 1)],
+                i: Some(0x300),
+                ram: &[(0x300, 0x80), (0x301, 0x40), (0x30F, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0xD010,
+            after: State {
+                v: &[(0xF, 0x00)],
+                pixels: Some(&[(2, 1), (3, 2), (9, 16)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SKP Vx not taken",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0xB],
+                ..Default::default()
+            },
+            instruction: 0xE49E,
+            ..Default::default()
+        },
+        Case {
+            name: "SKNP Vx not taken",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0xA],
+                ..Default::default()
+            },
+            instruction: 0xE4A1,
+            ..Default::default()
+        },
+        Case {
+            name: "SKP Vx not taken while other keys are held",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0x0, 0x9, 0xB, 0xF],
+                ..Default::default()
+            },
+            instruction: 0xE49E,
+            ..Default::default()
+        },
+        Case {
+            name: "SKNP Vx not taken as part of a chord",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0x1, 0xA, 0xF],
+                ..Default::default()
+            },
+            instruction: 0xE4A1,
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, DT",
+            before: State {
+                dt: Some(0x42),
+                ..Default::default()
+            },
+            instruction: 0xF307,
+            after: State {
+                v: &[(0x3, 0x42)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, K starts waiting",
+            instruction: 0xF30A,
+            after: State {
+                waiting: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD DT, Vx",
+            before: State {
+                v: &[(0x3, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0xF315,
+            after: State {
+                dt: Some(0xFF),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD ST, Vx",
+            before: State {
+                v: &[(0x3, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0xF318,
+            after: State {
+                st: Some(0x01),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "ADD I, Vx leaves VF alone",
+            before: State {
+                v: &[(0x3, 0x20)],
+                i: Some(0xFF0),
+                ..Default::default()
+            },
+            instruction: 0xF31E,
+            after: State {
+                i: Some(0x1010),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "JP addr",
+            instruction: 0x1123,
+            after: State {
+                pc: Pc::At(0x123),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SE Vx, byte taken",
+            before: State {
+                v: &[(0x3, 0xFF)],
+                ..Default::default()
+            },
+            instruction: 0x33FF,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SNE Vx, byte taken",
+            instruction: 0x4301,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SE Vx, Vx taken",
+            before: State {
+                v: &[(0x1, 0x42)],
+                ..Default::default()
+            },
+            instruction: 0x5110,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SNE Vx, Vy taken",
+            before: State {
+                v: &[(0x1, 0x01)],
+                ..Default::default()
+            },
+            instruction: 0x9120,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "JP V0, addr",
+            before: State {
+                v: &[(0x0, 0x10)],
+                ..Default::default()
+            },
+            instruction: 0xB200,
+            after: State {
+                pc: Pc::At(0x210),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "JP V0, addr adds Vx on SUPER-CHIP",
+            quirks: schip(),
+            before: State {
+                v: &[(0x0, 0x01), (0x2, 0x10)],
+                ..Default::default()
+            },
+            instruction: 0xB230,
+            after: State {
+                pc: Pc::At(0x240),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SKP Vx taken",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0xA],
+                ..Default::default()
+            },
+            instruction: 0xE49E,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SKNP Vx taken",
+            before: State {
+                v: &[(0x4, 0xA)],
+                ..Default::default()
+            },
+            instruction: 0xE4A1,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SKP Vx taken as part of a chord",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0x1, 0xA, 0xF],
+                ..Default::default()
+            },
+            instruction: 0xE49E,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "SKNP Vx taken while other keys are held",
+            before: State {
+                v: &[(0x4, 0xA)],
+                keys: &[0x0, 0x9, 0xB, 0xF],
+                ..Default::default()
+            },
+            instruction: 0xE4A1,
+            after: State {
+                pc: Pc::Skip,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD F, Vx",
+            before: State {
+                v: &[(0x3, 0xA)],
+                ..Default::default()
+            },
+            instruction: 0xF329,
+            after: State {
+                i: Some(FONT_ADDRESS as u16 + 5 * 0xA),
+                ram: &[
+                    (0x32, 0xF0),
+                    (0x33, 0x90),
+                    (0x34, 0xF0),
+                    (0x35, 0x90),
+                    (0x36, 0x90),
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD B, Vx",
+            before: State {
+                v: &[(0x3, 254)],
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0xF333,
+            after: State {
+                ram: &[(0x300, 2), (0x301, 5), (0x302, 4)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD [I], Vx",
+            before: State {
+                v: &[(0x0, 0x11), (0x1, 0x22), (0x2, 0x33), (0x3, 0x44)],
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0xF255,
+            after: State {
+                ram: &[(0x300, 0x11), (0x301, 0x22), (0x302, 0x33), (0x303, 0x00)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, [I]",
+            before: State {
+                i: Some(0x300),
+                ram: &[(0x300, 0x11), (0x301, 0x22), (0x302, 0x33), (0x303, 0x44)],
+                ..Default::default()
+            },
+            instruction: 0xF265,
+            after: State {
+                v: &[(0x0, 0x11), (0x1, 0x22), (0x2, 0x33)],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "LD [I], Vx advances I on the VIP",
+            quirks: vip(),
+            before: State {
+                v: &[(0x0, 0x11), (0x1, 0x22)],
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0xF155,
+            after: State {
+                i: Some(0x302),
+                ram: &[(0x300, 0x11), (0x301, 0x22)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD Vx, [I] advances I on the VIP",
+            quirks: vip(),
+            before: State {
+                i: Some(0x300),
+                ram: &[(0x300, 0x11), (0x301, 0x22)],
+                ..Default::default()
+            },
+            instruction: 0xF165,
+            after: State {
+                v: &[(0x0, 0x11), (0x1, 0x22)],
+                i: Some(0x302),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SCD scrolls down n rows",
+            quirks: schip(),
+            before: State {
+                pixels: Some(&[(1, 0), (5, 31)]),
+                ..Default::default()
+            },
+            instruction: 0x00C2,
+            after: State {
+                pixels: Some(&[(1, 2)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SCR scrolls right 4 pixels",
+            quirks: schip(),
+            before: State {
+                pixels: Some(&[(0, 1), (62, 1)]),
+                ..Default::default()
+            },
+            instruction: 0x00FB,
+            after: State {
+                pixels: Some(&[(4, 1)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SCL scrolls left 4 pixels",
+            quirks: schip(),
+            before: State {
+                hires: true,
+                pixels: Some(&[(1, 1), (127, 63)]),
+                ..Default::default()
+            },
+            instruction: 0x00FC,
+            after: State {
+                hires: true,
+                pixels: Some(&[(123, 63)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LOW leaves high resolution and clears",
+            quirks: schip(),
+            before: State {
+                hires: true,
+                pixels: Some(&[(100, 50)]),
+                ..Default::default()
+            },
+            instruction: 0x00FE,
+            after: State {
+                pixels: Some(&[]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "HIGH switches to 128x64 and clears",
+            quirks: schip(),
+            before: State {
+                pixels: Some(&[(0, 0)]),
+                ..Default::default()
+            },
+            instruction: 0x00FF,
+            after: State {
+                hires: true,
+                pixels: Some(&[]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "HIGH is a SYS call without SUPER-CHIP",
+            before: State {
+                pixels: Some(&[(0, 0)]),
+                ..Default::default()
+            },
+            instruction: 0x00FF,
+            ..Default::default()
+        },
+        Case {
+            name: "DRW Vx, Vy, 0 draws a clipped 16x16 sprite",
+            quirks: schip(),
+            before: State {
+                hires: true,
+                v: &[(0x0, 120), (0x1, 0)],
+                i: Some(0x300),
+                ram: &[(0x300, 0x80), (0x301, 0x01), (0x31E, 0x80)],
+                pixels: Some(&[(120, 0)]),
+                ..Default::default()
+            },
+            instruction: 0xD010,
+            after: State {
+                hires: true,
+                v: &[(0xF, 0x01)],
+                pixels: Some(&[(120, 15)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD HF, Vx",
+            quirks: schip(),
+            before: State {
+                v: &[(0x3, 0x9)],
+                ..Default::default()
+            },
+            instruction: 0xF330,
+            after: State {
+                i: Some(BIG_FONT_ADDRESS as u16 + 10 * 0x9),
+                ram: &[(BIG_FONT_ADDRESS + 90, 0x3C), (BIG_FONT_ADDRESS + 99, 0x7C)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD R, Vx leaves the registers alone",
+            quirks: schip(),
+            before: State {
+                v: &[(0x0, 0x11), (0x1, 0x22)],
+                ..Default::default()
+            },
+            instruction: 0xF175,
+            ..Default::default()
+        },
+        Case {
+            name: "LD Vx, R reads unset flags as zero",
+            quirks: schip(),
+            before: State {
+                v: &[(0x0, 0x11), (0x1, 0x22), (0x2, 0x33)],
+                ..Default::default()
+            },
+            instruction: 0xF185,
+            after: State {
+                v: &[(0x0, 0x00), (0x1, 0x00)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SCU scrolls up n rows",
+            quirks: xochip(),
+            before: State {
+                pixels: Some(&[(1, 3), (5, 0)]),
+                ..Default::default()
+            },
+            instruction: 0x00D2,
+            after: State {
+                pixels: Some(&[(1, 1)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "SCU is a SYS call without XO-CHIP",
+            quirks: schip(),
+            before: State {
+                pixels: Some(&[(1, 3)]),
+                ..Default::default()
+            },
+            instruction: 0x00D2,
+            ..Default::default()
+        },
+        Case {
+            name: "CLS leaves the planes that aren't selected",
+            quirks: xochip(),
+            before: State {
+                plane: Some(2),
+                pixels: Some(&[(3, 4)]),
+                ..Default::default()
+            },
+            instruction: 0x00E0,
+            ..Default::default()
+        },
+        Case {
+            name: "SE Vx, byte skips all of F000 nnnn",
+            quirks: xochip(),
+            before: State {
+                ram: &[(0x202, 0xF0), (0x203, 0x00)],
+                ..Default::default()
+            },
+            instruction: 0x3000,
+            after: State {
+                pc: Pc::At(0x206),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD [I], Vx-Vy",
+            quirks: xochip(),
+            before: State {
+                v: &[(0x1, 0x11), (0x2, 0x22), (0x3, 0x33)],
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0x5132,
+            after: State {
+                ram: &[(0x300, 0x11), (0x301, 0x22), (0x302, 0x33), (0x303, 0x00)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD [I], Vx-Vy stores backwards when x is above y",
+            quirks: xochip(),
+            before: State {
+                v: &[(0x1, 0x11), (0x2, 0x22), (0x3, 0x33)],
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0x5312,
+            after: State {
+                ram: &[(0x300, 0x33), (0x301, 0x22), (0x302, 0x11)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD Vx-Vy, [I]",
+            quirks: xochip(),
+            before: State {
+                i: Some(0x300),
+                ram: &[(0x300, 0xAA), (0x301, 0xBB)],
+                ..Default::default()
+            },
+            instruction: 0x5453,
+            after: State {
+                v: &[(0x4, 0xAA), (0x5, 0xBB)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "DRW wraps sprites around the edges on XO-CHIP",
+            quirks: xochip(),
+            before: State {
+                v: &[(0x0, 62), (0x1, 31)],
+                i: Some(0x300),
+                ram: &[(0x300, 0xC0), (0x301, 0xA0)],
+                ..Default::default()
+            },
+            instruction: 0xD012,
+            after: State {
+                v: &[(0xF, 0x00)],
+                pixels: Some(&[(0, 0), (62, 0), (62, 31), (63, 31)]),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD I, LONG",
+            quirks: xochip(),
+            before: State {
+                ram: &[(0x202, 0x12), (0x203, 0x34)],
+                ..Default::default()
+            },
+            instruction: 0xF000,
+            after: State {
+                i: Some(0x1234),
+                pc: Pc::At(0x204),
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "LD [I], Vx reaches past 4KB on XO-CHIP",
+            quirks: xochip(),
+            before: State {
+                v: &[(0x0, 0xAB)],
+                i: Some(0x8000),
+                ..Default::default()
+            },
+            instruction: 0xF055,
+            after: State {
+                i: Some(0x8001),
+                ram: &[(0x8000, 0xAB)],
+                ..Default::default()
+            },
+        },
+        Case {
+            name: "PLANE n",
+            quirks: xochip(),
+            instruction: 0xF201,
+            after: State {
+                plane: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        Case {
+            name: "AUDIO leaves the registers alone",
+            quirks: xochip(),
+            before: State {
+                i: Some(0x300),
+                ..Default::default()
+            },
+            instruction: 0xF002,
+            ..Default::default()
+        },
+        Case {
+            name: "PITCH Vx leaves the registers alone",
+            quirks: xochip(),
+            before: State {
+                v: &[(0x3, 0x70)],
+                ..Default::default()
+            },
+            instruction: 0xF33A,
+            ..Default::default()
+        },
+    ]
+}
+
+#[test]
+fn opcodes_match_the_table() {
+    run_all(cases());
+}
+
+#[test]
+fn table_covers_every_opcode() {
+    let mut seen: Vec<u16> = cases()
+        .iter()
+        .map(|case| opcode(case.instruction))
+        .collect();
+    seen.sort();
+    seen.dedup();
+    assert_eq!(seen.len(), 51, "{:04X?}", seen);
+}
+
+/// The instruction with its operands masked out.
+fn opcode(instruction: u16) -> u16 {
+    match instruction & 0xF000 {
+        0x0000
+            if matches!(
+                instruction,
+                0x00E0 | 0x00EE | 0x00FB | 0x00FC | 0x00FE | 0x00FF
+            ) =>
+        {
+            instruction
+        }
+        0x0000 if matches!(instruction & 0xFFF0, 0x00C0 | 0x00D0) => instruction & 0xFFF0,
+        // Dxy0 is an instruction of its own on SUPER-CHIP.
+        0xD000 => 0xD000 | u16::from(instruction & 0xF != 0),
+        0x0000 | 0x1000 | 0x2000 | 0x3000 | 0x4000 | 0x6000 | 0x7000 | 0xA000 | 0xB000 | 0xC000 => {
+            instruction & 0xF000
+        }
+        0x5000 | 0x8000 | 0x9000 => instruction & 0xF00F,
+        _ => instruction & 0xF0FF,
+    }
+}
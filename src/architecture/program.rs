@@ -0,0 +1,97 @@
+use super::{Instruction, LOAD_ADDRESS};
+use std::ops::Deref;
+
+/// What the interpreter runs at one ROM address.
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    /// A word that doesn't decode. Running it halts the machine with
+    /// `Fault::InvalidOpcode`, as `execute` does.
+    Invalid,
+    Single(Instruction),
+    /// `LD Vx, kk` then `ADD I, Vx`, the usual way to step I by a
+    /// constant.
+    LoadAddI {
+        x: usize,
+        kk: u8,
+    },
+    /// A skip then `JP nnn`: a conditional branch.
+    SkipJump {
+        skip: Instruction,
+        nnn: u16,
+    },
+}
+impl Op {
+    /// Bytes of RAM the op was decoded from.
+    fn size(self) -> usize {
+        match self {
+            Op::Invalid | Op::Single(_) => 2,
+            Op::LoadAddI { .. } | Op::SkipJump { .. } => 4,
+        }
+    }
+}
+
+/// A ROM with the instruction at every address decoded up front, so
+/// running it doesn't decode the same instructions again each time round a
+/// loop. Common pairs of instructions are fused into one op that runs both.
+///
+/// The machine runs whatever is in RAM, so an op is only used while the
+/// bytes it was decoded from are still there; code the program has
+/// rewritten is decoded afresh.
+///
+/// The ROM bytes stay available through `Deref`, for everything that reads
+/// the ROM rather than running it.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    bytes: Vec<u8>,
+    /// One per byte of the ROM, since nothing stops a jump to an odd
+    /// address.
+    ops: Vec<Op>,
+}
+impl Program {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        let mut ops: Vec<Op> = bytes
+            .windows(2)
+            .map(|pair| {
+                let word = u16::from_be_bytes([pair[0], pair[1]]);
+                Instruction::decode(word).map_or(Op::Invalid, Op::Single)
+            })
+            .collect();
+        // A jump may still land on the second word of a pair, so that
+        // keeps its own op; only the first is replaced.
+        for i in 0..ops.len().saturating_sub(2) {
+            if let (Op::Single(first), Op::Single(second)) = (ops[i], ops[i + 2]) {
+                if let Some(fused) = fuse(first, second) {
+                    ops[i] = fused;
+                }
+            }
+        }
+        Program { bytes, ops }
+    }
+
+    /// The op at `pc`, or `None` outside the ROM and where `ram` no longer
+    /// holds the bytes the op was decoded from.
+    pub(super) fn op(&self, pc: u16, ram: &[u8]) -> Option<Op> {
+        let address = usize::from(pc);
+        let offset = address.checked_sub(LOAD_ADDRESS)?;
+        let op = *self.ops.get(offset)?;
+        let decoded = self.bytes.get(offset..offset + op.size())?;
+        (ram.get(address..address + op.size())? == decoded).then_some(op)
+    }
+}
+impl Deref for Program {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+fn fuse(first: Instruction, second: Instruction) -> Option<Op> {
+    match (first, second) {
+        (Instruction::LdByte { x, kk }, Instruction::AddI { x: added }) if added == x => {
+            Some(Op::LoadAddI { x, kk })
+        }
+        (skip, Instruction::Jp { nnn }) if skip.is_skip() => Some(Op::SkipJump { skip, nnn }),
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+/// Behaviours that differ between CHIP-8 interpreters over the years.
+/// ROMs written for one era can break on another, so they are switchable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift Vx in place. Off, they shift Vy into Vx like the
+    /// COSMAC VIP did.
+    pub shift_uses_vx: bool,
+    /// 8xy1/8xy2/8xy3 reset VF to 0, a side effect of the VIP's ALU routine.
+    pub vf_reset: bool,
+    /// Fx55/Fx65 leave I just past the last register they copied, like the
+    /// VIP. Off, I is left alone.
+    pub load_store_increments_i: bool,
+    /// Bnnn jumps to nnn plus Vx, x being nnn's top digit, a CHIP-48
+    /// slip SUPER-CHIP kept. Off, it adds V0.
+    pub jump_uses_vx: bool,
+    /// Sprites that run off an edge carry on at the opposite one. Off,
+    /// they are clipped; either way the starting position wraps.
+    pub wrap_sprites: bool,
+    /// SUPER-CHIP's additions run: high resolution, scrolling, the large
+    /// font and the RPL flags. Off, they act as on a plain CHIP-8, so its
+    /// ROMs run the same either way.
+    pub superchip: bool,
+    /// What Dxy0 draws, which interpreters never agreed on.
+    pub dxy0: Dxy0,
+    /// XO-CHIP's additions run: 64KB of RAM, a second display plane,
+    /// `F000 nnnn`, register ranges and audio patterns. Off, their opcodes
+    /// don't exist.
+    pub xochip: bool,
+}
+
+/// What a sprite with no rows, Dxy0, draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dxy0 {
+    /// Nothing, and VF is cleared, as on the COSMAC VIP.
+    Nothing,
+    /// An 8x16 sprite of 16 bytes, like SUPER-CHIP 1.0 in low resolution.
+    Tall,
+    /// A 16x16 sprite of 32 bytes, two per row, as SUPER-CHIP 1.1 and
+    /// XO-CHIP draw it.
+    Large,
+}
+impl Dxy0 {
+    /// `nothing`, `8x16` or `16x16`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nothing" => Some(Dxy0::Nothing),
+            "8x16" => Some(Dxy0::Tall),
+            "16x16" => Some(Dxy0::Large),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter (1977).
+    Vip,
+    /// CHIP-48 on the HP-48 calculators (1990).
+    Chip48,
+    /// SUPER-CHIP 1.1.
+    Schip,
+    /// XO-CHIP, as implemented by Octo.
+    XoChip,
+    /// What most ROMs written for modern interpreters expect.
+    Modern,
+}
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vip" | "chip8" => Some(Platform::Vip),
+            "chip48" => Some(Platform::Chip48),
+            "schip" => Some(Platform::Schip),
+            "xochip" => Some(Platform::XoChip),
+            "modern" => Some(Platform::Modern),
+            _ => None,
+        }
+    }
+}
+
+impl Quirks {
+    pub fn preset(platform: Platform) -> Self {
+        match platform {
+            Platform::Vip => Quirks {
+                shift_uses_vx: false,
+                vf_reset: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                wrap_sprites: false,
+                superchip: false,
+                dxy0: Dxy0::Nothing,
+                xochip: false,
+            },
+            Platform::Chip48 => Quirks {
+                shift_uses_vx: true,
+                vf_reset: false,
+                load_store_increments_i: false,
+                jump_uses_vx: true,
+                wrap_sprites: false,
+                superchip: false,
+                dxy0: Dxy0::Nothing,
+                xochip: false,
+            },
+            Platform::Schip => Quirks {
+                shift_uses_vx: true,
+                vf_reset: false,
+                load_store_increments_i: false,
+                jump_uses_vx: true,
+                wrap_sprites: false,
+                superchip: true,
+                dxy0: Dxy0::Large,
+                xochip: false,
+            },
+            Platform::XoChip => Quirks {
+                shift_uses_vx: false,
+                vf_reset: false,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                wrap_sprites: true,
+                superchip: true,
+                dxy0: Dxy0::Large,
+                xochip: true,
+            },
+            Platform::Modern => Quirks {
+                shift_uses_vx: true,
+                vf_reset: false,
+                load_store_increments_i: false,
+                jump_uses_vx: false,
+                wrap_sprites: false,
+                superchip: false,
+                dxy0: Dxy0::Nothing,
+                xochip: false,
+            },
+        }
+    }
+}
+impl Quirks {
+    /// Sets one quirk from `NAME=VALUE`, as `--quirk` takes it, with the
+    /// names a sidecar's `[quirks]` uses: `true` or `false`, or for
+    /// `dxy0` one of its names. `None` if either half isn't known.
+    pub fn with(mut self, setting: &str) -> Option<Self> {
+        let (name, value) = setting.split_once('=')?;
+        let (name, value) = (name.trim(), value.trim());
+        if name == "dxy0" {
+            self.dxy0 = Dxy0::from_name(value)?;
+            return Some(self);
+        }
+        let value = value.parse().ok()?;
+        match name {
+            "shift_uses_vx" => self.shift_uses_vx = value,
+            "vf_reset" => self.vf_reset = value,
+            "load_store_increments_i" => self.load_store_increments_i = value,
+            "jump_uses_vx" => self.jump_uses_vx = value,
+            "wrap_sprites" => self.wrap_sprites = value,
+            "superchip" => self.superchip = value,
+            "xochip" => self.xochip = value,
+            _ => return None,
+        }
+        Some(self)
+    }
+}
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::preset(Platform::Modern)
+    }
+}
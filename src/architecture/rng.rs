@@ -0,0 +1,42 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+
+/// Where Cxkk gets its random bytes. `Rng` at runtime; tests can supply a
+/// fixed sequence instead.
+pub trait RandomSource: fmt::Debug + Send + Sync {
+    fn next_u8(&mut self) -> u8;
+    /// Everything needed to pick up the sequence where it is, for save
+    /// states. `None` for sources that can't be restored.
+    fn state(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// SplitMix64, small and good enough for Cxkk. Seeded explicitly for
+/// reproducible runs or from the OS through std's hasher keys otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct Rng {
+    state: u64,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn from_os() -> Self {
+        Rng::new(RandomState::new().build_hasher().finish())
+    }
+}
+impl RandomSource for Rng {
+    fn next_u8(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+    fn state(&self) -> Option<u64> {
+        Some(self.state)
+    }
+}
@@ -1,36 +1,67 @@
-const STACK_SIZE: usize = 16;
+/// Return addresses the stack holds, and so how deep calls can nest.
+pub const STACK_SIZE: usize = 16;
 
-#[derive(Debug, Clone, Copy)]
+/// The return addresses of the calls in progress. Only `push` and `pop`
+/// move the stack pointer, so CALL and RET can't get it out of step.
+#[derive(Debug, Clone)]
 pub struct Stack {
-    pub memory: [u16; STACK_SIZE],
-    pub sp: usize,
+    memory: [u16; STACK_SIZE],
+    sp: usize,
+    /// The deepest the stack has been since the machine started.
+    peak: usize,
 }
 impl Stack {
     pub fn new() -> Self {
         Stack {
             memory: [0; STACK_SIZE],
             sp: 0,
+            peak: 0,
         }
     }
 
-    pub fn push(&mut self, value: u16) {
-        if self.sp < STACK_SIZE {
-            self.memory[self.sp] = value;
-            self.sp += 1;
-        } else {
-            dbg!(self);
-            panic!("Stack overflow!")
+    /// False, leaving the stack as it was, when it is already full.
+    #[must_use]
+    pub fn push(&mut self, value: u16) -> bool {
+        if self.sp == STACK_SIZE {
+            return false;
         }
+        self.memory[self.sp] = value;
+        self.sp += 1;
+        self.peak = self.peak.max(self.sp);
+        true
     }
 
-    fn pop(&mut self) -> Option<u16> {
-        if self.sp > 0 {
-            self.sp -= 1;
-            let val = self.memory[self.sp];
-            self.memory[self.sp] = 0;
-            Some(val)
-        } else {
-            None
+    /// The most recent return address, or `None` when the stack is empty.
+    pub fn pop(&mut self) -> Option<u16> {
+        if self.sp == 0 {
+            return None;
         }
+        self.sp -= 1;
+        Some(std::mem::take(&mut self.memory[self.sp]))
+    }
+
+    /// Puts the stack back the way a save state or a tool describes it,
+    /// keeping the peak. False, changing nothing, if `sp` is past the end.
+    pub fn set(&mut self, memory: [u16; STACK_SIZE], sp: usize) -> bool {
+        if sp > STACK_SIZE {
+            return false;
+        }
+        self.memory = memory;
+        self.sp = sp;
+        self.peak = self.peak.max(sp);
+        true
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// Every slot, including the ones above the stack pointer.
+    pub fn memory(&self) -> [u16; STACK_SIZE] {
+        self.memory
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak
     }
 }
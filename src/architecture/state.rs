@@ -0,0 +1,212 @@
+use super::{Architecture, Dxy0, Fault, KeyWait, Quirks, HIRES_HEIGHT, HIRES_WIDTH, RAM_SIZE};
+use crate::architecture::rng::Rng;
+use crate::architecture::STACK_SIZE;
+use std::io::{self, Read};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 7;
+
+impl Architecture {
+    /// Serializes the whole machine into the versioned save-state format:
+    /// magic, version, the quirks (a byte of flags and then the Dxy0
+    /// behaviour), the size of RAM (4KB, or 64KB on XO-CHIP) as a u32 and
+    /// then RAM, the whole 128x64 display buffer, V0-VF, I, PC, DT,
+    /// ST, SP, the stack, any pending Fx0A wait (register and key, 0xFF
+    /// when absent), whether the display is in high resolution, the RPL
+    /// flags, the selected XO-CHIP planes, the pitch, 1 and the audio
+    /// pattern if one was loaded, else 0, and 1 and the random number
+    /// generator's state as a u64, else 0, whether the machine halted, and
+    /// the fault that stopped it, if any, as a kind (0 for none), the
+    /// faulting PC and, for an unknown opcode, its word. Multi-byte values
+    /// are little-endian.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(RAM_SIZE + HIRES_WIDTH * HIRES_HEIGHT + 128);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(quirk_flags(self.quirks));
+        bytes.push(match self.quirks.dxy0 {
+            Dxy0::Nothing => 0,
+            Dxy0::Tall => 1,
+            Dxy0::Large => 2,
+        });
+        bytes.extend_from_slice(&(self.ram().len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.ram());
+        bytes.extend_from_slice(&self.display);
+        bytes.extend_from_slice(&self.v);
+        bytes.extend_from_slice(&self.i.to_le_bytes());
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes.push(self.dt);
+        bytes.push(self.st);
+        bytes.push(self.stack.sp() as u8);
+        for address in &self.stack.memory() {
+            bytes.extend_from_slice(&address.to_le_bytes());
+        }
+        match self.key_wait {
+            Some(wait) => {
+                bytes.push(wait.register as u8);
+                bytes.push(wait.key.unwrap_or(0xFF));
+            }
+            None => bytes.extend_from_slice(&[0xFF, 0xFF]),
+        }
+        bytes.push(u8::from(self.hires));
+        bytes.extend_from_slice(&self.flags);
+        bytes.push(self.plane);
+        bytes.push(self.pitch);
+        match self.audio {
+            Some(pattern) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&pattern);
+            }
+            None => bytes.push(0),
+        }
+        match self.rng.state() {
+            Some(state) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&state.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes.push(u8::from(self.halted));
+        let (kind, pc) = match self.fault {
+            None => (0, 0),
+            Some(Fault::StackUnderflow { pc }) => (1, pc),
+            Some(Fault::StackOverflow { pc }) => (2, pc),
+            Some(Fault::InvalidOpcode { pc, .. }) => (3, pc),
+        };
+        bytes.push(kind);
+        bytes.extend_from_slice(&pc.to_le_bytes());
+        if let Some(Fault::InvalidOpcode { word, .. }) = self.fault {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn load_state(mut bytes: &[u8]) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        bytes.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a chip-n-claw save state"));
+        }
+        let version = read_u8(&mut bytes)?;
+        if version != VERSION {
+            return Err(invalid("unsupported save state version"));
+        }
+        let mut arch = Architecture::new();
+        let flags = read_u8(&mut bytes)?;
+        let dxy0 = match read_u8(&mut bytes)? {
+            0 => Dxy0::Nothing,
+            1 => Dxy0::Tall,
+            2 => Dxy0::Large,
+            _ => return Err(invalid("unknown Dxy0 quirk")),
+        };
+        arch.quirks = quirks_from_flags(flags, dxy0);
+        let ram_size = read_u32(&mut bytes)? as usize;
+        if ram_size != arch.memory_size() {
+            return Err(invalid("RAM size doesn't match the quirks"));
+        }
+        bytes.read_exact(&mut arch.ram[..ram_size])?;
+        bytes.read_exact(&mut arch.display)?;
+        bytes.read_exact(&mut arch.v)?;
+        arch.i = read_u16(&mut bytes)?;
+        arch.pc = read_u16(&mut bytes)?;
+        arch.dt = read_u8(&mut bytes)?;
+        arch.st = read_u8(&mut bytes)?;
+        let sp = read_u8(&mut bytes)? as usize;
+        let mut stack = [0; STACK_SIZE];
+        for address in stack.iter_mut() {
+            *address = read_u16(&mut bytes)?;
+        }
+        if !arch.stack.set(stack, sp) {
+            return Err(invalid("stack pointer out of range"));
+        }
+        let register = read_u8(&mut bytes)?;
+        let key = read_u8(&mut bytes)?;
+        if register < 16 {
+            arch.key_wait = Some(KeyWait {
+                register: register as usize,
+                key: (key < 16).then_some(key),
+            });
+        }
+        arch.hires = read_u8(&mut bytes)? != 0;
+        bytes.read_exact(&mut arch.flags)?;
+        arch.plane = read_u8(&mut bytes)? & 3;
+        arch.pitch = read_u8(&mut bytes)?;
+        if read_u8(&mut bytes)? != 0 {
+            let mut pattern = [0; 16];
+            bytes.read_exact(&mut pattern)?;
+            arch.audio = Some(pattern);
+        }
+        // A state without one keeps the fresh generator `new` seeded.
+        if read_u8(&mut bytes)? != 0 {
+            let mut state = [0; 8];
+            bytes.read_exact(&mut state)?;
+            arch.rng = Box::new(Rng::new(u64::from_le_bytes(state)));
+        }
+        arch.halted = read_u8(&mut bytes)? != 0;
+        let kind = read_u8(&mut bytes)?;
+        let pc = read_u16(&mut bytes)?;
+        arch.fault = match kind {
+            0 => None,
+            1 => Some(Fault::StackUnderflow { pc }),
+            2 => Some(Fault::StackOverflow { pc }),
+            3 => Some(Fault::InvalidOpcode {
+                pc,
+                word: read_u16(&mut bytes)?,
+            }),
+            _ => return Err(invalid("unknown fault")),
+        };
+        Ok(arch)
+    }
+}
+
+/// The quirks' switches, one bit each in the order `Quirks` declares them.
+fn quirk_flags(quirks: Quirks) -> u8 {
+    [
+        quirks.shift_uses_vx,
+        quirks.vf_reset,
+        quirks.load_store_increments_i,
+        quirks.jump_uses_vx,
+        quirks.wrap_sprites,
+        quirks.superchip,
+        quirks.xochip,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |flags, (bit, &on)| flags | u8::from(on) << bit)
+}
+
+fn quirks_from_flags(flags: u8, dxy0: Dxy0) -> Quirks {
+    let on = |bit: u8| flags & 1 << bit != 0;
+    Quirks {
+        shift_uses_vx: on(0),
+        vf_reset: on(1),
+        load_store_increments_i: on(2),
+        jump_uses_vx: on(3),
+        wrap_sprites: on(4),
+        superchip: on(5),
+        dxy0,
+        xochip: on(6),
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u8(bytes: &mut &[u8]) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    bytes.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(bytes: &mut &[u8]) -> io::Result<u16> {
+    let mut buf = [0u8; 2];
+    bytes.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(bytes: &mut &[u8]) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    bytes.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
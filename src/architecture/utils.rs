@@ -1,8 +1,25 @@
+/// Operand fields of a 16-bit opcode, named as in Cowgod's reference.
 pub struct Hex;
 
 impl Hex {
-    pub fn swap_hex_digits(num: u16, i: u16, j: u16) -> u16 {
-        let temp: u16 = (num >> (4 * i)) ^ (num >> (4 * j)) & 0xF;
-        num ^ (temp << (4 * i)) ^ (temp << (4 * j))
+    /// Register index in the second nibble: `_x__`.
+    pub fn x(op: u16) -> usize {
+        usize::from((op >> 8) & 0xF)
+    }
+    /// Register index in the third nibble: `__y_`.
+    pub fn y(op: u16) -> usize {
+        usize::from((op >> 4) & 0xF)
+    }
+    /// The lowest nibble: `___n`.
+    pub fn n(op: u16) -> u8 {
+        (op & 0xF) as u8
+    }
+    /// The lowest byte: `__kk`.
+    pub fn kk(op: u16) -> u8 {
+        (op & 0xFF) as u8
+    }
+    /// The lowest 12 bits, an address: `_nnn`.
+    pub fn nnn(op: u16) -> u16 {
+        op & 0xFFF
     }
 }
@@ -0,0 +1,739 @@
+use crate::architecture::{Instruction, LOAD_ADDRESS};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// How deep macros may expand into other macros, so one that uses itself
+/// is an error instead of a hang.
+const MACRO_DEPTH: usize = 16;
+/// How deep `:include`s may nest, for the loops a path's spelling hides.
+const INCLUDE_DEPTH: usize = 16;
+
+/// Where a statement was written, for errors and listings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    /// Empty for source that didn't come from a file.
+    pub file: String,
+    /// Counting from 1.
+    pub line: usize,
+}
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.file.as_str() {
+            "" => write!(f, "line {}", self.line),
+            file => write!(f, "{}:{}", file, self.line),
+        }
+    }
+}
+
+/// One statement of the program, after macros and includes.
+#[derive(Debug, Clone)]
+pub struct Listed {
+    pub origin: Origin,
+    pub address: usize,
+    /// Nothing for a line that only defines a label.
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+/// A ROM and the statements that made it, in order.
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    pub rom: Vec<u8>,
+    pub listing: Vec<Listed>,
+}
+impl Assembly {
+    /// Every statement with its address, bytes and the file and line it
+    /// came from: `20C: F090F0  sprites.8o:3  db F0, 90, F0`.
+    pub fn write_listing(&self, out: &mut impl Write) -> io::Result<()> {
+        let width = |of: fn(&Listed) -> usize| self.listing.iter().map(of).max().unwrap_or(0);
+        let bytes_width = width(|listed| 2 * listed.bytes.len());
+        let origin_width = width(|listed| listed.origin.to_string().len());
+        for listed in &self.listing {
+            let bytes: String = listed.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let origin = listed.origin.to_string();
+            writeln!(
+                out,
+                "{:03X}: {:bytes_width$}  {:origin_width$}  {}",
+                listed.address, bytes, origin, listed.text
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Assembles Cowgod-style mnemonics, as the disassembler writes them, into
+/// a ROM to load at 0x200. One statement per line:
+///
+/// ```text
+/// ; a comment
+/// :include "sprites.8o"    ; another file's statements, pasted in here
+/// :const SIZE 5            ; a named number
+/// :macro draw x y {        ; statements to paste in, with parameters
+///         DRW x, y, SIZE
+/// }
+/// start:  LD VA, 02        ; numbers are hex
+///         LD I, sprite     ; labels stand for addresses
+///         draw VA, VA
+///         JP start
+/// sprite: db F0, 90, F0    ; raw bytes
+///         db SIZE * 2 + 1  ; + - * / and parentheses
+/// ```
+///
+/// Every mnemonic the interpreter knows is accepted, in any case, so a
+/// listing's instructions assemble back to the same opcodes. Labels that
+/// read as hex numbers, like `FACE`, are taken as numbers. The directives
+/// follow Octo's, so its `:const`, `:calc` and `:macro` lines work as they
+/// are, and numbers may also be written `0x1F` or `0b0101`. Included
+/// paths are relative to the current directory.
+pub fn assemble(source: &str) -> io::Result<Vec<u8>> {
+    let read = &mut |path: &Path| fs::read_to_string(path);
+    assemble_source("", source, read).map(|assembly| assembly.rom)
+}
+
+/// Assembles the file at `path`, with the paths it includes relative to
+/// the file that includes them.
+pub fn assemble_file(path: &str) -> io::Result<Assembly> {
+    let source = fs::read_to_string(path)?;
+    let read = &mut |path: &Path| fs::read_to_string(path);
+    assemble_source(path, &source, read)
+}
+
+/// Assembles `source`, which came from `file`, reading what it includes
+/// with `read`.
+fn assemble_source(
+    file: &str,
+    source: &str,
+    read: &mut dyn FnMut(&Path) -> io::Result<String>,
+) -> io::Result<Assembly> {
+    // First the directives, leaving plain statements, then the addresses of
+    // the labels, then the bytes, so jumps can go forwards.
+    let mut preprocessor = Preprocessor {
+        read,
+        constants: HashMap::new(),
+        macros: HashMap::new(),
+        statements: Vec::new(),
+        including: Vec::new(),
+    };
+    preprocessor.file(file, source)?;
+    let Preprocessor {
+        constants,
+        statements: lines,
+        ..
+    } = preprocessor;
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address = LOAD_ADDRESS;
+    for (origin, text) in lines {
+        let mut line = text.as_str();
+        if let Some((label, rest)) = line.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(invalid(&origin, format!("`{}` is not a label", label)));
+            }
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(invalid(&origin, format!("`{}` is defined twice", label)));
+            }
+            line = rest.trim();
+        }
+        let tokens = tokenize(line);
+        let size = match tokens.first() {
+            None => 0,
+            Some(directive) if directive.eq_ignore_ascii_case("db") => {
+                // Only the shape of the expressions matters for counting.
+                let mut count = 0;
+                let mut at = 1;
+                while let Some((_, end)) = expression(&tokens, at, &|_| Some(1)).ok().flatten() {
+                    count += 1;
+                    at = end + usize::from(tokens.get(end).is_some_and(|t| t == ","));
+                }
+                count
+            }
+            _ => 2,
+        };
+        statements.push((origin, address, tokens, text.clone()));
+        address += size;
+    }
+    let value = |name: &str| {
+        literal(name)
+            .or_else(|| constants.get(name).copied())
+            .or_else(|| labels.get(name).map(|&address| address as i64))
+    };
+    let mut assembly = Assembly::default();
+    for (origin, address, tokens, text) in statements {
+        let mut bytes = Vec::new();
+        if tokens.is_empty() {
+            // Only a label.
+        } else if tokens[0].eq_ignore_ascii_case("db") {
+            let mut at = 1;
+            while at < tokens.len() {
+                let byte = expression(&tokens, at, &value).map_err(|e| invalid(&origin, e))?;
+                let Some((byte, end)) = byte.filter(|&(byte, _)| (0..=0xFF).contains(&byte)) else {
+                    return Err(invalid(&origin, format!("`{}` is not a byte", tokens[at])));
+                };
+                bytes.push(byte as u8);
+                at = end + usize::from(tokens.get(end).is_some_and(|t| t == ","));
+            }
+        } else {
+            let tokens = fold(&tokens, &constants, &value).map_err(|e| invalid(&origin, e))?;
+            // Numbers first, so `LD I, LONG` isn't read as a jump to a label.
+            let opcode = Instruction::SYNTAX
+                .iter()
+                .find_map(|&(pattern, mnemonic)| encode(pattern, mnemonic, &tokens, None))
+                .or_else(|| {
+                    Instruction::SYNTAX.iter().find_map(|&(pattern, mnemonic)| {
+                        encode(pattern, mnemonic, &tokens, Some(&labels))
+                    })
+                });
+            let Some(opcode) = opcode else {
+                let statement = text.split_once(':').map_or(text.as_str(), |(_, rest)| rest);
+                let message = format!("`{}` is not an instruction", statement.trim());
+                return Err(invalid(&origin, message));
+            };
+            bytes.extend(opcode.to_be_bytes());
+        }
+        assembly.rom.extend(&bytes);
+        assembly.listing.push(Listed {
+            origin,
+            address,
+            bytes,
+            text,
+        });
+    }
+    Ok(assembly)
+}
+
+fn invalid(origin: &Origin, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", origin, message),
+    )
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A macro's parameter names and the lines they are substituted into.
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<String>,
+}
+
+/// Takes the comments, directives and macros out of the source, leaving
+/// the statements to assemble. Constants and macros carry on into the
+/// files included after them.
+struct Preprocessor<'a> {
+    read: &'a mut dyn FnMut(&Path) -> io::Result<String>,
+    constants: HashMap<String, i64>,
+    macros: HashMap<String, Macro>,
+    statements: Vec<(Origin, String)>,
+    /// The files being read, outermost first, to catch one that includes
+    /// itself.
+    including: Vec<String>,
+}
+impl Preprocessor<'_> {
+    fn file(&mut self, file: &str, source: &str) -> io::Result<()> {
+        self.including.push(file.to_string());
+        let mut lines = source
+            .lines()
+            .map(|line| line.split(';').next().unwrap_or_default().trim())
+            .enumerate();
+        while let Some((number, line)) = lines.next() {
+            let origin = Origin {
+                file: file.to_string(),
+                line: number + 1,
+            };
+            if line.is_empty() {
+                continue;
+            }
+            let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match directive {
+                ":const" | ":calc" => self.constant(&origin, directive, rest)?,
+                ":macro" => {
+                    let (name, definition) =
+                        define_macro(&origin, rest, &mut lines.by_ref().map(|(_, line)| line))?;
+                    if self.macros.insert(name.clone(), definition).is_some() {
+                        return Err(invalid(&origin, format!("`{}` is defined twice", name)));
+                    }
+                }
+                ":include" => self.include(&origin, rest.trim())?,
+                _ if directive.starts_with(':') => {
+                    let message = format!("`{}` is not a directive", directive);
+                    return Err(invalid(&origin, message));
+                }
+                _ => expand(&origin, line, &self.macros, 0, &mut self.statements)?,
+            }
+        }
+        self.including.pop();
+        Ok(())
+    }
+
+    /// Reads `NAME VALUE` after `:const`, or `NAME { VALUE }` after
+    /// `:calc`. The value can use the constants defined before it.
+    fn constant(&mut self, origin: &Origin, directive: &str, rest: &str) -> io::Result<()> {
+        let (name, value) = rest.trim().split_once(char::is_whitespace).unzip();
+        let name = name.filter(|name| is_label(name)).ok_or_else(|| {
+            invalid(
+                origin,
+                format!("`{}` expects a name and a value", directive),
+            )
+        })?;
+        let tokens = tokenize(value.unwrap_or_default().trim_matches(['{', '}', ' ']));
+        let value = |name: &str| literal(name).or_else(|| self.constants.get(name).copied());
+        let value = match expression(&tokens, 0, &value).map_err(|e| invalid(origin, e))? {
+            Some((value, end)) if end == tokens.len() => value,
+            _ => {
+                let message = format!("`{}` is not a number", tokens.concat());
+                return Err(invalid(origin, message));
+            }
+        };
+        if self.constants.insert(name.to_string(), value).is_some() {
+            return Err(invalid(origin, format!("`{}` is defined twice", name)));
+        }
+        Ok(())
+    }
+
+    /// Pastes in the statements of the file named in quotes, relative to
+    /// the directory of the one including it.
+    fn include(&mut self, origin: &Origin, quoted: &str) -> io::Result<()> {
+        let name = quoted
+            .strip_prefix('"')
+            .and_then(|name| name.strip_suffix('"'))
+            .filter(|name| !name.is_empty());
+        let Some(name) = name else {
+            let message = "`:include` expects a file name in quotes".to_string();
+            return Err(invalid(origin, message));
+        };
+        let directory = Path::new(&origin.file).parent().unwrap_or(Path::new(""));
+        let path = directory.join(name);
+        let file = path.to_string_lossy().into_owned();
+        if self.including.contains(&file) {
+            return Err(invalid(origin, format!("`{}` includes itself", file)));
+        }
+        if self.including.len() == INCLUDE_DEPTH {
+            return Err(invalid(origin, "includes nest too deeply".to_string()));
+        }
+        let source = (self.read)(&path)
+            .map_err(|e| invalid(origin, format!("could not include `{}`: {}", file, e)))?;
+        self.file(&file, &source)
+    }
+}
+
+/// Reads `NAME PARAMETER... { BODY }` after `:macro`. The body may be on
+/// the same line or run over the following ones up to the closing brace.
+fn define_macro<'a>(
+    origin: &Origin,
+    header: &str,
+    lines: &mut impl Iterator<Item = &'a str>,
+) -> io::Result<(String, Macro)> {
+    let Some((signature, mut rest)) = header.split_once('{') else {
+        let message = "`:macro` expects a body in braces".to_string();
+        return Err(invalid(origin, message));
+    };
+    let mut words = signature.split_whitespace().map(str::to_string);
+    let name = words.next().filter(|name| is_label(name));
+    let Some(name) = name else {
+        return Err(invalid(origin, "`:macro` expects a name".to_string()));
+    };
+    let parameters: Vec<String> = words.collect();
+    if let Some(parameter) = parameters.iter().find(|parameter| !is_label(parameter)) {
+        let message = format!("`{}` is not a parameter name", parameter);
+        return Err(invalid(origin, message));
+    }
+    let mut body = Vec::new();
+    loop {
+        if let Some((last, after)) = rest.split_once('}') {
+            if !after.trim().is_empty() {
+                return Err(invalid(origin, format!("`{}` after a macro", after.trim())));
+            }
+            body.push(last.trim().to_string());
+            break;
+        }
+        body.push(rest.trim().to_string());
+        rest = match lines.next() {
+            Some(line) => line,
+            None => return Err(invalid(origin, format!("`{}` has no closing }}", name))),
+        };
+    }
+    body.retain(|line| !line.is_empty());
+    Ok((name, Macro { parameters, body }))
+}
+
+/// Appends `line` to `statements`, or the body of the macro it uses with
+/// the arguments in place of the parameters. A label before a macro marks
+/// its first statement.
+fn expand(
+    origin: &Origin,
+    line: &str,
+    macros: &HashMap<String, Macro>,
+    depth: usize,
+    statements: &mut Vec<(Origin, String)>,
+) -> io::Result<()> {
+    let (label, rest) = match line.split_once(':') {
+        Some((label, rest)) => (Some(label), rest.trim()),
+        None => (None, line),
+    };
+    let (name, arguments) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let Some(definition) = macros.get(name) else {
+        statements.push((origin.clone(), line.to_string()));
+        return Ok(());
+    };
+    if depth == MACRO_DEPTH {
+        return Err(invalid(origin, format!("`{}` expands too deeply", name)));
+    }
+    let arguments: Vec<&str> = arguments
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|argument| !argument.is_empty())
+        .collect();
+    if arguments.len() != definition.parameters.len() {
+        let message = format!(
+            "`{}` takes {} arguments, not {}",
+            name,
+            definition.parameters.len(),
+            arguments.len()
+        );
+        return Err(invalid(origin, message));
+    }
+    if let Some(label) = label {
+        statements.push((origin.clone(), format!("{}:", label)));
+    }
+    for body_line in &definition.body {
+        let body_line = substitute(body_line, &definition.parameters, &arguments);
+        expand(origin, &body_line, macros, depth + 1, statements)?;
+    }
+    Ok(())
+}
+
+/// `line` with every word that is one of `parameters` replaced by the
+/// matching argument.
+fn substitute(line: &str, parameters: &[String], arguments: &[&str]) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    let flush = |word: &mut String, result: &mut String| {
+        let parameter = parameters.iter().position(|parameter| parameter == word);
+        result.push_str(parameter.map_or(word.as_str(), |i| arguments[i]));
+        word.clear();
+    };
+    for c in line.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            flush(&mut word, &mut result);
+            result.push(c);
+        }
+    }
+    flush(&mut word, &mut result);
+    result
+}
+
+/// Words, and the punctuation between them as tokens of their own. The
+/// braces and colons of a mnemonic's operands stay inside their words.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '{' | '}' | ':') {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+/// A number written in hex, or with a `0x` or `0b` prefix.
+fn literal(token: &str) -> Option<i64> {
+    if let Some(digits) = token.strip_prefix("0x") {
+        i64::from_str_radix(digits, 16).ok()
+    } else if let Some(digits) = token.strip_prefix("0b") {
+        i64::from_str_radix(digits, 2).ok()
+    } else {
+        i64::from_str_radix(token, 16).ok()
+    }
+}
+
+/// The value of the expression starting at `tokens[at]` and where it ends:
+/// numbers and the names `value` knows, joined by `+ - * /` with `*` and
+/// `/` first, and parentheses. `None` if it doesn't read as an expression.
+fn expression(
+    tokens: &[String],
+    at: usize,
+    value: &dyn Fn(&str) -> Option<i64>,
+) -> Result<Option<(i64, usize)>, String> {
+    fn term(
+        tokens: &[String],
+        at: usize,
+        value: &dyn Fn(&str) -> Option<i64>,
+    ) -> Result<Option<(i64, usize)>, String> {
+        let Some(mut left) = factor(tokens, at, value)? else {
+            return Ok(None);
+        };
+        while let Some(operator @ ("*" | "/")) = tokens.get(left.1).map(String::as_str) {
+            let Some((right, end)) = factor(tokens, left.1 + 1, value)? else {
+                return Ok(None);
+            };
+            let result = match operator {
+                "*" => left.0.checked_mul(right),
+                _ if right == 0 => return Err("division by zero".to_string()),
+                _ => left.0.checked_div(right),
+            };
+            left = (result.ok_or("the expression overflows")?, end);
+        }
+        Ok(Some(left))
+    }
+    fn factor(
+        tokens: &[String],
+        at: usize,
+        value: &dyn Fn(&str) -> Option<i64>,
+    ) -> Result<Option<(i64, usize)>, String> {
+        match tokens.get(at).map(String::as_str) {
+            Some("(") => match expression(tokens, at + 1, value)? {
+                Some((inner, end)) if tokens.get(end).is_some_and(|t| t == ")") => {
+                    Ok(Some((inner, end + 1)))
+                }
+                _ => Ok(None),
+            },
+            Some(token) => Ok(value(token).map(|number| (number, at + 1))),
+            None => Ok(None),
+        }
+    }
+    let Some(mut left) = term(tokens, at, value)? else {
+        return Ok(None);
+    };
+    while let Some(operator @ ("+" | "-")) = tokens.get(left.1).map(String::as_str) {
+        let Some((right, end)) = term(tokens, left.1 + 1, value)? else {
+            return Ok(None);
+        };
+        let result = match operator {
+            "+" => left.0.checked_add(right),
+            _ => left.0.checked_sub(right),
+        };
+        left = (result.ok_or("the expression overflows")?, end);
+    }
+    Ok(Some(left))
+}
+
+/// `tokens` with each operand that is an expression or a constant replaced
+/// by its value in hex, leaving plain numbers, labels and registers for
+/// `encode`.
+fn fold(
+    tokens: &[String],
+    constants: &HashMap<String, i64>,
+    value: &dyn Fn(&str) -> Option<i64>,
+) -> Result<Vec<String>, String> {
+    let mut folded = vec![tokens[0].clone()];
+    let mut at = 1;
+    while at < tokens.len() {
+        let token = &tokens[at];
+        let computed = u16::from_str_radix(token, 16).is_err()
+            && (constants.contains_key(token) || literal(token).is_some());
+        match expression(tokens, at, value)? {
+            Some((number, end)) if end > at + 1 || computed => {
+                if !(0..=0xFFFF).contains(&number) {
+                    return Err(format!("`{}` is out of range", tokens[at..end].join(" ")));
+                }
+                folded.push(format!("{:X}", number));
+                at = end;
+            }
+            _ => {
+                folded.push(token.clone());
+                at += 1;
+            }
+        }
+    }
+    Ok(folded)
+}
+
+/// The opcode for `tokens` if they read as this row's mnemonic. Address
+/// operands may be labels when `labels` is given.
+fn encode(
+    pattern: &str,
+    mnemonic: &str,
+    tokens: &[String],
+    labels: Option<&HashMap<String, usize>>,
+) -> Option<u16> {
+    let template = tokenize(mnemonic);
+    if template.len() != tokens.len() {
+        return None;
+    }
+    // The pattern's hex digits, with its operand letters as zeroes.
+    let mut opcode = pattern.chars().fold(0, |opcode, c| {
+        opcode << 4 | c.to_digit(16).unwrap_or(0) as u16
+    });
+    for (expected, token) in template.iter().zip(tokens) {
+        let Some((prefix, operand)) = expected.split_once('{') else {
+            if !expected.eq_ignore_ascii_case(token) {
+                return None;
+            }
+            continue;
+        };
+        let name = operand.split([':', '}']).next()?;
+        let rest = token
+            .get(..prefix.len())
+            .filter(|start| start.eq_ignore_ascii_case(prefix))
+            .map(|_| &token[prefix.len()..])?;
+        let number = u16::from_str_radix(rest, 16).ok();
+        let value = match (number, labels) {
+            (Some(number), _) => number,
+            (None, Some(labels)) if name == "nnn" && prefix.is_empty() => {
+                u16::try_from(*labels.get(rest)?).ok()?
+            }
+            _ => return None,
+        };
+        // `nnn` is three nibbles wide, `kk` two, the rest one.
+        if u32::from(value) >= 1 << (4 * name.len()) {
+            return None;
+        }
+        let position = pattern.find(name)?;
+        opcode |= value << (4 * (4 - position - name.len()));
+    }
+    Some(opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(rom: &[u8]) -> Vec<u16> {
+        rom.chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn constants_and_arithmetic_fill_in_operands() {
+        let source = "\
+            :const SIZE 5
+            :calc DOUBLE { SIZE * 2 }
+            :const MASK 0b1111
+            start: LD VA, DOUBLE + 1
+                   DRW VA, VA, SIZE
+                   LD I, sprite + 2
+                   AND VA, VB
+                   RND VB, MASK
+                   JP (start + 4) / 2 * 2
+            sprite: db SIZE, 0x10, (1 + 2) * 3
+        ";
+        let rom = assemble(source).unwrap();
+        let (code, data) = rom.split_at(12);
+        assert_eq!(
+            words(code),
+            [0x6A0B, 0xDAA5, 0xA20E, 0x8AB2, 0xCB0F, 0x1204]
+        );
+        assert_eq!(data, [0x05, 0x10, 0x09]);
+    }
+
+    #[test]
+    fn register_ranges_are_not_subtraction() {
+        let rom = assemble(":const A 1\nLD V1-V3, [I]\nLD [I], V0-VA").unwrap();
+        assert_eq!(words(&rom), [0x5133, 0x50A2]);
+    }
+
+    #[test]
+    fn macros_paste_their_body_with_the_arguments() {
+        let source = "\
+            :macro wrap reg limit {
+                SNE reg, limit
+                LD reg, 00
+            }
+            :macro twice reg { ADD reg, 01
+            ADD reg, 01 }
+            loop: twice V3
+                  wrap V3, 0x10
+                  JP loop
+        ";
+        let rom = assemble(source).unwrap();
+        assert_eq!(words(&rom), [0x7301, 0x7301, 0x4310, 0x6300, 0x1200]);
+    }
+
+    #[test]
+    fn mistakes_in_directives_give_their_line() {
+        let error = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(
+            error("\n:const X 1\n:const X 2"),
+            "line 3: `X` is defined twice"
+        );
+        assert_eq!(
+            error(":macro m a b { LD a, b }\nm V1"),
+            "line 2: `m` takes 2 arguments, not 1"
+        );
+        assert_eq!(
+            error(":macro loop { loop }\nloop"),
+            "line 2: `loop` expands too deeply"
+        );
+        assert_eq!(error(":macro m {\nCLS"), "line 1: `m` has no closing }");
+        assert_eq!(
+            error("LD I, FFFF + 1"),
+            "line 1: `FFFF + 1` is out of range"
+        );
+        assert_eq!(error("db 1 / 0"), "line 1: division by zero");
+        assert_eq!(error(":org 300"), "line 1: `:org` is not a directive");
+    }
+
+    /// Assembles `files[0]`, reading the rest from memory.
+    fn assemble_files(files: &[(&str, &str)]) -> io::Result<Assembly> {
+        let read = &mut |path: &Path| {
+            let file = files.iter().find(|(name, _)| Path::new(name) == path);
+            file.map(|(_, source)| source.to_string())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        };
+        assemble_source(files[0].0, files[0].1, read)
+    }
+
+    #[test]
+    fn includes_paste_in_files_relative_to_the_one_including_them() {
+        let assembly = assemble_files(&[
+            (
+                "game/main.s",
+                ":include \"defs.s\"\nstart: blink\nJP start\n:include \"art/sprites.s\"",
+            ),
+            ("game/defs.s", ":const ON 1\n:macro blink { LD VA, ON }"),
+            ("game/art/sprites.s", "; sprites\nsprite: db F0, 90"),
+        ])
+        .unwrap();
+        assert_eq!(assembly.rom, [0x6A, 0x01, 0x12, 0x00, 0xF0, 0x90]);
+        let mut listing = Vec::new();
+        assembly.write_listing(&mut listing).unwrap();
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            "\
+200:       game/main.s:2         start:
+200: 6A01  game/main.s:2         LD VA, ON
+202: 1200  game/main.s:3         JP start
+204: F090  game/art/sprites.s:2  sprite: db F0, 90
+"
+        );
+    }
+
+    #[test]
+    fn errors_in_included_files_name_the_file_and_its_line() {
+        let error = |files: &[(&str, &str)]| assemble_files(files).unwrap_err().to_string();
+        let files = [
+            ("main.s", "CLS\n:include \"bad.s\""),
+            ("bad.s", "CLS\n\nJP nowhere"),
+        ];
+        assert_eq!(error(&files), "bad.s:3: `JP nowhere` is not an instruction");
+        let files = [
+            ("main.s", ":include \"loop.s\""),
+            ("loop.s", ":include \"loop.s\""),
+        ];
+        assert_eq!(error(&files), "loop.s:1: `loop.s` includes itself");
+        let files = [("main.s", "\n:include \"gone.s\"")];
+        assert!(error(&files).starts_with("main.s:2: could not include `gone.s`"));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::architecture::AudioPattern;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+const TONE_HZ: u32 = 440;
+/// At full volume.
+const AMPLITUDE: i16 = 8_000;
+
+/// Turns the sound timer into a square-wave beep, or an XO-CHIP audio
+/// pattern, one 60Hz frame at a time. The phase carries over between
+/// frames so consecutive beeps don't click.
+pub struct Buzzer {
+    amplitude: i16,
+    phase: u32,
+    /// How far into the 128-bit pattern playback has got, in bits.
+    position: f64,
+}
+impl Buzzer {
+    pub fn new() -> Self {
+        Buzzer::with_volume(100)
+    }
+
+    /// A buzzer `volume` percent as loud as the default.
+    pub fn with_volume(volume: u8) -> Self {
+        Buzzer {
+            amplitude: (i32::from(AMPLITUDE) * i32::from(volume.min(100)) / 100) as i16,
+            phase: 0,
+            position: 0.0,
+        }
+    }
+
+    /// Starts the next beep at the top of a cycle, for when the machine
+    /// jumps to a state the old phase has nothing to do with.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.position = 0.0;
+    }
+
+    pub fn frame_samples(
+        &mut self,
+        sound_on: bool,
+        pattern: Option<AudioPattern>,
+    ) -> [i16; SAMPLES_PER_FRAME] {
+        let mut samples = [0; SAMPLES_PER_FRAME];
+        if !sound_on {
+            return samples;
+        }
+        if let Some(pattern) = pattern {
+            let step = pattern.rate() / f64::from(SAMPLE_RATE);
+            for sample in samples.iter_mut() {
+                let bit = self.position as usize;
+                let on = pattern.bits[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                *sample = if on { self.amplitude } else { -self.amplitude };
+                self.position = (self.position + step) % 128.0;
+            }
+            return samples;
+        }
+        for sample in samples.iter_mut() {
+            *sample = if self.phase < SAMPLE_RATE / 2 {
+                self.amplitude
+            } else {
+                -self.amplitude
+            };
+            self.phase = (self.phase + TONE_HZ) % SAMPLE_RATE;
+        }
+        samples
+    }
+}
+impl Default for Buzzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,49 @@
+use crate::architecture::Architecture;
+use crate::statehash;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Where `--autosave` keeps the snapshot for a ROM. Snapshots are named
+/// after a hash of the ROM rather than its path, so a renamed or moved
+/// copy still resumes, and an edited one starts afresh.
+pub fn path(rom: &[u8]) -> io::Result<PathBuf> {
+    Ok(directory()?.join(format!("{:016x}.state", statehash::hash_bytes(rom))))
+}
+
+/// `$XDG_STATE_HOME/chip-n-claw`, or `~/.local/state/chip-n-claw`.
+fn directory() -> io::Result<PathBuf> {
+    let state_home = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+            PathBuf::from(home).join(".local/state")
+        }
+    };
+    Ok(state_home.join("chip-n-claw"))
+}
+
+/// The machine saved for this ROM, if there is one.
+pub fn load(rom: &[u8]) -> io::Result<Option<Architecture>> {
+    match fs::read(path(rom)?) {
+        Ok(bytes) => Architecture::load_state(&bytes).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the machine for the next launch. A halted machine has nothing to
+/// resume, so its snapshot is removed instead.
+pub fn save(arch: &Architecture, rom: &[u8]) -> io::Result<()> {
+    let path = path(rom)?;
+    if arch.halted() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    fs::create_dir_all(directory()?)?;
+    fs::write(path, arch.save_state())
+}
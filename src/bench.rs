@@ -0,0 +1,72 @@
+use crate::architecture::Outcome;
+use crate::emulator::Emulator;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Timings {
+    draw: (u64, Duration),
+    alu: (u64, Duration),
+    other: (u64, Duration),
+}
+impl Timings {
+    fn record(&mut self, instruction: u16, elapsed: Duration) {
+        let bucket = match instruction & 0xF000 {
+            0xD000 => &mut self.draw,
+            0x6000 | 0x7000 | 0x8000 => &mut self.alu,
+            _ => &mut self.other,
+        };
+        bucket.0 += 1;
+        bucket.1 += elapsed;
+    }
+}
+
+/// Runs `frames` frames with no frontend and no pacing, then prints how fast
+/// the core went and where the time went.
+pub fn run(emulator: &mut Emulator, frames: u64) {
+    let mut timings = Timings::default();
+    let mut executed_frames = 0;
+    let start = Instant::now();
+    for _ in 0..frames {
+        let budget = emulator.instructions_per_frame;
+        let mut instruction_start = Instant::now();
+        let outcome = emulator
+            .arch
+            .run_frame(&emulator.rom, budget, |_, pc, instruction| {
+                timings.record(instruction, instruction_start.elapsed());
+                if let Some(stats) = emulator.stats.as_mut() {
+                    stats.record(instruction);
+                }
+                if let Some(heatmap) = emulator.heatmap.as_mut() {
+                    heatmap.record(pc);
+                }
+                instruction_start = Instant::now();
+                true
+            });
+        executed_frames += 1;
+        if outcome == Outcome::Halted {
+            break;
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    let instructions = timings.draw.0 + timings.alu.0 + timings.other.0;
+
+    println!(
+        "{} frames, {} instructions in {:.3}s",
+        executed_frames, instructions, elapsed
+    );
+    println!("{:>12.0} instructions/s", instructions as f64 / elapsed);
+    println!("{:>12.0} frames/s", executed_frames as f64 / elapsed);
+    for (name, (count, time)) in [
+        ("draw", timings.draw),
+        ("alu", timings.alu),
+        ("other", timings.other),
+    ] {
+        println!(
+            "{:>6}: {:>10} instructions {:>9.3}ms {:>5.1}%",
+            name,
+            count,
+            time.as_secs_f64() * 1000.0,
+            time.as_secs_f64() / elapsed * 100.0
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+
+/// Matches instructions by opcode rather than address, e.g. every DRW.
+///
+/// Written either as four nibbles where `_` (or `x`, `y`, `n`, `k`) matches
+/// anything, like `D___` or `8F__`, or as `MASK/VALUE` in hex, like
+/// `F000/D000`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpcodePattern {
+    mask: u16,
+    value: u16,
+}
+impl OpcodePattern {
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some((mask, value)) = text.split_once('/') {
+            let mask = u16::from_str_radix(mask, 16).ok()?;
+            let value = u16::from_str_radix(value, 16).ok()?;
+            return Some(OpcodePattern {
+                mask,
+                value: value & mask,
+            });
+        }
+        if text.chars().count() != 4 {
+            return None;
+        }
+        let mut pattern = OpcodePattern { mask: 0, value: 0 };
+        for c in text.chars() {
+            pattern.mask <<= 4;
+            pattern.value <<= 4;
+            match c {
+                '_' | 'x' | 'y' | 'n' | 'k' => {}
+                _ => {
+                    pattern.mask |= 0xF;
+                    pattern.value |= c.to_digit(16)? as u16;
+                }
+            }
+        }
+        Some(pattern)
+    }
+
+    pub fn matches(&self, instruction: u16) -> bool {
+        instruction & self.mask == self.value
+    }
+}
+impl fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let whole_nibbles = (0..4).all(|i| matches!((self.mask >> (i * 4)) & 0xF, 0x0 | 0xF));
+        if !whole_nibbles {
+            return write!(f, "{:04X}/{:04X}", self.mask, self.value);
+        }
+        for shift in [12, 8, 4, 0] {
+            if (self.mask >> shift) & 0xF == 0 {
+                write!(f, "_")?;
+            } else {
+                write!(f, "{:X}", (self.value >> shift) & 0xF)?;
+            }
+        }
+        Ok(())
+    }
+}
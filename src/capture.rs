@@ -0,0 +1,350 @@
+use crate::architecture::{
+    display_size, fit_display, Architecture, AudioPattern, HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH,
+};
+use crate::audio::{Buzzer, SAMPLE_RATE};
+use crate::events::{Event, Subscriber};
+use crate::frontend::Palette;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Saves the display in `palette`'s colors as a plain PPM image, which any
+/// image viewer opens.
+pub fn save_screenshot(display: &[u8], palette: Palette, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (width, height) = display_size(display);
+    writeln!(out, "P3\n{} {}\n255", width, height)?;
+    for row in display.chunks(width) {
+        let line: Vec<String> = row
+            .iter()
+            .map(|&pixel| {
+                let (r, g, b) = palette.color(pixel);
+                format!("{} {} {}", r, g, b)
+            })
+            .collect();
+        writeln!(out, "{}", line.join(" "))?;
+    }
+    out.flush()
+}
+
+/// `<rom path>-<unix time>.<extension>`, so captures land next to the ROM.
+pub fn capture_path(rom_path: &str, extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("{}-{}.{}", rom_path, timestamp, extension)
+}
+
+const GIF_SCALE: usize = 4;
+/// Every other 60Hz frame, so the GIF runs at 30fps.
+const GIF_DECIMATION: u64 = 2;
+
+/// Records the display into a looping GIF. Runs of identical frames are
+/// merged into one longer frame, which keeps recordings of mostly static
+/// games small. High-resolution frames are scaled half as much, so the
+/// GIF keeps one size whichever resolution the game is in.
+///
+/// Pixel values index the color table directly. It starts as the palette
+/// the recording began with, and frames drawn after a palette change carry
+/// their own table.
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    frames: u64,
+    pending: Option<(Vec<u8>, u16)>,
+    global: Palette,
+    palette: Palette,
+}
+impl GifRecorder {
+    pub fn create(path: &str, palette: Palette) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let (width, height) = ((WIDTH * GIF_SCALE) as u16, (HEIGHT * GIF_SCALE) as u16);
+        let mut encoder = gif::Encoder::new(file, width, height, &color_table(palette))
+            .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        Ok(GifRecorder {
+            encoder,
+            frames: 0,
+            pending: None,
+            global: palette,
+            palette,
+        })
+    }
+
+    /// Draws the frames from here on in `palette`. The frame waiting to be
+    /// written keeps the colors it was shown in.
+    pub fn set_palette(&mut self, palette: Palette) -> io::Result<()> {
+        if palette != self.palette {
+            self.write_pending()?;
+            self.palette = palette;
+        }
+        Ok(())
+    }
+
+    /// Call once per emulated 60Hz frame.
+    pub fn push_frame(&mut self, display: &[u8]) -> io::Result<()> {
+        self.frames += 1;
+        if !self.frames.is_multiple_of(GIF_DECIMATION) {
+            return Ok(());
+        }
+        // GIF delays are in hundredths of a second, so spread the rounding
+        // over the frames instead of always rounding 3.33 down.
+        let delay = (self.frames * 100 / 60 - (self.frames - GIF_DECIMATION) * 100 / 60) as u16;
+        match self.pending.as_mut() {
+            Some((pixels, pending_delay))
+                if pixels == display && *pending_delay <= u16::MAX - delay =>
+            {
+                *pending_delay += delay
+            }
+            _ => {
+                self.write_pending()?;
+                self.pending = Some((display.to_vec(), delay));
+            }
+        }
+        Ok(())
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let Some((pixels, delay)) = self.pending.take() else {
+            return Ok(());
+        };
+        let (columns, _) = display_size(&pixels);
+        let scale = GIF_SCALE * WIDTH / columns;
+        let mut indexed = Vec::with_capacity(WIDTH * HEIGHT * GIF_SCALE * GIF_SCALE);
+        for row in pixels.chunks(columns) {
+            let scaled_row: Vec<u8> = row
+                .iter()
+                .flat_map(|&pixel| std::iter::repeat_n(pixel & 3, scale))
+                .collect();
+            for _ in 0..scale {
+                indexed.extend_from_slice(&scaled_row);
+            }
+        }
+        let (width, height) = ((WIDTH * GIF_SCALE) as u16, (HEIGHT * GIF_SCALE) as u16);
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, indexed, None);
+        frame.delay = delay;
+        if self.palette != self.global {
+            frame.palette = Some(color_table(self.palette));
+        }
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+}
+/// The palette as a GIF color table, one RGB triple per pixel value.
+fn color_table(palette: Palette) -> Vec<u8> {
+    palette
+        .colors
+        .iter()
+        .flat_map(|&(r, g, b)| [r, g, b])
+        .collect()
+}
+
+impl Subscriber for GifRecorder {
+    fn notify(&mut self, event: &Event, arch: &Architecture) -> io::Result<()> {
+        match event {
+            Event::FrameEnded => self.push_frame(arch.display()),
+            _ => Ok(()),
+        }
+    }
+}
+impl Drop for GifRecorder {
+    fn drop(&mut self) {
+        // The encoder writes the GIF trailer when it is dropped right after.
+        let _ = self.write_pending();
+    }
+}
+
+/// Pipes raw frames into an external `ffmpeg`, which picks the container
+/// and codec from the output file extension (.mp4, .webm, ...). Frames
+/// are sent at high resolution, so the video can switch between the two.
+///
+/// The buzzer is recorded into a WAV file next to the video while it
+/// runs, and a second `ffmpeg` muxes the two once recording stops. If that
+/// fails, as for containers without audio, the silent video is kept.
+pub struct VideoRecorder {
+    ffmpeg: Child,
+    frame: Vec<u8>,
+    /// What the frames are drawn in; it can change while recording.
+    pub palette: Palette,
+    audio: Option<WavRecorder>,
+    path: String,
+    silent_path: String,
+    audio_path: String,
+}
+impl VideoRecorder {
+    /// Records audio `volume` percent loud, as `WavRecorder` does.
+    pub fn create(path: &str, palette: Palette, volume: u8) -> io::Result<Self> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let sibling = |name: &str| Path::new(path).with_extension(name).display().to_string();
+        let silent_path = sibling(&format!("silent.{}", extension));
+        let audio_path = sibling("audio.wav");
+        let audio = WavRecorder::create(&audio_path, volume)?;
+        let size = format!("{}x{}", HIRES_WIDTH, HIRES_HEIGHT);
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24", "-s", &size])
+            .args(["-r", "60", "-i", "-"])
+            // Nearest-neighbour upscaling keeps the pixels sharp, and
+            // yuv420p is what most players expect.
+            .args([
+                "-vf",
+                "scale=iw*4:ih*4:flags=neighbor",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(&silent_path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        Ok(VideoRecorder {
+            ffmpeg,
+            frame: vec![0; HIRES_WIDTH * HIRES_HEIGHT * 3],
+            palette,
+            audio: Some(audio),
+            path: path.to_string(),
+            silent_path,
+            audio_path,
+        })
+    }
+
+    /// Puts the recorded audio under the finished video at `path`.
+    fn mux(&self) -> io::Result<()> {
+        let status = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-y"])
+            .args(["-i", &self.silent_path, "-i", &self.audio_path])
+            .args(["-c:v", "copy", "-shortest"])
+            .arg(&self.path)
+            .status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg exited with {}", status)));
+        }
+        fs::remove_file(&self.silent_path)
+    }
+
+    /// Call once per emulated 60Hz frame.
+    pub fn push_frame(&mut self, display: &[u8]) -> io::Result<()> {
+        let display = fit_display(display, HIRES_WIDTH);
+        for (rgb, &pixel) in self.frame.chunks_mut(3).zip(display.iter()) {
+            let (r, g, b) = self.palette.color(pixel);
+            rgb.copy_from_slice(&[r, g, b]);
+        }
+        match self.ffmpeg.stdin.as_mut() {
+            Some(stdin) => stdin.write_all(&self.frame),
+            None => Err(io::Error::from(io::ErrorKind::BrokenPipe)),
+        }
+    }
+}
+impl Subscriber for VideoRecorder {
+    fn notify(&mut self, event: &Event, arch: &Architecture) -> io::Result<()> {
+        if let Some(audio) = self.audio.as_mut() {
+            audio.notify(event, arch)?;
+        }
+        match event {
+            Event::FrameEnded => self.push_frame(arch.display()),
+            _ => Ok(()),
+        }
+    }
+}
+impl Drop for VideoRecorder {
+    fn drop(&mut self) {
+        // Closing stdin tells ffmpeg the video is over, then let it finish
+        // writing. Dropping the audio fills in the WAV header.
+        self.ffmpeg.stdin = None;
+        let _ = self.ffmpeg.wait();
+        self.audio = None;
+        if self.mux().is_err() {
+            let _ = fs::rename(&self.silent_path, &self.path);
+        }
+        let _ = fs::remove_file(&self.audio_path);
+    }
+}
+
+/// Writes 16-bit mono PCM to a WAV file, `volume` percent loud. The
+/// header's sizes are filled in when the recorder is dropped. The buzzer
+/// is silent while the rewind key is held.
+pub struct WavRecorder {
+    out: BufWriter<File>,
+    buzzer: Buzzer,
+    data_bytes: u32,
+    muted: bool,
+}
+impl WavRecorder {
+    pub fn create(path: &str, volume: u8) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let channels: u16 = 1;
+        let bits: u16 = 16;
+        let block_align = channels * bits / 8;
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&bits.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(WavRecorder {
+            out,
+            buzzer: Buzzer::with_volume(volume),
+            data_bytes: 0,
+            muted: false,
+        })
+    }
+
+    /// Call once per emulated 60Hz frame.
+    pub fn push_frame(&mut self, sound_on: bool, pattern: Option<AudioPattern>) -> io::Result<()> {
+        for sample in self.buzzer.frame_samples(sound_on, pattern) {
+            self.out.write_all(&sample.to_le_bytes())?;
+            self.data_bytes += 2;
+        }
+        Ok(())
+    }
+
+    /// Call when the machine jumps to another state, as on a reset or a
+    /// state load. What was recorded so far is written out and the tone
+    /// starts afresh.
+    pub fn restart(&mut self) -> io::Result<()> {
+        self.buzzer.reset();
+        self.out.flush()
+    }
+
+    fn write_sizes(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + self.data_bytes).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&self.data_bytes.to_le_bytes())?;
+        self.out.flush()
+    }
+}
+impl Subscriber for WavRecorder {
+    fn notify(&mut self, event: &Event, arch: &Architecture) -> io::Result<()> {
+        match event {
+            Event::FrameEnded => {
+                self.push_frame(arch.sound_on() && !self.muted, arch.audio_pattern())
+            }
+            Event::RewindStarted => {
+                self.muted = true;
+                Ok(())
+            }
+            Event::RewindEnded => {
+                self.muted = false;
+                Ok(())
+            }
+            event if event.replaces_machine() => self.restart(),
+            _ => Ok(()),
+        }
+    }
+}
+impl Drop for WavRecorder {
+    fn drop(&mut self) {
+        let _ = self.write_sizes();
+    }
+}
@@ -0,0 +1,123 @@
+use crate::architecture::Instruction;
+use crate::disasm::{self, LOAD_ADDRESS};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+/// A straight run of instructions that is only entered at the top.
+pub struct Block {
+    pub start: usize,
+    /// Address of each instruction in the block, in order.
+    pub instructions: Vec<usize>,
+    pub edges: Vec<Edge>,
+}
+
+pub struct Edge {
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    /// Falls through, jumps, or returns from a call to the next instruction.
+    Flow,
+    /// The instruction after a skip, taken when the skip condition holds.
+    Skip,
+    Call,
+    /// `JP V0` base address; the real target depends on V0.
+    Indirect,
+}
+
+/// Splits the code reachable from the entry point into basic blocks,
+/// following jumps, calls and skips.
+pub fn blocks(rom: &[u8]) -> Vec<Block> {
+    let code = disasm::code_map(rom);
+    let instruction_at = |address: usize| {
+        let offset = address - LOAD_ADDRESS;
+        let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        Instruction::decode(word).expect("code_map only marks instructions")
+    };
+    let is_code = |address: usize| {
+        address
+            .checked_sub(LOAD_ADDRESS)
+            .is_some_and(|offset| code.get(offset) == Some(&true))
+    };
+    let addresses: Vec<usize> = (0..rom.len())
+        .filter(|&offset| code[offset])
+        .map(|offset| LOAD_ADDRESS + offset)
+        .collect();
+
+    let mut leaders = BTreeSet::from([LOAD_ADDRESS]);
+    for &address in &addresses {
+        let successors = disasm::successors(address, instruction_at(address));
+        if successors != [address + 2] {
+            leaders.extend(successors.into_iter().filter(|&a| is_code(a)));
+            leaders.insert(address + 2);
+        }
+    }
+
+    let mut blocks: Vec<Block> = Vec::new();
+    for &address in &addresses {
+        match blocks.last_mut() {
+            Some(block)
+                if !leaders.contains(&address)
+                    && block.instructions.last() == Some(&(address - 2)) =>
+            {
+                block.instructions.push(address)
+            }
+            _ => blocks.push(Block {
+                start: address,
+                instructions: vec![address],
+                edges: Vec::new(),
+            }),
+        }
+    }
+    for block in &mut blocks {
+        let last = *block.instructions.last().unwrap();
+        let instruction = instruction_at(last);
+        block.edges = disasm::successors(last, instruction)
+            .into_iter()
+            .filter(|&to| is_code(to))
+            .map(|to| {
+                let kind = match instruction {
+                    Instruction::Call { .. } if to != last + 2 => EdgeKind::Call,
+                    Instruction::JpV0 { .. } => EdgeKind::Indirect,
+                    _ if instruction.is_skip() && to == last + 4 => EdgeKind::Skip,
+                    _ => EdgeKind::Flow,
+                };
+                Edge { to, kind }
+            })
+            .collect();
+    }
+    blocks
+}
+
+/// Writes the control-flow graph in Graphviz dot format, one box per
+/// block listing its instructions.
+pub fn write_dot(rom: &[u8], out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "digraph cfg {{")?;
+    writeln!(out, "    node [shape=box fontname=monospace];")?;
+    for block in blocks(rom) {
+        let mut label = String::new();
+        for &address in &block.instructions {
+            let offset = address - LOAD_ADDRESS;
+            let instruction = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            let mnemonic = disasm::mnemonic(instruction).unwrap_or_default();
+            label += &format!("{:03X}: {}\\l", address, mnemonic);
+        }
+        writeln!(out, "    b{:03X} [label=\"{}\"];", block.start, label)?;
+        for edge in &block.edges {
+            let style = match edge.kind {
+                EdgeKind::Flow => "",
+                EdgeKind::Skip => " [label=skip]",
+                EdgeKind::Call => " [label=call style=dashed]",
+                EdgeKind::Indirect => " [label=\"+V0\" style=dotted]",
+            };
+            writeln!(
+                out,
+                "    b{:03X} -> b{:03X}{};",
+                block.start, edge.to, style
+            )?;
+        }
+    }
+    writeln!(out, "}}")
+}
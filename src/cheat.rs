@@ -0,0 +1,95 @@
+use crate::architecture::Architecture;
+use std::fmt;
+
+/// Where a cheat writes: `V0`-`VF`, or a RAM address in hex like `2A0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Register(usize),
+    Ram(u16),
+}
+impl Target {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(register) = text.strip_prefix(['V', 'v']) {
+            let register = usize::from_str_radix(register, 16).ok()?;
+            return (register < 16).then_some(Target::Register(register));
+        }
+        let address = u16::from_str_radix(text, 16).ok()?;
+        (address < 0x1000).then_some(Target::Ram(address))
+    }
+
+    pub fn read(&self, arch: &Architecture) -> u8 {
+        match *self {
+            Target::Register(register) => arch.registers().v[register],
+            Target::Ram(address) => arch.ram()[usize::from(address)],
+        }
+    }
+}
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Register(register) => write!(f, "V{:X}", register),
+            Target::Ram(address) => write!(f, "{:03X}", address),
+        }
+    }
+}
+
+/// A byte to write, written `TARGET=VALUE` with the value in hex, like
+/// `V3=05` or `2A0=03`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Poke {
+    pub target: Target,
+    pub value: u8,
+}
+impl Poke {
+    pub fn parse(text: &str) -> Option<Self> {
+        let (target, value) = text.split_once('=')?;
+        Some(Poke {
+            target: Target::parse(target)?,
+            value: u8::from_str_radix(value.trim(), 16).ok()?,
+        })
+    }
+
+    pub fn apply(&self, arch: &mut Architecture) {
+        match self.target {
+            Target::Register(register) => arch.set_register(register, self.value),
+            Target::Ram(address) => arch.poke(address, self.value),
+        }
+    }
+}
+impl fmt::Display for Poke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={:02X}", self.target, self.value)
+    }
+}
+
+/// Pokes written again before every frame, so a game can't change them:
+/// the usual way to keep a lives counter from going down.
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    frozen: Vec<Poke>,
+}
+impl Cheats {
+    /// Freezes a target, replacing any value it was frozen at before.
+    pub fn freeze(&mut self, poke: Poke) {
+        self.unfreeze(poke.target);
+        self.frozen.push(poke);
+    }
+
+    /// Returns false if the target wasn't frozen.
+    pub fn unfreeze(&mut self, target: Target) -> bool {
+        let before = self.frozen.len();
+        self.frozen.retain(|poke| poke.target != target);
+        self.frozen.len() != before
+    }
+
+    pub fn frozen(&self) -> &[Poke] {
+        &self.frozen
+    }
+
+    pub fn apply(&self, arch: &mut Architecture) {
+        for poke in &self.frozen {
+            poke.apply(arch);
+        }
+    }
+}
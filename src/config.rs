@@ -0,0 +1,25 @@
+use crate::sidecar::Sidecar;
+use std::env;
+use std::io;
+use std::path::PathBuf;
+
+/// `$XDG_CONFIG_HOME/chip-n-claw/config.toml`, or
+/// `~/.config/chip-n-claw/config.toml`.
+pub fn path() -> io::Result<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+            PathBuf::from(home).join(".config")
+        }
+    };
+    Ok(config_home.join("chip-n-claw/config.toml"))
+}
+
+/// Settings for every game. The file takes the same entries as a
+/// [`Sidecar`]; a game's own sidecar goes on top of it, and the command
+/// line on top of both. `None` if there is no file.
+pub fn load() -> io::Result<Option<Sidecar>> {
+    Sidecar::read(&path()?)
+}
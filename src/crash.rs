@@ -0,0 +1,122 @@
+use crate::architecture::Architecture;
+use crate::disasm;
+use crate::notes::Notes;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    // Per thread, since the panic hook runs on the thread whose machine panicked.
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static HOLD_OUTPUT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A `.c8core` file: crash metadata followed by a regular save state.
+///
+/// Layout: `C8CORE`, format version, unix timestamp (u64 LE), panic message
+/// length (u32 LE), the UTF-8 message, then the bytes of
+/// [`Architecture::save_state`].
+const CORE_MAGIC: &[u8; 6] = b"C8CORE";
+const CORE_VERSION: u8 = 1;
+
+pub struct CoreDump {
+    pub timestamp: u64,
+    pub message: String,
+    pub arch: Architecture,
+}
+
+/// Keeps the default panic output, but also remembers the message so it can
+/// be written into the core dump once the stack has unwound.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        PANIC_MESSAGE.with(|message| *message.borrow_mut() = Some(info.to_string()));
+        if !HOLD_OUTPUT.get() {
+            default_hook(info);
+        }
+    }));
+}
+
+/// While a frontend owns the screen, panics on this thread are only
+/// remembered: printed then, they would land on the alternate screen and
+/// vanish with it. Print [`panic_message`] once the frontend is gone.
+pub fn hold_panic_output(hold: bool) {
+    HOLD_OUTPUT.set(hold);
+}
+
+/// The last panic raised on this thread, as the default hook words it.
+pub fn panic_message() -> Option<String> {
+    PANIC_MESSAGE.with(|message| message.borrow().clone())
+}
+
+/// The message a panic was raised with.
+pub fn panic_text(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(text) => text.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "panic".to_string()),
+    }
+}
+
+/// Writes `chip-n-claw-<unix time>.c8core` to the working directory and
+/// returns its path.
+pub fn dump_core(arch: &Architecture) -> io::Result<String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let message = panic_message().unwrap_or_default();
+    let path = format!("chip-n-claw-{}.c8core", timestamp);
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(CORE_MAGIC);
+    bytes.push(CORE_VERSION);
+    bytes.extend_from_slice(&timestamp.to_le_bytes());
+    bytes.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(message.as_bytes());
+    bytes.extend_from_slice(&arch.save_state());
+    fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+pub fn load_core(path: &str) -> io::Result<CoreDump> {
+    let file = fs::read(path)?;
+    let mut bytes = file.as_slice();
+    let mut magic = [0u8; 6];
+    bytes.read_exact(&mut magic)?;
+    let mut version = [0u8; 1];
+    bytes.read_exact(&mut version)?;
+    if &magic != CORE_MAGIC || version[0] != CORE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a chip-n-claw core dump",
+        ));
+    }
+    let mut timestamp = [0u8; 8];
+    bytes.read_exact(&mut timestamp)?;
+    let mut length = [0u8; 4];
+    bytes.read_exact(&mut length)?;
+    let mut message = vec![0u8; u32::from_le_bytes(length) as usize];
+    bytes.read_exact(&mut message)?;
+    Ok(CoreDump {
+        timestamp: u64::from_le_bytes(timestamp),
+        message: String::from_utf8_lossy(&message).into_owned(),
+        arch: Architecture::load_state(bytes)?,
+    })
+}
+
+/// Post-mortem view of a core dump: crash message, registers, the ROM
+/// around the faulting PC and a dump of RAM.
+pub fn inspect(core: &CoreDump, rom: &[u8], notes: &Notes, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "core dumped at unix time {}", core.timestamp)?;
+    writeln!(out, "{}", core.message)?;
+    core.arch.write_state(out)?;
+    writeln!(out)?;
+    disasm::write_around(out, rom, usize::from(core.arch.pc()), 4, notes)?;
+    writeln!(out)?;
+    core.arch.write_memory(out)
+}
@@ -0,0 +1,285 @@
+use crate::architecture::Architecture;
+use crate::breakpoint::OpcodePattern;
+use crate::crash;
+use crate::disasm;
+use crate::notes::Notes;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+const HELP: &str = "\
+step [N]            run one instruction, or N
+continue            run until a breakpoint, a halt or a key wait
+break ADDR          stop before the instruction at ADDR runs
+clear ADDR          remove a breakpoint
+break-op PATTERN    stop after any instruction matching PATTERN (D___, 8F__, F000/D000)
+unbreak-op PATTERN  remove an opcode break
+breaks              list breakpoints and opcode breaks
+press KEY           hold a keypad key down (0-F)
+release KEY         let it go; a key wait ends on the release
+regs                show registers, timers and the stack
+mem START LEN       dump LEN bytes of RAM from START, in hex
+list                disassemble around PC
+comment ADDR TEXT   note TEXT at ADDR; without TEXT, remove the note
+region START-END NAME  name a range of addresses
+unregion START      remove the region starting at START
+notes               list comments and regions
+quit                leave the debugger
+";
+
+/// The `--debug` REPL: runs the machine one instruction at a time under
+/// the user's control, reading commands from one stream and answering on
+/// another. Timers tick once every `instructions_per_frame` instructions,
+/// as they would at full speed.
+pub struct Debugger {
+    pub arch: Architecture,
+    rom: Vec<u8>,
+    rom_path: String,
+    /// Saved next to the ROM whenever they change.
+    pub notes: Notes,
+    instructions_per_frame: usize,
+    /// Instructions run since the timers last ticked.
+    executed: usize,
+    breakpoints: Vec<u16>,
+    pub opcode_breaks: Vec<OpcodePattern>,
+    /// Set once the core has panicked; nothing more runs.
+    error: Option<String>,
+}
+impl Debugger {
+    pub fn new(
+        arch: Architecture,
+        rom: Vec<u8>,
+        rom_path: &str,
+        instructions_per_frame: usize,
+    ) -> Self {
+        Debugger {
+            arch,
+            rom,
+            rom_path: rom_path.to_string(),
+            notes: Notes::default(),
+            instructions_per_frame,
+            executed: 0,
+            breakpoints: Vec::new(),
+            opcode_breaks: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Reads commands until `quit` or the end of `input`.
+    pub fn run(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "chip-n-claw debugger, type `help`")?;
+        self.arch.write_state(out)?;
+        write!(out, "> ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let command = line?;
+            let command = command.trim();
+            if command == "quit" {
+                break;
+            }
+            out.write_all(self.execute(command).as_bytes())?;
+            write!(out, "> ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    pub fn execute(&mut self, command: &str) -> String {
+        let (name, argument) = command.split_once(' ').unwrap_or((command, ""));
+        let argument = argument.trim();
+        match name {
+            "step" | "s" => {
+                let count = if argument.is_empty() {
+                    Some(1)
+                } else {
+                    argument.parse().ok()
+                };
+                let Some(count) = count else {
+                    return format!("`{}` is not a number of instructions\n", argument);
+                };
+                self.resume(Some(count))
+            }
+            "continue" | "c" => self.resume(None),
+            "break" | "clear" => {
+                let Ok(address) = u16::from_str_radix(argument, 16) else {
+                    return format!("`{}` is not a hex address\n", argument);
+                };
+                let present = self.breakpoints.contains(&address);
+                match name {
+                    "break" if !present => self.breakpoints.push(address),
+                    "clear" if present => self.breakpoints.retain(|&a| a != address),
+                    "clear" => return format!("no breakpoint at {:03X}\n", address),
+                    _ => {}
+                }
+                String::new()
+            }
+            "break-op" | "unbreak-op" => {
+                let Some(pattern) = OpcodePattern::parse(argument) else {
+                    return format!("`{}` is not an opcode pattern\n", argument);
+                };
+                let present = self.opcode_breaks.contains(&pattern);
+                match name {
+                    "break-op" if !present => self.opcode_breaks.push(pattern),
+                    "unbreak-op" if present => self.opcode_breaks.retain(|p| *p != pattern),
+                    "unbreak-op" => return format!("no break on {}\n", pattern),
+                    _ => {}
+                }
+                String::new()
+            }
+            "breaks" => {
+                let addresses = self.breakpoints.iter().map(|a| format!("{:03X}\n", a));
+                let patterns = self.opcode_breaks.iter().map(|p| format!("{}\n", p));
+                addresses.chain(patterns).collect()
+            }
+            "press" | "release" => {
+                let Ok(key) = u8::from_str_radix(argument, 16) else {
+                    return format!("`{}` is not a key, 0 to F\n", argument);
+                };
+                if key > 0xF {
+                    return format!("`{}` is not a key, 0 to F\n", argument);
+                }
+                self.arch.set_key(key, name == "press");
+                String::new()
+            }
+            "regs" | "r" => {
+                let mut out = Vec::new();
+                let _ = self.arch.write_state(&mut out);
+                String::from_utf8_lossy(&out).into_owned()
+            }
+            "mem" | "m" => {
+                let range = argument.split_once(' ').and_then(|(start, length)| {
+                    let start = usize::from_str_radix(start, 16).ok()?;
+                    let length = usize::from_str_radix(length.trim(), 16).ok()?;
+                    Some((start, length))
+                });
+                let Some((start, length)) = range else {
+                    return "mem expects a start address and a length, in hex\n".to_string();
+                };
+                self.dump(start, length)
+            }
+            "list" | "l" => {
+                let mut out = Vec::new();
+                let pc = usize::from(self.arch.pc());
+                let _ = disasm::write_around(&mut out, &self.rom, pc, 4, &self.notes);
+                String::from_utf8_lossy(&out).into_owned()
+            }
+            "comment" | "region" => {
+                if !self.notes.apply(command) {
+                    let usage = match name {
+                        "comment" => "comment ADDR TEXT",
+                        _ => "region START-END NAME",
+                    };
+                    return format!("expected `{}`\n", usage);
+                }
+                self.save_notes()
+            }
+            "unregion" => {
+                let start = usize::from_str_radix(argument, 16).ok();
+                if !start.is_some_and(|start| self.notes.remove_region(start)) {
+                    return format!("no region starts at `{}`\n", argument);
+                }
+                self.save_notes()
+            }
+            "notes" => self
+                .notes
+                .lines()
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect(),
+            "help" => HELP.to_string(),
+            "" => String::new(),
+            _ => format!("unknown command `{}`, try `help`\n", command),
+        }
+    }
+
+    /// Runs `count` instructions, or until something stops it when there
+    /// is no count, and says why it stopped.
+    fn resume(&mut self, count: Option<usize>) -> String {
+        if let Some(error) = &self.error {
+            return format!("the machine crashed: {}\n", error);
+        }
+        let mut remaining = count;
+        let mut first = true;
+        loop {
+            if let Some(fault) = self.arch.fault() {
+                return format!("the machine faulted: {}\n", fault);
+            }
+            if self.arch.halted() {
+                return "halted\n".to_string();
+            }
+            if self.arch.waiting_for_key() {
+                return "waiting for a key\n".to_string();
+            }
+            if remaining == Some(0) {
+                return self.location();
+            }
+            let pc = self.arch.pc();
+            // The instruction a breakpoint stopped at runs when resumed.
+            if !first && self.breakpoints.contains(&pc) {
+                return format!("breakpoint at {:03X}\n{}", pc, self.location());
+            }
+            first = false;
+            let word = self.arch.word_at(pc);
+            let ran = panic::catch_unwind(AssertUnwindSafe(|| self.arch.execute()));
+            if let Err(payload) = ran {
+                let error = crash::panic_text(&*payload);
+                self.error = Some(error.clone());
+                return format!("the machine crashed at {:03X}: {}\n", pc, error);
+            }
+            self.executed += 1;
+            if self.executed >= self.instructions_per_frame {
+                self.executed = 0;
+                self.arch.tick_timers();
+            }
+            remaining = remaining.map(|n| n - 1);
+            if let Some(pattern) = self.opcode_breaks.iter().find(|p| p.matches(word)) {
+                return format!("break on {}: {:04X} at {:03X}\n", pattern, word, pc);
+            }
+            if remaining.is_none() && self.arch.pc() == pc && !self.arch.waiting_for_key() {
+                return format!("spinning at {:03X}\n", pc);
+            }
+        }
+    }
+
+    fn save_notes(&self) -> String {
+        match self.notes.save(&self.rom_path) {
+            Ok(()) => String::new(),
+            Err(e) => format!("could not save {}: {}\n", Notes::path(&self.rom_path), e),
+        }
+    }
+
+    /// The next instruction to run, as a listing shows it, with its note.
+    fn location(&self) -> String {
+        let pc = self.arch.pc();
+        let word = self.arch.word_at(pc);
+        let mut line = match disasm::mnemonic(word) {
+            Some(mnemonic) => format!("{:03X}: {:04X}  {}", pc, word, mnemonic),
+            None => format!("{:03X}: {:04X}", pc, word),
+        };
+        if let Some(note) = self.notes.describe(usize::from(pc)) {
+            line += &format!("  ; {}", note);
+        }
+        line + "\n"
+    }
+
+    /// `length` bytes from `start`, 16 to a line, cut off at the end of RAM.
+    /// Each line ends with the note on its first address.
+    fn dump(&self, start: usize, length: usize) -> String {
+        let ram = self.arch.ram();
+        let end = start.saturating_add(length).min(ram.len());
+        let mut out = String::new();
+        let mut address = start;
+        while address < end {
+            let row = &ram[address..end.min(address + 16)];
+            out += &format!("{:03X}:", address);
+            for byte in row {
+                out += &format!(" {:02X}", byte);
+            }
+            if let Some(note) = self.notes.describe(address) {
+                out += &format!("  ; {}", note);
+            }
+            out += "\n";
+            address += row.len();
+        }
+        out
+    }
+}
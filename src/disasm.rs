@@ -0,0 +1,201 @@
+use crate::architecture::Instruction;
+pub use crate::architecture::LOAD_ADDRESS;
+use crate::notes::Notes;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// One line of a listing: an instruction, or a data byte that no path from
+/// the entry point executes.
+pub struct Line {
+    pub address: usize,
+    /// `L_0230` for jump and call targets, `DATA_0400` for I loads.
+    pub label: Option<String>,
+    pub text: String,
+}
+
+/// Cowgod-style mnemonic, or `None` for words that aren't instructions.
+pub fn mnemonic(instruction: u16) -> Option<String> {
+    Instruction::decode(instruction)
+        .ok()
+        .map(|instruction| instruction.to_string())
+}
+
+/// Where execution can continue after the instruction at `address`.
+/// `JP V0` only lists its base address, since V0 isn't known statically.
+pub fn successors(address: usize, instruction: Instruction) -> Vec<usize> {
+    let next = address + 2;
+    match instruction {
+        Instruction::Ret | Instruction::Exit => vec![],
+        Instruction::Jp { nnn } | Instruction::JpV0 { nnn } => vec![usize::from(nnn)],
+        Instruction::Call { nnn } => vec![usize::from(nnn), next],
+        // XO-CHIP's F000 is followed by its address, not an instruction.
+        Instruction::LdILong => vec![next + 2],
+        _ if instruction.is_skip() => vec![next, next + 2],
+        _ => vec![next],
+    }
+}
+
+/// Marks the ROM offsets where a reachable instruction starts, walking
+/// every path from the entry point. Whatever is never reached is data
+/// (sprites, tables) or dead code.
+pub fn code_map(rom: &[u8]) -> Vec<bool> {
+    let mut code = vec![false; rom.len()];
+    let mut pending = vec![LOAD_ADDRESS];
+    while let Some(address) = pending.pop() {
+        let Some(offset) = address.checked_sub(LOAD_ADDRESS) else {
+            continue;
+        };
+        if offset + 1 >= rom.len() || code[offset] {
+            continue;
+        }
+        let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let Ok(instruction) = Instruction::decode(word) else {
+            continue;
+        };
+        code[offset] = true;
+        pending.extend(successors(address, instruction));
+    }
+    code
+}
+
+/// Names the ROM addresses that instructions refer to: jump and call
+/// targets get an `L_` label, I loads a `DATA_` one. Addresses outside the
+/// ROM stay numeric.
+fn labels(rom: &[u8], code: &[bool]) -> BTreeMap<usize, String> {
+    let mut labels = BTreeMap::new();
+    for offset in (0..rom.len()).filter(|&offset| code[offset]) {
+        let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let Ok(instruction) = Instruction::decode(word) else {
+            continue;
+        };
+        let Some(target) = instruction.address().map(usize::from) else {
+            continue;
+        };
+        let Some(target_offset) = target.checked_sub(LOAD_ADDRESS) else {
+            continue;
+        };
+        if target_offset >= rom.len() {
+            continue;
+        }
+        match instruction {
+            Instruction::Jp { .. } | Instruction::Call { .. } | Instruction::JpV0 { .. } => {
+                labels.insert(target, format!("L_{:04X}", target));
+            }
+            Instruction::LdI { .. } => {
+                labels
+                    .entry(target)
+                    .or_insert_with(|| format!("DATA_{:04X}", target));
+            }
+            _ => {}
+        }
+    }
+    labels
+}
+
+/// The mnemonic with its address operand replaced by a label, if it has one.
+fn labelled_mnemonic(instruction: Instruction, labels: &BTreeMap<usize, String>) -> String {
+    let label = instruction
+        .address()
+        .and_then(|nnn| labels.get(&usize::from(nnn)));
+    match (instruction, label) {
+        (Instruction::Jp { .. }, Some(label)) => format!("JP {}", label),
+        (Instruction::Call { .. }, Some(label)) => format!("CALL {}", label),
+        (Instruction::LdI { .. }, Some(label)) => format!("LD I, {}", label),
+        (Instruction::JpV0 { .. }, Some(label)) => format!("JP V0, {}", label),
+        _ => instruction.to_string(),
+    }
+}
+
+/// Lists reachable instructions as mnemonics and everything else as data
+/// bytes, drawn as sprite rows so graphics are easy to spot.
+pub fn disassemble(rom: &[u8]) -> Vec<Line> {
+    let code = code_map(rom);
+    let labels = labels(rom, &code);
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let address = LOAD_ADDRESS + offset;
+        let label = labels.get(&address).cloned();
+        if code[offset] {
+            let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            let instruction = Instruction::decode(word).expect("code_map only marks instructions");
+            let mnemonic = labelled_mnemonic(instruction, &labels);
+            let text = format!("{:04X}  {}", word, mnemonic);
+            lines.push(Line {
+                address,
+                label,
+                text,
+            });
+            offset += 2;
+        } else {
+            let byte = rom[offset];
+            let sprite: String = (0..8)
+                .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+                .collect();
+            let text = format!("{:02X}    db {:02X}  ; {}", byte, byte, sprite);
+            lines.push(Line {
+                address,
+                label,
+                text,
+            });
+            offset += 1;
+        }
+    }
+    lines
+}
+
+/// Writes `context` lines either side of the one at `address`, marked
+/// with an arrow.
+pub fn write_around(
+    out: &mut impl Write,
+    rom: &[u8],
+    address: usize,
+    context: usize,
+    notes: &Notes,
+) -> io::Result<()> {
+    let lines = disassemble(rom);
+    let at = lines
+        .iter()
+        .position(|line| line.address >= address)
+        .unwrap_or(lines.len());
+    for line in lines
+        .iter()
+        .skip(at.saturating_sub(context))
+        .take(2 * context + 1)
+    {
+        let marker = if line.address == address {
+            "-> "
+        } else {
+            "   "
+        };
+        write_line(out, line, marker, notes)?;
+    }
+    Ok(())
+}
+
+/// Writes the whole ROM, one line per instruction or data byte, as
+/// `chip-n-claw disasm` prints it.
+pub fn write_listing(out: &mut impl Write, rom: &[u8], notes: &Notes) -> io::Result<()> {
+    for line in disassemble(rom) {
+        write_line(out, &line, "", notes)?;
+    }
+    Ok(())
+}
+
+/// Labels line up with the addresses, past the marker. A region gets a
+/// heading where it starts, and a comment goes at the end of its line.
+fn write_line(out: &mut impl Write, line: &Line, marker: &str, notes: &Notes) -> io::Result<()> {
+    let indent = marker.len();
+    if let Some(region) = notes.region_at(line.address) {
+        let (start, end, name) = (region.start, region.end, &region.name);
+        writeln!(out, "{:indent$}; {} ({:03X}-{:03X})", "", name, start, end)?;
+    }
+    if let Some(label) = &line.label {
+        writeln!(out, "{:indent$}{}:", "", label)?;
+    }
+    write!(out, "{}{:03X}: {}", marker, line.address, line.text)?;
+    match notes.comment(line.address) {
+        Some(comment) => writeln!(out, "  ; {}", comment),
+        None => writeln!(out),
+    }
+}
@@ -0,0 +1,639 @@
+use crate::architecture::{
+    Architecture, Fault, KeyEvent, MachineState, Outcome, Program, INSTRUCTIONS_PER_FRAME,
+};
+use crate::breakpoint::OpcodePattern;
+use crate::capture::{self, GifRecorder, VideoRecorder, WavRecorder};
+use crate::cheat::Cheats;
+use crate::events::{self, Event, Subscriber};
+use crate::fps::FpsCounter;
+use crate::frontend::{InputSource, Keypad, Palette};
+use crate::heatmap::Heatmap;
+use crate::hotkeys::{Action, Hotkeys};
+use crate::http::HttpServer;
+use crate::inputscript::{InputRecorder, InputScript};
+use crate::keypolls::KeyPolls;
+use crate::library::Playtime;
+use crate::menu::{Menu, MenuView, Navigation, Selection};
+use crate::rewind::Rewind;
+use crate::session::SessionStats;
+use crate::statehash::HashLog;
+use crate::stats::OpcodeStats;
+use crate::trigger::{Condition, Trigger};
+use std::fs;
+use std::time::Duration;
+
+pub const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+pub const DEFAULT_SLOW_MOTION: f64 = 0.25;
+/// How far one press of the rewind key goes back while running. Paused,
+/// it goes back a single frame, undoing a frame step.
+const REWIND_FRAMES: usize = 60;
+
+/// What to do once the program is over, chosen with `--on-halt`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnHalt {
+    Quit,
+    /// Open the pause menu, from which another ROM can be picked.
+    Menu,
+    /// Keep the last picture on screen, saying the game is finished.
+    Finished,
+    /// Load the ROM after this one in its directory, or quit after the last.
+    Next,
+}
+impl OnHalt {
+    /// `quit`, `menu`, `finished` or `next`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quit" => Some(OnHalt::Quit),
+            "menu" => Some(OnHalt::Menu),
+            "finished" => Some(OnHalt::Finished),
+            "next" => Some(OnHalt::Next),
+            _ => None,
+        }
+    }
+}
+
+/// Everything the frontend-facing loop keeps around besides the machine.
+pub struct Emulator {
+    pub arch: Architecture,
+    pub rom: Program,
+    pub rom_path: String,
+    pub instructions_per_frame: usize,
+    pub paused: bool,
+    /// Set by the frame-step action; the next loop iteration runs one frame
+    /// even though the emulator is paused.
+    pub step_requested: bool,
+    /// Executing an instruction that matches one of these pauses emulation.
+    pub opcode_breaks: Vec<OpcodePattern>,
+    /// Emulation pauses before running the instruction at any of these.
+    pub address_breaks: Vec<u16>,
+    /// Each captures a screenshot and a save state the first time its
+    /// condition holds, then is dropped.
+    pub triggers: Vec<Trigger>,
+    pub cheats: Cheats,
+    /// Frames run as fast as the host allows, timers and all, instead of
+    /// 60 a second.
+    pub turbo: bool,
+    /// The pause menu, while it is open.
+    pub menu: Option<Menu>,
+    /// A ROM picked from the menu, for the caller to load.
+    pub requested_rom: Option<String>,
+    /// Speed used while slow motion is on, below 1.0.
+    pub slow_motion_speed: f64,
+    pub slow_motion: bool,
+    pub message: String,
+    /// Set when the core panicked, with the panic message. Nothing more
+    /// runs, and the caller finishes the crash once the user quits.
+    pub error: Option<String>,
+    /// The frontend's colors, which screenshots, recordings and the HTTP
+    /// framebuffer are drawn in too. Changed with `set_palette`.
+    pub palette: Palette,
+    pub gif: Option<GifRecorder>,
+    pub video: Option<VideoRecorder>,
+    pub wav: Option<WavRecorder>,
+    /// Percent of full volume audio is recorded at.
+    pub volume: u8,
+    pub hash_log: Option<HashLog>,
+    /// Keys to press before each frame, replayed from a file.
+    pub input_script: Option<InputScript>,
+    pub input_recorder: Option<InputRecorder>,
+    pub stats: Option<OpcodeStats>,
+    pub heatmap: Option<Heatmap>,
+    /// Frame rate and instructions per frame, while the counter is shown.
+    pub fps: Option<FpsCounter>,
+    /// Keys the ROM reads, while the overlay is shown.
+    pub key_polls: Option<KeyPolls>,
+    /// Recent states to go back to, with `--rewind`.
+    pub rewind: Option<Rewind>,
+    /// Time played, for the ROM library, when a person is playing.
+    pub playtime: Option<Playtime>,
+    /// Totals for the summary printed on exit.
+    pub session: Option<SessionStats>,
+    pub http: Option<HttpServer>,
+    #[cfg(feature = "remote-debug")]
+    pub remote: Option<crate::remote::RemoteDebugger>,
+    /// Published and not yet taken by the caller.
+    events: Vec<Event>,
+    /// Whether the buzzer was sounding after the last frame.
+    sound_on: bool,
+    /// Whether a rewind hotkey is down. Rewinding from the menu holds
+    /// nothing, so it doesn't mute.
+    rewind_held: bool,
+    /// Set from a rewind until its key is let go, muting the buzzer.
+    rewinding: bool,
+}
+impl Emulator {
+    pub fn new(arch: Architecture, rom: Vec<u8>, rom_path: &str) -> Self {
+        Emulator {
+            arch,
+            rom: Program::new(rom),
+            rom_path: rom_path.to_string(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            paused: false,
+            step_requested: false,
+            opcode_breaks: Vec::new(),
+            address_breaks: Vec::new(),
+            triggers: Vec::new(),
+            cheats: Cheats::default(),
+            turbo: false,
+            menu: None,
+            requested_rom: None,
+            slow_motion_speed: DEFAULT_SLOW_MOTION,
+            slow_motion: false,
+            message: String::new(),
+            error: None,
+            palette: Palette::default(),
+            gif: None,
+            video: None,
+            wav: None,
+            volume: 100,
+            hash_log: None,
+            input_script: None,
+            input_recorder: None,
+            stats: None,
+            heatmap: None,
+            fps: None,
+            key_polls: None,
+            rewind: None,
+            playtime: None,
+            session: None,
+            http: None,
+            #[cfg(feature = "remote-debug")]
+            remote: None,
+            events: Vec::new(),
+            sound_on: false,
+            rewind_held: false,
+            rewinding: false,
+        }
+    }
+
+    /// Tells everything following the machine about `event`: the
+    /// emulator's own recorders and debuggers at once, and the frontend
+    /// once the caller passes on [`take_events`](Self::take_events).
+    pub fn publish(&mut self, event: Event) {
+        let (arch, message) = (&self.arch, &mut self.message);
+        notify(&mut self.gif, "GIF recording", &event, arch, message);
+        notify(&mut self.video, "Video recording", &event, arch, message);
+        notify(&mut self.wav, "Audio recording", &event, arch, message);
+        notify(&mut self.hash_log, "State hashing", &event, arch, message);
+        notify(&mut self.rewind, "Rewind", &event, arch, message);
+        #[cfg(feature = "remote-debug")]
+        notify(&mut self.remote, "Remote debugging", &event, arch, message);
+        self.events.push(event);
+    }
+
+    /// The events published since the last call, oldest first. Call it
+    /// once per frame, or they pile up.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Runs just the instruction at PC, for a debugger's single step. The
+    /// timers don't tick and queued keys stay queued, as both belong to
+    /// whole frames; stats and heatmaps still count it.
+    pub fn step_instruction(&mut self) {
+        if self.arch.halted() || self.arch.waiting_for_key() {
+            return;
+        }
+        let pc = self.arch.pc();
+        let instruction = self.arch.word_at(pc);
+        self.arch.execute();
+        if let Some(stats) = self.stats.as_mut() {
+            stats.record(instruction);
+        }
+        if let Some(heatmap) = self.heatmap.as_mut() {
+            heatmap.record(pc);
+        }
+        if let Some(session) = self.session.as_mut() {
+            session.ran(1);
+            if let Some(Fault::InvalidOpcode { pc, word }) = self.arch.fault() {
+                session.unknown_opcode(pc, word);
+            }
+        }
+        if events::touches_display(instruction) {
+            self.publish(Event::DisplayDirty);
+        }
+    }
+
+    pub fn run_frame(&mut self, frame: u64) -> Outcome {
+        let _span = tracing::trace_span!("frame", frame).entered();
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.push(&self.arch);
+        }
+        if let Some(script) = self.input_script.as_mut() {
+            script.apply_frame(&mut self.arch);
+        }
+        self.cheats.apply(&mut self.arch);
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            if let Err(e) = recorder.push_frame() {
+                self.message = format!("Input recording stopped: {}", e);
+                self.input_recorder = None;
+            }
+        }
+        let mut hit = None;
+        let mut reached = None;
+        let mut fired = None;
+        let mut executed = 0;
+        let was_halted = self.arch.halted();
+        let mut drew = false;
+        let outcome = self.arch.run_frame(
+            &self.rom,
+            self.instructions_per_frame,
+            |arch, pc, instruction| {
+                executed += 1;
+                drew |= events::touches_display(instruction);
+                if let Some(stats) = self.stats.as_mut() {
+                    stats.record(instruction);
+                }
+                if let Some(heatmap) = self.heatmap.as_mut() {
+                    heatmap.record(pc);
+                }
+                if let Some(key_polls) = self.key_polls.as_mut() {
+                    key_polls.record(arch, instruction);
+                }
+                let pattern = self.opcode_breaks.iter().find(|p| p.matches(instruction));
+                hit = pattern.map(|&pattern| (pattern, instruction, pc));
+                reached = Some(arch.pc()).filter(|next| self.address_breaks.contains(next));
+                for (index, trigger) in self.triggers.iter_mut().enumerate() {
+                    if trigger.check(arch, pc) {
+                        fired.get_or_insert(index);
+                    }
+                }
+                // Stop right there so the capture shows the very instruction.
+                hit.is_none() && reached.is_none() && fired.is_none()
+            },
+        );
+        if let Some(session) = self.session.as_mut() {
+            session.ran(executed);
+            if let (false, Some(Fault::InvalidOpcode { pc, word })) =
+                (was_halted, self.arch.fault())
+            {
+                session.unknown_opcode(pc, word);
+            }
+        }
+        if drew {
+            self.publish(Event::DisplayDirty);
+        }
+        let sound_on = self.arch.sound_on() && !self.rewinding;
+        if sound_on != self.sound_on {
+            self.sound_on = sound_on;
+            self.publish(if self.sound_on {
+                Event::SoundStarted
+            } else {
+                Event::SoundStopped
+            });
+        }
+        if let Some(fps) = self.fps.as_mut() {
+            fps.ran(executed);
+        }
+        if let Some(key_polls) = self.key_polls.as_mut() {
+            key_polls.end_frame(&self.arch);
+        }
+        if let Some((pattern, instruction, pc)) = hit {
+            self.message = format!("Break on {}: {:04X} at {:03X}", pattern, instruction, pc);
+            self.paused = true;
+            self.publish(Event::BreakpointHit {
+                pattern,
+                instruction,
+                pc,
+            });
+        }
+        if let Some(pc) = reached {
+            self.message = format!("Break at {:03X}", pc);
+            self.paused = true;
+            self.publish(Event::AddressReached { pc });
+        }
+        if let Some(index) = fired {
+            let trigger = self.triggers.remove(index);
+            self.capture(trigger.condition);
+        }
+        self.publish(Event::FrameEnded);
+        outcome
+    }
+
+    /// Feeds the source's keys to the keypad, except for hotkeys, whose
+    /// actions are returned for the caller to perform. While the menu is
+    /// open, the keys that move around it go to the menu instead.
+    pub fn handle_input(
+        &mut self,
+        source: &mut dyn InputSource,
+        hotkeys: &Hotkeys,
+        keypad: &Keypad,
+    ) -> Vec<Action> {
+        let _span = tracing::trace_span!("input").entered();
+        let mut actions = Vec::new();
+        for input in source.poll_input() {
+            let navigation = Navigation::from_key(&input.key, keypad.key(&input.key));
+            if let Some(menu) = self.menu.as_mut().filter(|_| input.pressed) {
+                if let Some(navigation) = navigation {
+                    match menu.navigate(navigation) {
+                        Some(Selection::Resume) => self.close_menu(),
+                        Some(Selection::Action(action)) => {
+                            if !matches!(action, Action::Turbo | Action::SlowMotion) {
+                                self.close_menu();
+                            }
+                            actions.push(action);
+                        }
+                        Some(Selection::LoadRom(path)) => {
+                            self.close_menu();
+                            self.requested_rom = Some(path);
+                        }
+                        None => {}
+                    }
+                    continue;
+                }
+            }
+            if let Some(action) = hotkeys.action(&input.key) {
+                if action == Action::Rewind {
+                    self.rewind_held = input.pressed;
+                }
+                if input.pressed {
+                    actions.push(action);
+                } else if action == Action::Rewind && self.rewinding {
+                    self.rewinding = false;
+                    self.publish(Event::RewindEnded);
+                }
+            } else if let Some(key) = keypad.key(&input.key) {
+                let pressed = input.pressed;
+                self.arch.queue_key(KeyEvent { key, pressed });
+                if let Some(recorder) = self.input_recorder.as_mut() {
+                    recorder.record(key, pressed);
+                }
+            }
+        }
+        actions
+    }
+
+    fn close_menu(&mut self) {
+        if let Some(menu) = self.menu.take() {
+            self.paused = menu.was_paused;
+        }
+    }
+
+    /// What the open menu shows.
+    pub fn menu_view(&self) -> Option<MenuView> {
+        let menu = self.menu.as_ref()?;
+        Some(menu.view(self.turbo, self.slow_motion))
+    }
+
+    /// Carries out the actions that don't need anything from outside the
+    /// emulator. Quit, Reset and palette changes are left to the caller.
+    pub fn perform(&mut self, action: Action) {
+        match action {
+            Action::Pause => self.paused = !self.paused,
+            Action::FrameStep => {
+                if self.paused {
+                    self.step_requested = true;
+                } else {
+                    self.paused = true;
+                }
+            }
+            Action::Menu => {
+                if self.menu.is_some() {
+                    self.close_menu();
+                } else {
+                    self.menu = Some(Menu::new(&self.rom_path, self.paused));
+                    self.paused = true;
+                }
+            }
+            Action::ShowFps => {
+                self.fps = match self.fps {
+                    Some(_) => None,
+                    None => Some(FpsCounter::new()),
+                }
+            }
+            Action::ShowKeys => {
+                self.key_polls = match self.key_polls {
+                    Some(_) => None,
+                    None => Some(KeyPolls::new()),
+                }
+            }
+            Action::Turbo => {
+                self.turbo = !self.turbo;
+                self.publish(Event::SpeedChanged);
+            }
+            Action::SlowMotion => {
+                self.slow_motion = !self.slow_motion;
+                self.publish(Event::SpeedChanged);
+            }
+            Action::Screenshot => {
+                let path = capture::capture_path(&self.rom_path, "ppm");
+                let saved = capture::save_screenshot(self.arch.display(), self.palette, &path);
+                self.message = match saved {
+                    Ok(()) => format!("Screenshot saved to {}", path),
+                    Err(e) => format!("Could not save screenshot: {}", e),
+                };
+            }
+            Action::RecordGif => {
+                if self.gif.take().is_some() {
+                    self.message = "GIF recording saved".to_string();
+                } else {
+                    let path = capture::capture_path(&self.rom_path, "gif");
+                    self.start_gif(&path);
+                }
+            }
+            Action::RecordVideo => {
+                if self.video.take().is_some() {
+                    self.message = "Video recording saved".to_string();
+                } else {
+                    let path = capture::capture_path(&self.rom_path, "mp4");
+                    self.start_video(&path);
+                }
+            }
+            Action::RecordAudio => {
+                if self.wav.take().is_some() {
+                    self.message = "Audio recording saved".to_string();
+                } else {
+                    let path = capture::capture_path(&self.rom_path, "wav");
+                    self.start_wav(&path);
+                }
+            }
+            Action::SaveState => {
+                self.message = match fs::write(self.state_path(), self.arch.save_state()) {
+                    Ok(()) => "State saved".to_string(),
+                    Err(e) => format!("Could not save state: {}", e),
+                };
+            }
+            Action::LoadState => {
+                let loaded =
+                    fs::read(self.state_path()).and_then(|bytes| Architecture::load_state(&bytes));
+                self.message = match loaded {
+                    Ok(arch) => {
+                        self.arch = arch;
+                        self.publish(Event::StateLoaded);
+                        "State loaded".to_string()
+                    }
+                    Err(e) => format!("Could not load state: {}", e),
+                };
+            }
+            Action::Rewind => {
+                let frames = if self.paused { 1 } else { REWIND_FRAMES };
+                let Some(rewind) = self.rewind.as_mut() else {
+                    self.message = "Rewind is off, start with --rewind SECONDS".to_string();
+                    return;
+                };
+                self.message = match rewind.back(frames) {
+                    Some(arch) => {
+                        self.arch = arch;
+                        self.publish(Event::StateLoaded);
+                        if self.rewind_held && !self.rewinding {
+                            self.rewinding = true;
+                            self.publish(Event::RewindStarted);
+                        }
+                        "Rewound".to_string()
+                    }
+                    None => "Nothing left to rewind".to_string(),
+                };
+            }
+            Action::Quit | Action::Reset | Action::CyclePalette | Action::Fullscreen => {}
+        }
+    }
+
+    fn capture(&mut self, condition: Condition) {
+        let screenshot = capture::capture_path(&self.rom_path, "ppm");
+        let state = capture::capture_path(&self.rom_path, "state");
+        let saved = capture::save_screenshot(self.arch.display(), self.palette, &screenshot)
+            .and_then(|()| fs::write(&state, self.arch.save_state()));
+        self.message = match saved {
+            Ok(()) => format!("{} held: saved {} and {}", condition, screenshot, state),
+            Err(e) => format!("{} held but the capture failed: {}", condition, e),
+        };
+    }
+
+    pub fn start_gif(&mut self, path: &str) {
+        self.message = match GifRecorder::create(path, self.palette) {
+            Ok(gif) => {
+                self.gif = Some(gif);
+                format!("Recording GIF to {}", path)
+            }
+            Err(e) => format!("Could not record GIF: {}", e),
+        };
+    }
+
+    /// Switches the colors captures are drawn in, recordings included.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        if let Some(gif) = self.gif.as_mut() {
+            if let Err(e) = gif.set_palette(palette) {
+                self.message = format!("GIF recording stopped: {}", e);
+                self.gif = None;
+            }
+        }
+        if let Some(video) = self.video.as_mut() {
+            video.palette = palette;
+        }
+    }
+
+    pub fn start_video(&mut self, path: &str) {
+        self.message = match VideoRecorder::create(path, self.palette, self.volume) {
+            Ok(video) => {
+                self.video = Some(video);
+                format!("Recording video to {}", path)
+            }
+            Err(e) => format!("Could not start ffmpeg: {}", e),
+        };
+    }
+
+    pub fn start_wav(&mut self, path: &str) {
+        self.message = match WavRecorder::create(path, self.volume) {
+            Ok(wav) => {
+                self.wav = Some(wav);
+                format!("Recording audio to {}", path)
+            }
+            Err(e) => format!("Could not record audio: {}", e),
+        };
+    }
+
+    /// How long one emulated frame should last in real time, when the
+    /// frame rate is being stretched. Timers still tick once per emulated
+    /// frame, so everything slows down together.
+    pub fn slowed_frame_duration(&self) -> Option<Duration> {
+        self.slow_motion
+            .then(|| FRAME.div_f64(self.slow_motion_speed))
+    }
+
+    pub fn status(&self) -> String {
+        let mut status = Vec::new();
+        if self.paused {
+            status.push("PAUSED");
+        }
+        if self.turbo {
+            status.push("TURBO");
+        }
+        let slow_motion = format!("SLOW x{}", self.slow_motion_speed);
+        if self.slow_motion {
+            status.push(&slow_motion);
+        }
+        let error = self
+            .error
+            .as_ref()
+            .map(|error| format!("CRASHED: {} (quit to write a core dump)", error));
+        let fault = match self.arch.state() {
+            MachineState::Faulted(fault) => Some(format!("FAULT: {}", fault)),
+            _ => None,
+        };
+        match (error.as_ref().or(fault.as_ref()), self.arch.state()) {
+            (Some(error), _) => status.push(error),
+            (None, MachineState::WaitingForKey) => status.push("WAITING FOR KEY"),
+            (None, MachineState::Halted) => status.push("HALTED"),
+            (None, MachineState::Running | MachineState::Faulted(_)) => {}
+        }
+        if self.gif.is_some() || self.video.is_some() || self.wav.is_some() {
+            status.push("REC");
+        }
+        if let Some(fps) = self.fps.as_ref() {
+            status.push(fps.text());
+        }
+        let keys = self.key_polls.as_ref().map(|polls| polls.text(&self.arch));
+        if let Some(keys) = &keys {
+            status.push(keys);
+        }
+        if !self.message.is_empty() {
+            status.push(&self.message);
+        }
+        status.join(" | ")
+    }
+
+    /// Whether a frame that ran left the program over: it ran EXIT or,
+    /// with `jump_to_self` set, it is jumping to itself with no key to
+    /// wait for, the way many ROMs end.
+    pub fn finished(&self, outcome: Outcome, jump_to_self: bool) -> bool {
+        match outcome {
+            Outcome::Halted => true,
+            Outcome::Idle => jump_to_self && !self.arch.waiting_for_key(),
+            _ => false,
+        }
+    }
+
+    /// `running`, `waiting_for_key`, `halted`, `faulted` or `error`, the
+    /// last when the core itself panicked, for tools.
+    pub fn state_name(&self) -> &'static str {
+        if self.error.is_some() {
+            return "error";
+        }
+        match self.arch.state() {
+            MachineState::Running => "running",
+            MachineState::WaitingForKey => "waiting_for_key",
+            MachineState::Halted => "halted",
+            MachineState::Faulted(_) => "faulted",
+        }
+    }
+
+    fn state_path(&self) -> String {
+        format!("{}.state", self.rom_path)
+    }
+}
+
+/// Hands `event` to `subscriber`, if there is one, and drops it with a
+/// message if it fails.
+fn notify(
+    subscriber: &mut Option<impl Subscriber>,
+    name: &str,
+    event: &Event,
+    arch: &Architecture,
+    message: &mut String,
+) {
+    if let Some(Err(e)) = subscriber.as_mut().map(|s| s.notify(event, arch)) {
+        *message = format!("{} stopped: {}", name, e);
+        *subscriber = None;
+    }
+}
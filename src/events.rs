@@ -0,0 +1,64 @@
+use crate::architecture::Architecture;
+use crate::breakpoint::OpcodePattern;
+use std::io;
+
+/// Something that happened to the machine which parts outside the core
+/// react to. The emulator publishes them as they happen, so recorders,
+/// frontends and debuggers don't each need to be called from every place
+/// that resets or reloads the machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The frame that just ran drew to the display, cleared or scrolled it.
+    DisplayDirty,
+    SoundStarted,
+    SoundStopped,
+    /// The machine was swapped for one from a save state or the rewind
+    /// buffer.
+    StateLoaded,
+    /// The machine started over on the same ROM.
+    Reset,
+    /// Another ROM was loaded, or the same one changed on disk.
+    RomReloaded,
+    /// The rewind key went down and took the machine back. The buzzer is
+    /// muted until `RewindEnded`, so the replayed past doesn't beep.
+    RewindStarted,
+    /// The rewind key was let go.
+    RewindEnded,
+    /// Emulation paused after an instruction matching an opcode break.
+    BreakpointHit {
+        pattern: OpcodePattern,
+        instruction: u16,
+        pc: u16,
+    },
+    /// Emulation paused before the instruction at an address break.
+    AddressReached {
+        pc: u16,
+    },
+    /// Turbo or slow motion was switched on or off.
+    SpeedChanged,
+    /// A frame finished running, so recorders can take what it left.
+    FrameEnded,
+}
+impl Event {
+    /// Whether the machine jumped somewhere its past has nothing to do
+    /// with, so anything following it along has to start over.
+    pub fn replaces_machine(&self) -> bool {
+        matches!(self, Event::StateLoaded | Event::Reset | Event::RomReloaded)
+    }
+}
+
+/// Anything that reacts to events. The emulator hands them to its own
+/// subscribers as they are published; the caller passes on the rest with
+/// [`Emulator::take_events`](crate::emulator::Emulator::take_events).
+pub trait Subscriber {
+    /// `arch` is the machine as the event left it. An error unsubscribes
+    /// the subscriber, and the emulator's message says why.
+    fn notify(&mut self, event: &Event, arch: &Architecture) -> io::Result<()>;
+}
+
+/// Whether `word` draws, clears or scrolls, for [`Event::DisplayDirty`].
+pub fn touches_display(word: u16) -> bool {
+    word & 0xF000 == 0xD000
+        || matches!(word, 0x00E0 | 0x00FB | 0x00FC | 0x00FE | 0x00FF)
+        || matches!(word & 0xFFF0, 0x00C0 | 0x00D0)
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::io;
+
+/// 16 glyphs (0-F) of 5 rows each.
+pub const FONT_SIZE: usize = 16 * 5;
+
+/// Loads a replacement hex font.
+///
+/// A file of exactly 80 bytes is taken as raw sprite data. Anything else is
+/// read as text: each glyph is five rows of up to eight `#` (on) or `.` (off)
+/// characters, glyphs follow each other in order 0 to F, and blank lines or
+/// lines starting with `;` are ignored.
+pub fn load_font(path: &str) -> io::Result<[u8; FONT_SIZE]> {
+    let bytes = fs::read(path)?;
+    if bytes.len() == FONT_SIZE {
+        let mut font = [0u8; FONT_SIZE];
+        font.copy_from_slice(&bytes);
+        return Ok(font);
+    }
+    let text =
+        String::from_utf8(bytes).map_err(|_| invalid("font is neither 80 bytes nor text"))?;
+    parse_text_font(&text)
+}
+
+fn parse_text_font(text: &str) -> io::Result<[u8; FONT_SIZE]> {
+    let mut font = [0u8; FONT_SIZE];
+    let mut rows = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if rows == FONT_SIZE {
+            return Err(invalid("font has more than 16 glyphs"));
+        }
+        if line.len() > 8 {
+            return Err(invalid("font rows are at most 8 pixels wide"));
+        }
+        for (column, pixel) in line.chars().enumerate() {
+            match pixel {
+                '#' => font[rows] |= 0x80 >> column,
+                '.' => {}
+                _ => return Err(invalid("font rows may only contain '#' and '.'")),
+            }
+        }
+        rows += 1;
+    }
+    if rows != FONT_SIZE {
+        return Err(invalid("font must have 16 glyphs of 5 rows"));
+    }
+    Ok(font)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+/// How often the counter's figures are updated.
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Frames and instructions over the last second, shown on the status line
+/// to help pick a `--speed` for a game. There is no audio buffer to report
+/// on: the browser build only tells the page whether to beep, and
+/// `--record-audio` writes samples as frames run, so it can't fall behind.
+pub struct FpsCounter {
+    window_start: Instant,
+    frames: u32,
+    drawn: u32,
+    instructions: usize,
+    /// The figures for the last full window, once there is one.
+    shown: Option<String>,
+}
+impl FpsCounter {
+    pub fn new() -> Self {
+        FpsCounter {
+            window_start: Instant::now(),
+            frames: 0,
+            drawn: 0,
+            instructions: 0,
+            shown: None,
+        }
+    }
+
+    /// Records an emulated frame and how many instructions it ran.
+    pub fn ran(&mut self, instructions: usize) {
+        self.frames += 1;
+        self.instructions += instructions;
+    }
+
+    /// Called once per pass of the run loop, whether a frame ran or not.
+    pub fn end_frame(&mut self, drawn: bool) {
+        self.drawn += u32::from(drawn);
+        let elapsed = self.window_start.elapsed();
+        if elapsed < WINDOW {
+            return;
+        }
+        let seconds = elapsed.as_secs_f64();
+        let per_frame = self.instructions / (self.frames.max(1) as usize);
+        self.shown = Some(format!(
+            "{:.0} FPS ({:.0} drawn) {} IPF",
+            f64::from(self.frames) / seconds,
+            f64::from(self.drawn) / seconds,
+            per_frame
+        ));
+        self.window_start = Instant::now();
+        self.frames = 0;
+        self.drawn = 0;
+        self.instructions = 0;
+    }
+
+    pub fn text(&self) -> &str {
+        self.shown.as_deref().unwrap_or("-- FPS")
+    }
+}
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,224 @@
+pub mod ascii;
+#[cfg(feature = "gpio-keypad")]
+pub mod button_matrix;
+#[cfg(feature = "fbdev")]
+pub mod fbdev;
+#[cfg(feature = "gl")]
+pub mod gl;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+#[cfg(feature = "led-matrix")]
+pub mod led_matrix;
+#[cfg(feature = "oled")]
+pub mod oled;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+#[cfg(any(feature = "wgpu", feature = "gl"))]
+pub mod window;
+
+use crate::events::Event;
+use crate::menu::MenuView;
+
+/// A host key going down or up, named the way hotkey files spell it:
+/// uppercase letters and digits, `F1`-`F12`, `Escape`, `Space`, `Up`,
+/// `Ctrl+C`...
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyInput {
+    pub key: String,
+    pub pressed: bool,
+}
+
+/// Anything keys come from: a frontend, or a keypad wired up on its own.
+pub trait InputSource {
+    /// Returns the key presses and releases since the last call, without blocking.
+    fn poll_input(&mut self) -> Vec<KeyInput>;
+}
+
+pub trait Frontend: InputSource {
+    /// Shows the 64x32 or 128x64 display (one byte per pixel) and a one-line status.
+    fn present(&mut self, display: &[u8], status: &str);
+
+    /// Shows the display with the pause menu open. Frontends that can't
+    /// draw text over the display get the highlighted item as their status.
+    fn present_menu(&mut self, display: &[u8], _status: &str, menu: &MenuView) {
+        self.present(display, &menu.status_line());
+    }
+
+    /// Switches colors at runtime. Frontends without colors ignore it.
+    fn set_palette(&mut self, _palette: Palette) {}
+
+    /// Switches between a window and the whole screen. False if the
+    /// frontend has no window to switch.
+    fn toggle_fullscreen(&mut self) -> bool {
+        false
+    }
+
+    /// Hears about what happened to the machine, after each frame.
+    /// Frontends that don't care ignore it.
+    fn notify(&mut self, _event: &Event) {}
+}
+
+/// The usual layout of the COSMAC VIP hex keypad on a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  <-  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+const KEYPAD: [(&str, u8); 16] = [
+    ("1", 0x1),
+    ("2", 0x2),
+    ("3", 0x3),
+    ("4", 0xC),
+    ("Q", 0x4),
+    ("W", 0x5),
+    ("E", 0x6),
+    ("R", 0xD),
+    ("A", 0x7),
+    ("S", 0x8),
+    ("D", 0x9),
+    ("F", 0xE),
+    ("Z", 0xA),
+    ("X", 0x0),
+    ("C", 0xB),
+    ("V", 0xF),
+];
+
+/// Maps host key names to CHIP-8 keys, starting from the QWERTY layout.
+pub struct Keypad {
+    bindings: Vec<(String, u8)>,
+}
+impl Keypad {
+    pub fn new() -> Self {
+        let bindings = KEYPAD
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        Keypad { bindings }
+    }
+
+    /// Moves CHIP-8 `key` to `host_key`. The key loses its default host
+    /// key, and `host_key` stops sending whatever it sent before.
+    pub fn bind(&mut self, key: u8, host_key: &str) {
+        self.bindings
+            .retain(|(name, value)| *value != key && name != host_key);
+        self.bindings.push((host_key.to_string(), key));
+    }
+
+    pub fn key(&self, host_key: &str) -> Option<u8> {
+        self.bindings
+            .iter()
+            .find(|(name, _)| name == host_key)
+            .map(|(_, value)| *value)
+    }
+}
+impl Default for Keypad {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Colors for each value a display pixel can have: 0 is unlit, 1 the first
+/// XO-CHIP plane (and every lit pixel on other platforms), 2 the second
+/// plane and 3 both. Frontends without one use the terminal's own colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub colors: [(u8, u8, u8); 4],
+}
+/// Named palettes for `--palette` and the cycle hotkey, in cycling order.
+/// Besides the classic look, they are picked for low vision and for red-green
+/// color blindness, with the XO-CHIP planes told apart by lightness as well
+/// as hue.
+const PRESETS: [(&str, Palette); 4] = [
+    (
+        "classic",
+        Palette {
+            colors: [
+                (0x00, 0x00, 0x00),
+                (0xFF, 0xFF, 0xFF),
+                (0xAA, 0xAA, 0xAA),
+                (0x55, 0x55, 0x55),
+            ],
+        },
+    ),
+    (
+        "high-contrast",
+        Palette {
+            colors: [
+                (0x00, 0x00, 0x00),
+                (0xFF, 0xFF, 0x00),
+                (0x00, 0xFF, 0xFF),
+                (0xFF, 0xFF, 0xFF),
+            ],
+        },
+    ),
+    (
+        "inverted",
+        Palette {
+            colors: [
+                (0xFF, 0xFF, 0xFF),
+                (0x00, 0x00, 0x00),
+                (0x55, 0x55, 0x55),
+                (0xAA, 0xAA, 0xAA),
+            ],
+        },
+    ),
+    // Blue and orange stay distinct without red-green vision; the second
+    // plane is a light blue and the overlap white.
+    (
+        "deuteranopia",
+        Palette {
+            colors: [
+                (0x00, 0x2B, 0x70),
+                (0xFF, 0xB0, 0x00),
+                (0x56, 0xB4, 0xE9),
+                (0xFF, 0xFF, 0xFF),
+            ],
+        },
+    ),
+];
+
+impl Palette {
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// The color of a pixel with this value.
+    pub fn color(&self, pixel: u8) -> (u8, u8, u8) {
+        self.colors[usize::from(pixel & 3)]
+    }
+
+    pub fn preset_names() -> Vec<&'static str> {
+        PRESETS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// The preset after `current` and its name. Palettes that aren't
+    /// presets, like ones from a sidecar file, go back to the first.
+    pub fn next_preset(current: Option<Palette>) -> (&'static str, Palette) {
+        let current = current.unwrap_or_default();
+        let next = PRESETS
+            .iter()
+            .position(|(_, palette)| *palette == current)
+            .map_or(0, |index| (index + 1) % PRESETS.len());
+        PRESETS[next]
+    }
+
+    /// Parses `#RRGGBB`.
+    pub fn parse_color(text: &str) -> Option<(u8, u8, u8)> {
+        let hex = text.strip_prefix('#')?;
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).ok();
+        Some((channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+impl Default for Palette {
+    fn default() -> Self {
+        PRESETS[0].1
+    }
+}
@@ -0,0 +1,63 @@
+use super::{Frontend, InputSource, KeyInput};
+use crate::architecture::display_size;
+use std::io::{self, Stdout, Write};
+
+/// What each pixel value prints as.
+const GLYPHS: [char; 4] = [' ', '#', '+', '@'];
+
+/// Prints every new frame to stdout as plain text: 32 rows of `#` and
+/// spaces, or 64 in high resolution, then the status line. XO-CHIP's second
+/// plane is drawn with `+` and the two overlapping with `@`. No escape
+/// sequences and no raw mode, so it works on dumb terminals, in CI logs and
+/// through pipes, but it can't read keys either.
+pub struct Ascii {
+    stdout: Stdout,
+    previous_frame: Vec<u8>,
+    previous_status: String,
+}
+impl Ascii {
+    pub fn new() -> Self {
+        Ascii {
+            stdout: io::stdout(),
+            previous_frame: Vec::new(),
+            previous_status: String::new(),
+        }
+    }
+
+    fn draw(&mut self, display: &[u8], status: &str) -> io::Result<()> {
+        let mut out = self.stdout.lock();
+        let (width, _) = display_size(display);
+        for row in display.chunks(width) {
+            let line: String = row
+                .iter()
+                .map(|&pixel| GLYPHS[usize::from(pixel & 3)])
+                .collect();
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        writeln!(out, "{}", status)?;
+        out.flush()
+    }
+}
+impl Default for Ascii {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl InputSource for Ascii {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        Vec::new()
+    }
+}
+impl Frontend for Ascii {
+    fn present(&mut self, display: &[u8], status: &str) {
+        // Only changes are printed, or a log would fill up with copies of
+        // the same frame.
+        if display == self.previous_frame && status == self.previous_status {
+            return;
+        }
+        if self.draw(display, status).is_ok() {
+            self.previous_frame = display.to_vec();
+            self.previous_status = status.to_string();
+        }
+    }
+}
@@ -0,0 +1,71 @@
+pub mod cdev;
+
+use super::{InputSource, KeyInput, KEYPAD};
+use embedded_hal::digital::{InputPin, OutputPin};
+
+/// A 4x4 keypad wired as a matrix, laid out like the COSMAC VIP's:
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+///
+/// Rows are pulled low one at a time, and a pressed button pulls its column
+/// low with them, so the columns need pull-ups. Buttons report the QWERTY
+/// key in the same position (`1`, `Q`, `A`...), so keypad bindings apply to
+/// them as they do to the keyboard.
+pub struct ButtonMatrix<O, I> {
+    rows: [O; 4],
+    columns: [I; 4],
+    pressed: [bool; 16],
+}
+
+impl<O: OutputPin, I: InputPin> ButtonMatrix<O, I> {
+    pub fn new(mut rows: [O; 4], columns: [I; 4]) -> Result<Self, O::Error> {
+        for row in &mut rows {
+            row.set_high()?;
+        }
+        Ok(ButtonMatrix {
+            rows,
+            columns,
+            pressed: [false; 16],
+        })
+    }
+}
+impl<O: OutputPin, I: InputPin> InputSource for ButtonMatrix<O, I> {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        let mut inputs = Vec::new();
+        for (r, row) in self.rows.iter_mut().enumerate() {
+            if row.set_low().is_err() {
+                continue;
+            }
+            for (c, column) in self.columns.iter_mut().enumerate() {
+                let button = r * 4 + c;
+                // A pin that can't be read counts as released.
+                let pressed = column.is_low().unwrap_or(false);
+                if pressed != self.pressed[button] {
+                    self.pressed[button] = pressed;
+                    let key = KEYPAD[button].0.to_string();
+                    inputs.push(KeyInput { key, pressed });
+                }
+            }
+            let _ = row.set_high();
+        }
+        inputs
+    }
+}
+
+/// Parses `ROWS/COLUMNS` pin lists, like `5,6,13,19/12,16,20,21`.
+pub fn parse_pins(spec: &str) -> Option<([u32; 4], [u32; 4])> {
+    let pins = |list: &str| -> Option<[u32; 4]> {
+        let pins: Vec<u32> = list
+            .split(',')
+            .map(|pin| pin.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        pins.try_into().ok()
+    };
+    let (rows, columns) = spec.split_once('/')?;
+    Some((pins(rows)?, pins(columns)?))
+}
@@ -0,0 +1,66 @@
+use super::ButtonMatrix;
+use embedded_hal::digital::{self, ErrorKind, ErrorType, InputPin, OutputPin};
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+
+/// The GPIO controller behind the Raspberry Pi's header pins, which are
+/// numbered as on the BCM pinout.
+pub const CHIP: &str = "/dev/gpiochip0";
+
+/// One GPIO line from the Linux character device.
+pub struct CdevPin(LineHandle);
+
+#[derive(Debug)]
+pub struct Error(pub gpio_cdev::Error);
+impl digital::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl ErrorType for CdevPin {
+    type Error = Error;
+}
+impl OutputPin for CdevPin {
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.0.set_value(0).map_err(Error)
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.0.set_value(1).map_err(Error)
+    }
+}
+impl InputPin for CdevPin {
+    fn is_high(&mut self) -> Result<bool, Error> {
+        Ok(self.0.get_value().map_err(Error)? != 0)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+/// Claims the row and column lines on `CHIP`. Rows are open drain, so two
+/// buttons held in one column can't short a driven row to a high one.
+pub fn open(
+    rows: [u32; 4],
+    columns: [u32; 4],
+) -> Result<ButtonMatrix<CdevPin, CdevPin>, gpio_cdev::Error> {
+    let mut chip = Chip::new(CHIP)?;
+    let output = LineRequestFlags::OUTPUT | LineRequestFlags::OPEN_DRAIN;
+    let rows = lines(&mut chip, rows, output)?;
+    let columns = lines(&mut chip, columns, LineRequestFlags::INPUT)?;
+    ButtonMatrix::new(rows, columns).map_err(|e| e.0)
+}
+
+fn lines(
+    chip: &mut Chip,
+    offsets: [u32; 4],
+    flags: LineRequestFlags,
+) -> Result<[CdevPin; 4], gpio_cdev::Error> {
+    let mut pins = Vec::new();
+    for offset in offsets {
+        let handle = chip.get_line(offset)?.request(flags, 1, "chip-n-claw")?;
+        pins.push(CdevPin(handle));
+    }
+    Ok(pins.try_into().unwrap_or_else(|_| unreachable!()))
+}
@@ -0,0 +1,218 @@
+use super::{Frontend, InputSource, KeyInput, Palette};
+use crate::architecture::{display_size, fit_display, HEIGHT, WIDTH};
+use evdev::{Device, EventSummary, KeyCode};
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+
+const DEVICE: &str = "/dev/fb0";
+/// Where the kernel describes the framebuffer's geometry and pixel format.
+const SYSFS: &str = "/sys/class/graphics/fb0";
+
+/// Draws straight onto the Linux framebuffer and reads keyboards through
+/// evdev, so a Raspberry Pi can run chip-n-claw as a console without X or
+/// Wayland. The display is scaled by the largest whole factor that fits and
+/// centered; there is no room for the status line.
+pub struct Framebuffer {
+    file: File,
+    height: usize,
+    stride: usize,
+    bytes_per_pixel: usize,
+    scale: usize,
+    /// Top left corner of the scaled display, in screen pixels.
+    origin: (usize, usize),
+    palette: Palette,
+    keyboards: Vec<Device>,
+    ctrl: bool,
+    previous: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(palette: Option<Palette>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(DEVICE)?;
+        let size = sysfs("virtual_size")?;
+        let (width, height) = size
+            .split_once(',')
+            .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+            .ok_or_else(|| io::Error::other(format!("unexpected framebuffer size {}", size)))?;
+        let stride = sysfs("stride")?.parse().map_err(io::Error::other)?;
+        let bytes_per_pixel = match sysfs("bits_per_pixel")?.as_str() {
+            "16" => 2,
+            "32" => 4,
+            bits => {
+                return Err(io::Error::other(format!(
+                    "{}-bit framebuffers aren't supported, only 16 and 32",
+                    bits
+                )))
+            }
+        };
+        let scale = (width / WIDTH).min(height / HEIGHT);
+        if scale == 0 {
+            return Err(io::Error::other(format!(
+                "the {}x{} framebuffer is smaller than the display",
+                width, height
+            )));
+        }
+        let origin = ((width - WIDTH * scale) / 2, (height - HEIGHT * scale) / 2);
+
+        // Every device with letter keys counts as a keyboard. Grabbing them
+        // keeps the keys from also reaching the console underneath.
+        let mut keyboards = Vec::new();
+        for (_, mut device) in evdev::enumerate() {
+            let is_keyboard = device
+                .supported_keys()
+                .is_some_and(|keys| keys.contains(KeyCode::KEY_A));
+            if is_keyboard {
+                device.set_nonblocking(true)?;
+                let _ = device.grab();
+                keyboards.push(device);
+            }
+        }
+        if keyboards.is_empty() {
+            return Err(io::Error::other("no keyboard found in /dev/input"));
+        }
+
+        let framebuffer = Framebuffer {
+            file,
+            height,
+            stride,
+            bytes_per_pixel,
+            scale,
+            origin,
+            palette: palette.unwrap_or_default(),
+            keyboards,
+            ctrl: false,
+            previous: Vec::new(),
+        };
+        framebuffer.clear()?;
+        Ok(framebuffer)
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        self.file
+            .write_all_at(&vec![0; self.stride * self.height], 0)
+    }
+
+    /// One pixel in the framebuffer's format: little-endian XRGB8888 or
+    /// RGB565.
+    fn pixel(&self, (r, g, b): (u8, u8, u8)) -> Vec<u8> {
+        if self.bytes_per_pixel == 4 {
+            vec![b, g, r, 0xFF]
+        } else {
+            let rgb565 = (u16::from(r) >> 3) << 11 | (u16::from(g) >> 2) << 5 | u16::from(b) >> 3;
+            rgb565.to_le_bytes().to_vec()
+        }
+    }
+
+    fn draw(&self, display: &[u8]) -> io::Result<()> {
+        let colors = self.palette.colors.map(|color| self.pixel(color));
+        let (x, y) = self.origin;
+        // High resolution draws at half the scale, which has to stay whole.
+        let display = if self.scale.is_multiple_of(2) {
+            Cow::Borrowed(display)
+        } else {
+            fit_display(display, WIDTH)
+        };
+        let (columns, _) = display_size(&display);
+        let scale = self.scale * WIDTH / columns;
+        for (row, pixels) in display.chunks(columns).enumerate() {
+            let line: Vec<u8> = pixels
+                .iter()
+                .flat_map(|&pixel| colors[usize::from(pixel & 3)].repeat(scale))
+                .collect();
+            for line_y in y + row * scale..y + (row + 1) * scale {
+                let offset = line_y * self.stride + x * self.bytes_per_pixel;
+                self.file.write_all_at(&line, offset as u64)?;
+            }
+        }
+        Ok(())
+    }
+}
+impl InputSource for Framebuffer {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        let mut inputs = Vec::new();
+        for keyboard in &mut self.keyboards {
+            // Nothing to read shows up as a WouldBlock error.
+            let Ok(events) = keyboard.fetch_events() else {
+                continue;
+            };
+            for event in events {
+                let EventSummary::Key(_, code, value) = event.destructure() else {
+                    continue;
+                };
+                if code == KeyCode::KEY_LEFTCTRL || code == KeyCode::KEY_RIGHTCTRL {
+                    self.ctrl = value != 0;
+                    continue;
+                }
+                // 2 is autorepeat, which the emulator does itself.
+                if value == 2 {
+                    continue;
+                }
+                let Some(name) = key_name(code) else {
+                    continue;
+                };
+                let key = if self.ctrl {
+                    format!("Ctrl+{}", name)
+                } else {
+                    name
+                };
+                inputs.push(KeyInput {
+                    key,
+                    pressed: value == 1,
+                });
+            }
+        }
+        inputs
+    }
+}
+impl Frontend for Framebuffer {
+    fn present(&mut self, display: &[u8], _status: &str) {
+        if display == self.previous {
+            return;
+        }
+        if self.draw(display).is_ok() {
+            self.previous = display.to_vec();
+        }
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.previous.clear();
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let _ = self.clear();
+    }
+}
+
+fn sysfs(name: &str) -> io::Result<String> {
+    Ok(fs::read_to_string(format!("{}/{}", SYSFS, name))?
+        .trim()
+        .to_string())
+}
+
+/// Key names as the terminal frontend spells them.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::KEY_ESC => "Escape",
+        KeyCode::KEY_SPACE => "Space",
+        KeyCode::KEY_TAB => "Tab",
+        KeyCode::KEY_BACKSPACE => "Backspace",
+        KeyCode::KEY_ENTER => "Enter",
+        KeyCode::KEY_UP => "Up",
+        KeyCode::KEY_DOWN => "Down",
+        KeyCode::KEY_LEFT => "Left",
+        KeyCode::KEY_RIGHT => "Right",
+        _ => {
+            // Debug names look like `KEY_A`, `KEY_1` and `KEY_F5`.
+            let debug = format!("{:?}", code);
+            let name = debug.strip_prefix("KEY_")?;
+            let function_key = name.starts_with('F') && name[1..].parse::<u8>().is_ok();
+            return (name.len() == 1 || function_key).then(|| name.to_string());
+        }
+    };
+    Some(name.to_string())
+}
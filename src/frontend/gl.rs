@@ -0,0 +1,215 @@
+use super::window::{letterbox, Renderer};
+use super::Palette;
+use crate::architecture::{fit_display, HIRES_HEIGHT, HIRES_WIDTH};
+use glow::HasContext;
+use glutin::config::ConfigTemplateBuilder;
+use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version};
+use glutin::display::GetGlDisplay;
+use glutin::prelude::*;
+use glutin::surface::{Surface, SwapInterval, WindowSurface};
+use glutin_winit::{DisplayBuilder, GlWindow};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use winit::event_loop::ActiveEventLoop;
+use winit::raw_window_handle::HasWindowHandle;
+use winit::window::{Window, WindowAttributes};
+
+/// Draws a full-screen triangle from the vertex index alone.
+const VERTEX_SHADER: &str = r#"
+out vec2 uv;
+
+void main() {
+    vec2 xy = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    gl_Position = vec4(xy * 2.0 - 1.0, 0.0, 1.0);
+    uv = vec2(xy.x, 1.0 - xy.y);
+}
+"#;
+
+/// Looks each fragment up in the 128x64 display texture, letterboxed to
+/// keep the 2:1 aspect ratio.
+const FRAGMENT_SHADER: &str = r#"
+in vec2 uv;
+out vec4 color;
+
+uniform sampler2D screen;
+// By pixel value: unlit, the first plane, the second and both.
+uniform vec4 colors[4];
+// Fraction of the window the display covers on each axis.
+uniform vec2 scale;
+
+void main() {
+    vec2 at = (uv - 0.5) / scale + 0.5;
+    if (any(lessThan(at, vec2(0.0))) || any(greaterThanEqual(at, vec2(1.0)))) {
+        color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    float pixel = texelFetch(screen, ivec2(at * vec2(textureSize(screen, 0))), 0).r;
+    color = colors[int(pixel * 255.0 + 0.5)];
+}
+"#;
+
+/// Draws the display with OpenGL 3.3, or OpenGL ES 3.0 where desktop GL
+/// isn't available, for drivers that wgpu doesn't handle well. Like the
+/// wgpu renderer, it uploads a 128x64 texture and scales it in a shader.
+pub struct Gl {
+    gl: glow::Context,
+    context: PossiblyCurrentContext,
+    surface: Surface<WindowSurface>,
+    program: glow::Program,
+    texture: glow::Texture,
+    size: (u32, u32),
+}
+
+impl Renderer for Gl {
+    fn create(
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Result<(Arc<Window>, Self), String> {
+        let (window, config) = DisplayBuilder::new()
+            .with_window_attributes(Some(attributes))
+            .build(event_loop, ConfigTemplateBuilder::new(), |mut configs| {
+                configs.next().unwrap()
+            })
+            .map_err(|e| e.to_string())?;
+        let window = window.ok_or("the window was not created")?;
+        let handle = window.window_handle().map_err(|e| e.to_string())?.as_raw();
+        let display = config.display();
+
+        let desktop = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+            .build(Some(handle));
+        let embedded = ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
+            .build(Some(handle));
+        // SAFETY: the window behind `handle` outlives the context.
+        let (context, header) = match unsafe { display.create_context(&config, &desktop) } {
+            Ok(context) => (context, "#version 330 core\n"),
+            Err(_) => unsafe { display.create_context(&config, &embedded) }
+                .map(|context| (context, "#version 300 es\nprecision mediump float;\n"))
+                .map_err(|e| e.to_string())?,
+        };
+        let surface_attributes = window
+            .build_surface_attributes(Default::default())
+            .map_err(|e| e.to_string())?;
+        // SAFETY: as above, the window outlives the surface.
+        let surface = unsafe { display.create_window_surface(&config, &surface_attributes) }
+            .map_err(|e| e.to_string())?;
+        let context = context.make_current(&surface).map_err(|e| e.to_string())?;
+        // The emulator paces its own frames, and turbo mode runs faster
+        // than the screen refreshes.
+        let _ = surface.set_swap_interval(&context, SwapInterval::DontWait);
+
+        // SAFETY: the context is current on this thread, and every call
+        // below uses objects created from it.
+        let renderer = unsafe {
+            let gl =
+                glow::Context::from_loader_function_cstr(|name| display.get_proc_address(name));
+            let program = link(&gl, header)?;
+            let texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            for parameter in [glow::TEXTURE_MIN_FILTER, glow::TEXTURE_MAG_FILTER] {
+                gl.tex_parameter_i32(glow::TEXTURE_2D, parameter, glow::NEAREST as i32);
+            }
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::R8 as i32,
+                HIRES_WIDTH as i32,
+                HIRES_HEIGHT as i32,
+                0,
+                glow::RED,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(None),
+            );
+            // Core profiles can't draw without a vertex array bound, even
+            // though the shader doesn't read any attributes.
+            let vertex_array = gl.create_vertex_array()?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.use_program(Some(program));
+            let size = window.inner_size();
+            Gl {
+                gl,
+                context,
+                surface,
+                program,
+                texture,
+                size: (size.width, size.height),
+            }
+        };
+        Ok((Arc::new(window), renderer))
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let (Some(w), Some(h)) = (NonZeroU32::new(width), NonZeroU32::new(height)) else {
+            return;
+        };
+        self.surface.resize(&self.context, w, h);
+        self.size = (width, height);
+    }
+
+    fn render(&mut self, display: &[u8], palette: Palette) {
+        let pixels: Vec<u8> = fit_display(display, HIRES_WIDTH)
+            .iter()
+            .map(|&pixel| pixel & 3)
+            .collect();
+        let (width, height) = self.size;
+        let color = |(r, g, b): (u8, u8, u8)| [r, g, b, 0xFF].map(|c| f32::from(c) / 255.0);
+        let gl = &self.gl;
+        // SAFETY: the context made current in `create` is still current.
+        unsafe {
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.bind_texture(glow::TEXTURE_2D, Some(self.texture));
+            gl.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                0,
+                0,
+                HIRES_WIDTH as i32,
+                HIRES_HEIGHT as i32,
+                glow::RED,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(Some(&pixels)),
+            );
+            let uniform = |name| gl.get_uniform_location(self.program, name);
+            let colors: Vec<f32> = palette.colors.iter().flat_map(|&c| color(c)).collect();
+            gl.uniform_4_f32_slice(uniform("colors").as_ref(), &colors);
+            let [x, y] = letterbox(width, height);
+            gl.uniform_2_f32(uniform("scale").as_ref(), x, y);
+            gl.uniform_1_i32(uniform("screen").as_ref(), 0);
+
+            gl.viewport(0, 0, width as i32, height as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        }
+        let _ = self.surface.swap_buffers(&self.context);
+    }
+}
+
+/// Compiles both shaders with `header` (the GLSL version line) in front.
+unsafe fn link(gl: &glow::Context, header: &str) -> Result<glow::Program, String> {
+    let program = gl.create_program()?;
+    let mut shaders = Vec::new();
+    for (kind, source) in [
+        (glow::VERTEX_SHADER, VERTEX_SHADER),
+        (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+    ] {
+        let shader = gl.create_shader(kind)?;
+        gl.shader_source(shader, &format!("{}{}", header, source));
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            return Err(gl.get_shader_info_log(shader));
+        }
+        gl.attach_shader(program, shader);
+        shaders.push(shader);
+    }
+    gl.link_program(program);
+    if !gl.get_program_link_status(program) {
+        return Err(gl.get_program_info_log(program));
+    }
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+    Ok(program)
+}
@@ -0,0 +1,92 @@
+use super::{Frontend, InputSource, KeyInput, Palette};
+use crate::architecture::{fit_display, HEIGHT, WIDTH};
+use std::io;
+use std::os::raw::c_int;
+
+/// Opaque handles from hzeller's rpi-rgb-led-matrix library.
+#[repr(C)]
+struct RgbLedMatrix {
+    _private: [u8; 0],
+}
+#[repr(C)]
+struct LedCanvas {
+    _private: [u8; 0],
+}
+
+// The plain C API from `led-matrix-c.h`.
+#[link(name = "rgbmatrix")]
+extern "C" {
+    fn led_matrix_create(rows: c_int, chained: c_int, parallel: c_int) -> *mut RgbLedMatrix;
+    fn led_matrix_delete(matrix: *mut RgbLedMatrix);
+    fn led_matrix_create_offscreen_canvas(matrix: *mut RgbLedMatrix) -> *mut LedCanvas;
+    fn led_matrix_swap_on_vsync(
+        matrix: *mut RgbLedMatrix,
+        canvas: *mut LedCanvas,
+    ) -> *mut LedCanvas;
+    fn led_canvas_set_pixel(canvas: *mut LedCanvas, x: c_int, y: c_int, r: u8, g: u8, b: u8);
+}
+
+/// Shows the display 1:1 on a 64x32 HUB75 LED panel driven from a Raspberry
+/// Pi's GPIO pins. The library treats a 64-column panel as two chained
+/// 32x32 ones. High resolution is halved to fit. Panels have no keys.
+pub struct LedMatrix {
+    matrix: *mut RgbLedMatrix,
+    /// Drawn off screen, then swapped in on the panel's next refresh.
+    canvas: *mut LedCanvas,
+    palette: Palette,
+    previous: Vec<u8>,
+}
+
+impl LedMatrix {
+    /// Needs root, or the GPIO memory the library maps won't be writable.
+    pub fn new(palette: Option<Palette>) -> io::Result<Self> {
+        // SAFETY: plain constructor calls; null means the library failed.
+        let matrix = unsafe { led_matrix_create(HEIGHT as c_int, (WIDTH / HEIGHT) as c_int, 1) };
+        if matrix.is_null() {
+            return Err(io::Error::other("could not set up the LED matrix"));
+        }
+        let canvas = unsafe { led_matrix_create_offscreen_canvas(matrix) };
+        Ok(LedMatrix {
+            matrix,
+            canvas,
+            palette: palette.unwrap_or_default(),
+            previous: Vec::new(),
+        })
+    }
+}
+impl InputSource for LedMatrix {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        Vec::new()
+    }
+}
+impl Frontend for LedMatrix {
+    fn present(&mut self, display: &[u8], _status: &str) {
+        let display = fit_display(display, WIDTH);
+        if *display == *self.previous {
+            return;
+        }
+        for (i, &pixel) in display.iter().enumerate() {
+            let (r, g, b) = self.palette.color(pixel);
+            let (x, y) = ((i % WIDTH) as c_int, (i / WIDTH) as c_int);
+            // SAFETY: the canvas belongs to the matrix, which lives as long
+            // as `self`.
+            unsafe { led_canvas_set_pixel(self.canvas, x, y, r, g, b) };
+        }
+        // SAFETY: as above; the swap hands back the canvas to draw next.
+        self.canvas = unsafe { led_matrix_swap_on_vsync(self.matrix, self.canvas) };
+        self.previous = display.to_vec();
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.previous.clear();
+    }
+}
+
+impl Drop for LedMatrix {
+    fn drop(&mut self) {
+        // SAFETY: the matrix isn't used again; deleting it also frees the
+        // canvases and blanks the panel.
+        unsafe { led_matrix_delete(self.matrix) };
+    }
+}
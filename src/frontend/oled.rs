@@ -0,0 +1,127 @@
+#[cfg(target_os = "linux")]
+pub mod i2cdev;
+
+use super::{Frontend, InputSource, KeyInput, Palette};
+use crate::architecture::{fit_display, HIRES_HEIGHT, HIRES_WIDTH};
+use embedded_hal::i2c::I2c;
+
+/// The usual I2C address of these modules; some can be strapped to 0x3D.
+const ADDRESS: u8 = 0x3C;
+const COLUMNS: usize = HIRES_WIDTH;
+/// Rows of 8 vertical pixels, one byte per column.
+const PAGES: usize = HIRES_HEIGHT / 8;
+
+/// The two controllers found on cheap 128x64 modules. They share a command
+/// set, but the SH1106 has 132 columns of RAM with the panel in the middle.
+#[derive(Clone, Copy)]
+pub enum Controller {
+    Ssd1306,
+    Sh1106,
+}
+impl Controller {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ssd1306" => Some(Controller::Ssd1306),
+            "sh1106" => Some(Controller::Sh1106),
+            _ => None,
+        }
+    }
+
+    fn column_offset(self) -> u8 {
+        match self {
+            Controller::Ssd1306 => 0,
+            Controller::Sh1106 => 2,
+        }
+    }
+}
+
+/// A 128x64 monochrome OLED on any embedded-hal I2C bus, showing the display
+/// at 2x, or 1:1 in high resolution. Only whole pages that changed are sent,
+/// since a full frame is about a kilobyte on a 400 kHz bus. The command line
+/// drives it through Linux's `i2c-dev`, but nothing here needs Linux: other
+/// boards pass their HAL's bus to `new`.
+pub struct Oled<I> {
+    i2c: I,
+    controller: Controller,
+    pages: [[u8; COLUMNS]; PAGES],
+    /// Which pixel values light the panel's one color: those the palette
+    /// gives a color other than the background's.
+    lit: [bool; 4],
+    /// False until the first frame, so that one is sent in full.
+    drawn: bool,
+}
+
+impl<I: I2c> Oled<I> {
+    pub fn new(i2c: I, controller: Controller) -> Result<Self, I::Error> {
+        let mut oled = Oled {
+            i2c,
+            controller,
+            pages: [[0; COLUMNS]; PAGES],
+            lit: lit(Palette::default()),
+            drawn: false,
+        };
+        let charge_pump: &[u8] = match controller {
+            Controller::Ssd1306 => &[0x8D, 0x14],
+            Controller::Sh1106 => &[0xAD, 0x8B],
+        };
+        oled.commands(&[0xAE])?; // Off while setting up.
+        oled.commands(&[0xD5, 0x80, 0xA8, 0x3F, 0xD3, 0x00, 0x40])?;
+        oled.commands(charge_pump)?;
+        // Flip both axes so row 0 is at the top with the pins up, then
+        // contrast, precharge and VCOMH as the datasheets recommend.
+        oled.commands(&[0xA1, 0xC8, 0xDA, 0x12, 0x81, 0xCF, 0xD9, 0xF1, 0xDB, 0x40])?;
+        oled.commands(&[0xA4, 0xA6, 0xAF])?;
+        Ok(oled)
+    }
+
+    fn commands(&mut self, bytes: &[u8]) -> Result<(), I::Error> {
+        self.i2c.write(ADDRESS, &[&[0x00], bytes].concat())
+    }
+
+    fn send_page(&mut self, page: usize) -> Result<(), I::Error> {
+        let column = self.controller.column_offset();
+        self.commands(&[0xB0 | page as u8, column & 0x0F, 0x10 | column >> 4])?;
+        let data = [&[0x40], &self.pages[page][..]].concat();
+        self.i2c.write(ADDRESS, &data)
+    }
+}
+impl<I: I2c> InputSource for Oled<I> {
+    /// Input comes from a separate keypad driver, not the panel.
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        Vec::new()
+    }
+}
+impl<I: I2c> Frontend for Oled<I> {
+    fn present(&mut self, display: &[u8], _status: &str) {
+        let display = fit_display(display, HIRES_WIDTH);
+        for page in 0..PAGES {
+            let mut bytes = [0u8; COLUMNS];
+            for (column, byte) in bytes.iter_mut().enumerate() {
+                for bit in 0..8 {
+                    if self.lit[usize::from(display[(page * 8 + bit) * COLUMNS + column] & 3)] {
+                        *byte |= 1 << bit;
+                    }
+                }
+            }
+            if self.drawn && bytes == self.pages[page] {
+                continue;
+            }
+            self.pages[page] = bytes;
+            if self.send_page(page).is_err() {
+                // Try the whole frame again next time.
+                self.drawn = false;
+                return;
+            }
+        }
+        self.drawn = true;
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.lit = lit(palette);
+        self.drawn = false;
+    }
+}
+
+fn lit(palette: Palette) -> [bool; 4] {
+    palette.colors.map(|color| color != palette.colors[0])
+}
@@ -0,0 +1,75 @@
+use embedded_hal::i2c::{self, ErrorKind, ErrorType, I2c, Operation};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+
+/// The I2C bus on the Raspberry Pi's header pins.
+pub const BUS: &str = "/dev/i2c-1";
+
+/// `ioctl` request that sets the device address for later reads and writes.
+const I2C_SLAVE: libc::c_ulong = 0x0703;
+
+/// An I2C bus from the Linux `i2c-dev` driver, like `/dev/i2c-1` on the
+/// Raspberry Pi header. Each operation is its own transfer, without repeated
+/// starts between them, which is all the OLED needs.
+pub struct I2cDev {
+    file: File,
+    address: Option<u8>,
+}
+impl I2cDev {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(I2cDev {
+            file,
+            address: None,
+        })
+    }
+
+    fn set_address(&mut self, address: u8) -> io::Result<()> {
+        if self.address == Some(address) {
+            return Ok(());
+        }
+        // SAFETY: I2C_SLAVE takes the address by value and touches no memory.
+        let result = unsafe {
+            libc::ioctl(
+                self.file.as_raw_fd(),
+                I2C_SLAVE as _,
+                libc::c_ulong::from(address),
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.address = Some(address);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Error(pub io::Error);
+impl i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl ErrorType for I2cDev {
+    type Error = Error;
+}
+impl I2c for I2cDev {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.set_address(address).map_err(Error)?;
+        for operation in operations {
+            match operation {
+                Operation::Read(buffer) => self.file.read_exact(buffer),
+                Operation::Write(bytes) => self.file.write_all(bytes),
+            }
+            .map_err(Error)?;
+        }
+        Ok(())
+    }
+}
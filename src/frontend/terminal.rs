@@ -0,0 +1,415 @@
+use super::{Frontend, InputSource, KeyInput, Palette};
+use crate::menu::MenuView;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, queue, style, terminal};
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+mod graphics;
+
+/// Terminals without the kitty keyboard protocol never report releases, so
+/// a key counts as released once it stops auto-repeating for this long.
+const RELEASE_DELAY: Duration = Duration::from_millis(200);
+
+/// Item rows the menu shows below an image, which it can't be drawn over.
+const MENU_ROWS_BELOW_IMAGE: usize = 8;
+
+/// Sixel scale used when the terminal doesn't report its cell size.
+const DEFAULT_SIXEL_SCALE: usize = 8;
+
+/// How the display is drawn. The image modes need a terminal that
+/// implements the kitty graphics protocol or sixel, and cover the same
+/// 64x16 cells as the half blocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Render {
+    /// Half-block characters, two pixels per cell.
+    Blocks,
+    /// Braille characters, 2x4 pixels per cell, so the display fits in 32x8.
+    Braille,
+    Kitty,
+    Sixel,
+}
+impl Render {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blocks" => Some(Render::Blocks),
+            "braille" => Some(Render::Braille),
+            "kitty" => Some(Render::Kitty),
+            "sixel" => Some(Render::Sixel),
+            _ => None,
+        }
+    }
+
+    /// Terminal rows taken by the display.
+    fn rows(self) -> u16 {
+        let pixels_per_row = match self {
+            Render::Braille => 4,
+            _ => 2,
+        };
+        (crate::architecture::HEIGHT / pixels_per_row) as u16
+    }
+
+    /// Terminal columns taken by the display.
+    fn columns(self) -> usize {
+        match self {
+            Render::Braille => crate::architecture::WIDTH / 2,
+            _ => crate::architecture::WIDTH,
+        }
+    }
+
+    fn is_image(self) -> bool {
+        matches!(self, Render::Kitty | Render::Sixel)
+    }
+}
+
+pub struct Terminal {
+    stdout: Stdout,
+    reports_release: bool,
+    render: Render,
+    sixel_scale: usize,
+    palette: Option<Palette>,
+    held: Vec<(String, Instant)>,
+    previous_frame: Vec<u8>,
+    previous_status: String,
+    /// The menu last drawn, while it is open.
+    previous_menu: Option<MenuView>,
+}
+impl Terminal {
+    pub fn new(render: Render, palette: Option<Palette>) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        let reports_release = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if reports_release {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        // Fit the image into 64x16 cells when the terminal says how big they are.
+        let sixel_scale = match terminal::window_size() {
+            Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => {
+                let cell_width = usize::from(size.width / size.columns);
+                let cell_height = usize::from(size.height / size.rows);
+                cell_width.min(cell_height / 2).max(1)
+            }
+            _ => DEFAULT_SIXEL_SCALE,
+        };
+        Ok(Terminal {
+            stdout,
+            reports_release,
+            render,
+            sixel_scale,
+            palette,
+            held: Vec::new(),
+            previous_frame: Vec::new(),
+            previous_status: String::new(),
+            previous_menu: None,
+        })
+    }
+
+    fn read_events(&mut self, inputs: &mut Vec<KeyInput>) -> io::Result<()> {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key_event) = event::read()? else {
+                continue;
+            };
+            let Some(key) = key_name(&key_event) else {
+                continue;
+            };
+            let pressed = key_event.kind != KeyEventKind::Release;
+            if self.reports_release {
+                if key_event.kind != KeyEventKind::Repeat {
+                    inputs.push(KeyInput { key, pressed });
+                }
+                continue;
+            }
+            match self.held.iter_mut().find(|(held, _)| *held == key) {
+                Some((_, last_seen)) => *last_seen = Instant::now(),
+                None => {
+                    self.held.push((key.clone(), Instant::now()));
+                    inputs.push(KeyInput { key, pressed });
+                }
+            }
+        }
+        if !self.reports_release {
+            self.held.retain(|(key, last_seen)| {
+                let released = last_seen.elapsed() >= RELEASE_DELAY;
+                if released {
+                    inputs.push(KeyInput {
+                        key: key.clone(),
+                        pressed: false,
+                    });
+                }
+                !released
+            });
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, display: &[u8], status: &str) -> io::Result<()> {
+        let width = crate::architecture::WIDTH;
+        let rows = self.render.rows();
+        let palette = self.palette.unwrap_or_default();
+        match self.render {
+            Render::Blocks => self.draw_blocks(display)?,
+            Render::Braille => self.draw_braille(display)?,
+            Render::Kitty => {
+                let image = graphics::kitty(display, palette, width as u16, rows)?;
+                queue!(self.stdout, cursor::MoveTo(0, 0), style::Print(image))?;
+            }
+            Render::Sixel => {
+                let image = graphics::sixel(display, palette, self.sixel_scale);
+                queue!(self.stdout, cursor::MoveTo(0, 0), style::Print(image))?;
+            }
+        }
+        queue!(
+            self.stdout,
+            style::ResetColor,
+            cursor::MoveTo(0, rows),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print(status)
+        )?;
+        self.stdout.flush()
+    }
+
+    /// Draws the menu in a box over the display, or under the status line
+    /// when the display is an image.
+    fn draw_menu(&mut self, menu: &MenuView) -> io::Result<()> {
+        let rows = usize::from(self.render.rows());
+        let columns = self.render.columns();
+        let room = if self.render.is_image() {
+            MENU_ROWS_BELOW_IMAGE
+        } else {
+            rows - 2
+        };
+        // Scroll long lists so the selected item stays in the box.
+        let first = (menu.selected + 1).saturating_sub(room);
+        let shown = &menu.items[first..menu.items.len().min(first + room)];
+        let widest = shown.iter().map(|item| item.chars().count()).max();
+        let inner = (widest.unwrap_or(0) + 3)
+            .max(menu.title.chars().count() + 4)
+            .min(columns - 2);
+        let left = ((columns - inner - 2) / 2) as u16;
+        let top = if self.render.is_image() {
+            rows + 1
+        } else {
+            (rows - shown.len() - 2) / 2
+        };
+        let mut lines = vec![format!(
+            "┌─ {} {}┐",
+            menu.title,
+            "─".repeat(inner.saturating_sub(menu.title.chars().count() + 3))
+        )];
+        for (n, item) in shown.iter().enumerate() {
+            let marker = if first + n == menu.selected { '>' } else { ' ' };
+            let item: String = item.chars().take(inner - 3).collect();
+            lines.push(format!("│{} {:<width$} │", marker, item, width = inner - 3));
+        }
+        lines.push(format!("└{}┘", "─".repeat(inner)));
+        queue!(self.stdout, style::ResetColor)?;
+        if self.render.is_image() {
+            queue!(
+                self.stdout,
+                cursor::MoveTo(0, top as u16),
+                terminal::Clear(terminal::ClearType::FromCursorDown)
+            )?;
+        }
+        for (n, line) in lines.iter().enumerate() {
+            queue!(
+                self.stdout,
+                cursor::MoveTo(left, (top + n) as u16),
+                style::Print(line)
+            )?;
+        }
+        self.stdout.flush()
+    }
+
+    /// The colors for character modes: the palette, or none for the
+    /// terminal's own. Those have only one foreground, so frames using
+    /// XO-CHIP's second plane fall back on the default palette's colors.
+    fn colors(&self, display: &[u8]) -> Option<Palette> {
+        self.palette.or_else(|| {
+            display
+                .iter()
+                .any(|&pixel| pixel > 1)
+                .then(Palette::default)
+        })
+    }
+
+    /// Prints a row of cells, each a character and the pixel values whose
+    /// colors it takes for its foreground and background, switching colors
+    /// only where they change.
+    fn print_row(
+        &mut self,
+        row: usize,
+        cells: &[(char, u8, u8)],
+        palette: Option<Palette>,
+    ) -> io::Result<()> {
+        queue!(self.stdout, cursor::MoveTo(0, row as u16))?;
+        let Some(palette) = palette else {
+            let line: String = cells.iter().map(|&(c, _, _)| c).collect();
+            return queue!(self.stdout, style::Print(line));
+        };
+        let color = |pixel| {
+            let (r, g, b) = palette.color(pixel);
+            style::Color::Rgb { r, g, b }
+        };
+        for run in cells.chunk_by(|a, b| (a.1, a.2) == (b.1, b.2)) {
+            let (_, foreground, background) = run[0];
+            let line: String = run.iter().map(|&(c, _, _)| c).collect();
+            queue!(
+                self.stdout,
+                style::SetColors(style::Colors::new(color(foreground), color(background))),
+                style::Print(line)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw_blocks(&mut self, display: &[u8]) -> io::Result<()> {
+        let width = crate::architecture::WIDTH;
+        let display = crate::architecture::fit_display(display, width);
+        let palette = self.colors(&display);
+        for (row, pixels) in display.chunks(width * 2).enumerate() {
+            let (top, bottom) = pixels.split_at(width);
+            let cells: Vec<(char, u8, u8)> = top
+                .iter()
+                .zip(bottom)
+                .map(|(&top, &bottom)| match (palette, top, bottom) {
+                    // With colors to set, the top pixel is the foreground
+                    // and the bottom one the background.
+                    (Some(_), _, _) => ('▀', top, bottom),
+                    (None, 0, 0) => (' ', 0, 0),
+                    (None, _, 0) => ('▀', 0, 0),
+                    (None, 0, _) => ('▄', 0, 0),
+                    (None, _, _) => ('█', 0, 0),
+                })
+                .collect();
+            self.print_row(row, &cells, palette)?;
+        }
+        Ok(())
+    }
+
+    fn draw_braille(&mut self, display: &[u8]) -> io::Result<()> {
+        // Bit of each dot in a braille cell, by row and column.
+        const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+        let width = crate::architecture::WIDTH;
+        let display = crate::architecture::fit_display(display, width);
+        let palette = self.colors(&display);
+        for (row, pixels) in display.chunks(width * 4).enumerate() {
+            let cells: Vec<(char, u8, u8)> = (0..width / 2)
+                .map(|cell| {
+                    let mut bits = 0;
+                    // A cell has one color, so it takes the one most of
+                    // its lit dots have.
+                    let mut counts = [0; 4];
+                    for (y, dots) in DOTS.iter().enumerate() {
+                        for (x, dot) in dots.iter().enumerate() {
+                            let pixel = pixels[y * width + cell * 2 + x] & 3;
+                            if pixel != 0 {
+                                bits |= dot;
+                                counts[usize::from(pixel)] += 1;
+                            }
+                        }
+                    }
+                    let color = (1..4).max_by_key(|&pixel| counts[usize::from(pixel)]);
+                    let glyph = char::from_u32(0x2800 + bits).unwrap_or(' ');
+                    (glyph, color.unwrap_or(1), 0)
+                })
+                .collect();
+            self.print_row(row, &cells, palette)?;
+        }
+        Ok(())
+    }
+}
+impl InputSource for Terminal {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        let mut inputs = Vec::new();
+        // A terminal that stops answering leaves us without input, not broken.
+        let _ = self.read_events(&mut inputs);
+        inputs
+    }
+}
+impl Frontend for Terminal {
+    fn present(&mut self, display: &[u8], status: &str) {
+        if self.previous_menu.take().is_some() {
+            // Wipe the menu, along with the rows it used under an image.
+            let _ = queue!(self.stdout, terminal::Clear(terminal::ClearType::All));
+            self.previous_frame.clear();
+        }
+        if display == self.previous_frame && status == self.previous_status {
+            return;
+        }
+        if self.draw(display, status).is_ok() {
+            self.previous_frame = display.to_vec();
+            self.previous_status = status.to_string();
+        }
+    }
+
+    fn present_menu(&mut self, display: &[u8], status: &str, menu: &MenuView) {
+        if self.previous_menu.as_ref() == Some(menu)
+            && display == self.previous_frame
+            && status == self.previous_status
+        {
+            return;
+        }
+        if self
+            .draw(display, status)
+            .and_then(|()| self.draw_menu(menu))
+            .is_ok()
+        {
+            self.previous_frame = display.to_vec();
+            self.previous_status = status.to_string();
+            self.previous_menu = Some(menu.clone());
+        }
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = Some(palette);
+        self.previous_frame.clear();
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.reports_release {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            self.stdout,
+            style::ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn key_name(event: &KeyEvent) -> Option<String> {
+    let name = match event.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_uppercase().to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Esc => "Escape".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        _ => return None,
+    };
+    if event.modifiers.contains(KeyModifiers::CONTROL) {
+        Some(format!("Ctrl+{}", name))
+    } else {
+        Some(name)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::architecture::{display_size, HEIGHT, WIDTH};
+use crate::frontend::Palette;
+use base64::Engine;
+use std::io;
+
+/// Kitty limits each escape sequence to 4096 bytes of base64 payload.
+const KITTY_CHUNK: usize = 4096;
+
+/// The display as a kitty graphics protocol PNG, stretched over
+/// `columns` x `rows` cells and replacing the previous frame in place.
+pub fn kitty(display: &[u8], palette: Palette, columns: u16, rows: u16) -> io::Result<String> {
+    let mut png = Vec::new();
+    let (width, height) = display_size(display);
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = display
+        .iter()
+        .flat_map(|&pixel| {
+            let (r, g, b) = palette.color(pixel);
+            [r, g, b]
+        })
+        .collect();
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+
+    let data = base64::engine::general_purpose::STANDARD.encode(&png);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(n + 1 < chunks.len());
+        // Only the first chunk carries the keys. Same image and placement
+        // ids every frame, so each frame replaces the last; q=2 keeps the
+        // terminal from answering on stdin.
+        let keys = if n == 0 {
+            format!("a=T,f=100,i=1,p=1,q=2,C=1,c={},r={},", columns, rows)
+        } else {
+            String::new()
+        };
+        out += &format!("\x1b_G{}m={};", keys, more);
+        out += std::str::from_utf8(chunk).unwrap();
+        out += "\x1b\\";
+    }
+    Ok(out)
+}
+
+/// The display as a sixel image, each CHIP-8 pixel drawn as a `scale` x
+/// `scale` square, or half that in high resolution so the image keeps its
+/// size.
+pub fn sixel(display: &[u8], palette: Palette, scale: usize) -> String {
+    let (columns, _) = display_size(display);
+    let (width, height) = (WIDTH * scale, HEIGHT * scale);
+    let scale = (scale * WIDTH / columns).max(1);
+    let pixel = |x: usize, y: usize| {
+        if y < height && x / scale < columns {
+            display[(y / scale) * columns + x / scale] & 3
+        } else {
+            0
+        }
+    };
+    let percent = |(r, g, b): (u8, u8, u8)| {
+        let p = |c: u8| u32::from(c) * 100 / 255;
+        format!("{};{};{}", p(r), p(g), p(b))
+    };
+    // One color register per pixel value.
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for (register, &color) in palette.colors.iter().enumerate() {
+        out += &format!("#{};2;{}", register, percent(color));
+    }
+    for band in (0..height).step_by(6) {
+        for color in 0..4 {
+            out += &format!("#{}", color);
+            let columns: Vec<u8> = (0..width)
+                .map(|x| {
+                    let bits = (0..6)
+                        .filter(|&row| band + row < height && pixel(x, band + row) == color)
+                        .fold(0, |bits, row| bits | 1 << row);
+                    63 + bits
+                })
+                .collect();
+            push_run_length(&mut out, &columns);
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out += "\x1b\\";
+    out
+}
+
+/// Sixel data with runs of four or more repeats written as `!count`.
+fn push_run_length(out: &mut String, columns: &[u8]) {
+    let mut rest = columns;
+    while let Some(&first) = rest.first() {
+        let run = rest.iter().take_while(|&&c| c == first).count();
+        if run >= 4 {
+            out.push_str(&format!("!{}{}", run, first as char));
+        } else {
+            out.extend(std::iter::repeat_n(first as char, run));
+        }
+        rest = &rest[run..];
+    }
+}
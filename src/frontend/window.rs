@@ -0,0 +1,216 @@
+use super::{Frontend, InputSource, KeyInput, Palette};
+use crate::architecture::{HEIGHT, WIDTH};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
+use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
+use winit::window::{Fullscreen, Window, WindowAttributes, WindowId};
+
+/// Window size at startup, in logical pixels per CHIP-8 pixel, unless
+/// `--scale` picks another.
+pub const DEFAULT_SCALE: u32 = 12;
+
+/// A graphics API that draws the display into a window.
+pub trait Renderer: Sized {
+    /// Opens the window along with the renderer, since some APIs have to
+    /// pick the window's pixel format.
+    fn create(
+        event_loop: &ActiveEventLoop,
+        attributes: WindowAttributes,
+    ) -> Result<(Arc<Window>, Self), String>;
+    /// Called with the new size in physical pixels.
+    fn resize(&mut self, width: u32, height: u32);
+    fn render(&mut self, display: &[u8], palette: Palette);
+}
+
+/// A desktop window drawn by `R`, with keys read from winit. The event loop
+/// is pumped from `poll_input`, so the emulator keeps its own main loop.
+pub struct Windowed<R> {
+    event_loop: EventLoop<()>,
+    app: App<R>,
+    palette: Palette,
+    previous_status: Option<String>,
+}
+
+struct App<R> {
+    window: Option<Arc<Window>>,
+    renderer: Option<R>,
+    error: Option<String>,
+    inputs: Vec<KeyInput>,
+    modifiers: ModifiersState,
+    scale: u32,
+}
+
+impl<R: Renderer> Windowed<R> {
+    /// Opens a window `scale` logical pixels per CHIP-8 pixel big.
+    pub fn new(palette: Option<Palette>, scale: u32) -> io::Result<Self> {
+        let mut event_loop = EventLoop::new().map_err(io::Error::other)?;
+        let mut app = App {
+            window: None,
+            renderer: None,
+            error: None,
+            inputs: Vec::new(),
+            modifiers: ModifiersState::empty(),
+            scale,
+        };
+        // The window can only be created once the event loop has started.
+        event_loop.pump_app_events(Some(Duration::ZERO), &mut app);
+        if let Some(error) = app.error.take() {
+            return Err(io::Error::other(error));
+        }
+        if app.renderer.is_none() {
+            return Err(io::Error::other("the window was not created"));
+        }
+        Ok(Windowed {
+            event_loop,
+            app,
+            palette: palette.unwrap_or_default(),
+            previous_status: None,
+        })
+    }
+}
+impl<R: Renderer> InputSource for Windowed<R> {
+    fn poll_input(&mut self) -> Vec<KeyInput> {
+        let status = self
+            .event_loop
+            .pump_app_events(Some(Duration::ZERO), &mut self.app);
+        if let PumpStatus::Exit(_) = status {
+            self.app.close();
+        }
+        std::mem::take(&mut self.app.inputs)
+    }
+}
+impl<R: Renderer> Frontend for Windowed<R> {
+    fn present(&mut self, display: &[u8], status: &str) {
+        let (Some(window), Some(renderer)) = (&self.app.window, self.app.renderer.as_mut()) else {
+            return;
+        };
+        if self.previous_status.as_deref() != Some(status) {
+            window.set_title(&title(status));
+            self.previous_status = Some(status.to_string());
+        }
+        window.pre_present_notify();
+        renderer.render(display, self.palette);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    fn toggle_fullscreen(&mut self) -> bool {
+        let Some(window) = &self.app.window else {
+            return false;
+        };
+        let fullscreen = window.fullscreen().is_none();
+        window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+        true
+    }
+}
+
+fn title(status: &str) -> String {
+    if status.is_empty() {
+        "chip-n-claw".to_string()
+    } else {
+        format!("chip-n-claw - {}", status)
+    }
+}
+
+/// How much of a `width` x `height` window the display covers on each
+/// axis, keeping its 2:1 aspect ratio.
+pub fn letterbox(width: u32, height: u32) -> [f32; 2] {
+    let window_aspect = width.max(1) as f32 / height.max(1) as f32;
+    let display_aspect = WIDTH as f32 / HEIGHT as f32;
+    if window_aspect > display_aspect {
+        [display_aspect / window_aspect, 1.0]
+    } else {
+        [1.0, window_aspect / display_aspect]
+    }
+}
+
+impl<R> App<R> {
+    /// Closing the window is reported as the `Close` key, which quits
+    /// unless it has been rebound.
+    fn close(&mut self) {
+        for pressed in [true, false] {
+            let key = "Close".to_string();
+            self.inputs.push(KeyInput { key, pressed });
+        }
+    }
+}
+impl<R: Renderer> ApplicationHandler for App<R> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let size = LogicalSize::new(WIDTH as u32 * self.scale, HEIGHT as u32 * self.scale);
+        let attributes = Window::default_attributes()
+            .with_title(title(""))
+            .with_inner_size(size);
+        match R::create(event_loop, attributes) {
+            Ok((window, renderer)) => {
+                self.window = Some(window);
+                self.renderer = Some(renderer);
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => self.close(),
+            WindowEvent::Resized(size) => {
+                if let Some(renderer) = self.renderer.as_mut() {
+                    renderer.resize(size.width, size.height);
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput { event, .. } if !event.repeat => {
+                let PhysicalKey::Code(code) = event.physical_key else {
+                    return;
+                };
+                let Some(name) = key_name(code) else {
+                    return;
+                };
+                let key = if self.modifiers.control_key() {
+                    format!("Ctrl+{}", name)
+                } else {
+                    name
+                };
+                let pressed = event.state == ElementState::Pressed;
+                self.inputs.push(KeyInput { key, pressed });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Key names as the terminal frontend spells them.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Escape => "Escape",
+        KeyCode::Space => "Space",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Enter => "Enter",
+        KeyCode::ArrowUp => "Up",
+        KeyCode::ArrowDown => "Down",
+        KeyCode::ArrowLeft => "Left",
+        KeyCode::ArrowRight => "Right",
+        _ => {
+            // Debug names look like `KeyA`, `Digit1` and `F5`.
+            let debug = format!("{:?}", code);
+            let name = debug
+                .strip_prefix("Key")
+                .or_else(|| debug.strip_prefix("Digit"))
+                .unwrap_or(&debug);
+            let function_key = name.starts_with('F') && name[1..].parse::<u8>().is_ok();
+            return (name.len() == 1 || function_key).then(|| name.to_string());
+        }
+    };
+    Some(name.to_string())
+}
@@ -0,0 +1,157 @@
+use crate::disasm::LOAD_ADDRESS;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Words per row of the heatmap image.
+const IMAGE_COLUMNS: usize = 64;
+const IMAGE_SCALE: usize = 4;
+const BAR_WIDTH: u64 = 40;
+
+/// How many times each ROM word was executed, counted by word from the
+/// load address. Code below the load address isn't counted.
+#[derive(Default)]
+pub struct Heatmap {
+    counts: Vec<u64>,
+}
+impl Heatmap {
+    pub fn record(&mut self, pc: u16) {
+        let Some(offset) = usize::from(pc).checked_sub(LOAD_ADDRESS) else {
+            return;
+        };
+        let word = offset / 2;
+        if word >= self.counts.len() {
+            self.counts.resize(word + 1, 0);
+        }
+        self.counts[word] += 1;
+    }
+
+    fn count(&self, word: usize) -> u64 {
+        self.counts.get(word).copied().unwrap_or(0)
+    }
+
+    /// Writes the heatmap as a PNG if `path` ends in `.png`, and as an
+    /// annotated listing otherwise.
+    pub fn save(&self, rom: &[u8], path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        if path.ends_with(".png") {
+            self.write_png(rom, &mut out)?;
+        } else {
+            self.write_listing(rom, &mut out)?;
+        }
+        out.flush()
+    }
+
+    /// One line per ROM word with its execution count and a bar scaled to
+    /// the hottest address. There is no disassembler yet, so instructions
+    /// are shown as raw words.
+    pub fn write_listing(&self, rom: &[u8], out: &mut impl Write) -> io::Result<()> {
+        let rom = words(rom);
+        let hottest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        for index in 0..rom.len().max(self.counts.len()) {
+            let address = address(index);
+            let word = rom.get(index).copied().unwrap_or(0);
+            let count = self.count(index);
+            if count == 0 {
+                writeln!(out, "{:03X}  {:04X}", address, word)?;
+                continue;
+            }
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(hottest) as usize);
+            writeln!(out, "{:03X}  {:04X} {:>12}  {}", address, word, count, bar)?;
+        }
+        Ok(())
+    }
+
+    /// How much of the ROM ever ran, and the address ranges that never did.
+    /// Unexecuted ranges include data as well as dead code.
+    pub fn write_coverage(&self, rom: &[u8], out: &mut impl Write) -> io::Result<()> {
+        let rom = words(rom);
+        let executed = (0..rom.len()).filter(|&a| self.count(a) > 0).count();
+        writeln!(
+            out,
+            "coverage: {} of {} words executed ({:.1}%)",
+            executed,
+            rom.len(),
+            executed as f64 / rom.len().max(1) as f64 * 100.0
+        )?;
+        let mut index = 0;
+        while index < rom.len() {
+            if self.count(index) > 0 {
+                index += 1;
+                continue;
+            }
+            let start = index;
+            while index < rom.len() && self.count(index) == 0 {
+                index += 1;
+            }
+            let range = if index - start == 1 {
+                format!("{:03X}", address(start))
+            } else {
+                format!("{:03X}-{:03X}", address(start), address(index - 1))
+            };
+            let words = index - start;
+            let plural = if words == 1 { "" } else { "s" };
+            writeln!(
+                out,
+                "  never executed: {:<8} ({} word{})",
+                range, words, plural
+            )?;
+        }
+        Ok(())
+    }
+
+    /// One cell per ROM word, left to right and top to bottom. Words that
+    /// never ran are dark blue; the rest go from red to white on a log
+    /// scale, so a hot loop doesn't wash out everything else.
+    pub fn write_png(&self, rom: &[u8], out: &mut impl Write) -> io::Result<()> {
+        let words = rom.len().div_ceil(2).max(self.counts.len()).max(1);
+        let rows = words.div_ceil(IMAGE_COLUMNS);
+        let (width, height) = (IMAGE_COLUMNS * IMAGE_SCALE, rows * IMAGE_SCALE);
+        let hottest = (self.counts.iter().copied().max().unwrap_or(0) as f64).ln_1p();
+        let mut pixels = vec![0u8; width * height * 3];
+        for address in 0..rows * IMAGE_COLUMNS {
+            let count = self.count(address);
+            let color = if address >= words {
+                [0x00, 0x00, 0x00]
+            } else if count == 0 {
+                [0x10, 0x10, 0x30]
+            } else {
+                heat((count as f64).ln_1p() / hottest)
+            };
+            let (column, row) = (address % IMAGE_COLUMNS, address / IMAGE_COLUMNS);
+            for y in row * IMAGE_SCALE..(row + 1) * IMAGE_SCALE {
+                for x in column * IMAGE_SCALE..(column + 1) * IMAGE_SCALE {
+                    let at = (y * width + x) * 3;
+                    pixels[at..at + 3].copy_from_slice(&color);
+                }
+            }
+        }
+        let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&pixels).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/// The ROM as words, the last one padded if the ROM has an odd length.
+fn words(rom: &[u8]) -> Vec<u16> {
+    rom.chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+        .collect()
+}
+
+/// The address of a word counted from the load address.
+fn address(word: usize) -> usize {
+    LOAD_ADDRESS + 2 * word
+}
+
+/// Dark red at 0, through red and yellow, to white at 1.
+fn heat(level: f64) -> [u8; 3] {
+    let channel = |from: f64| ((level - from).clamp(0.0, 1.0 / 3.0) * 3.0 * 255.0) as u8;
+    [
+        channel(0.0).max(0x60),
+        channel(1.0 / 3.0),
+        channel(2.0 / 3.0),
+    ]
+}
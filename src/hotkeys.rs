@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+/// Emulator actions that can be bound to a host key. Frontends only report
+/// key names; what those keys do is decided here, the same for all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    FrameStep,
+    Reset,
+    Screenshot,
+    RecordGif,
+    RecordVideo,
+    RecordAudio,
+    SaveState,
+    LoadState,
+    Rewind,
+    Turbo,
+    SlowMotion,
+    Fullscreen,
+    Menu,
+    ShowFps,
+    ShowKeys,
+    CyclePalette,
+}
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "quit" => Some(Action::Quit),
+            "pause" => Some(Action::Pause),
+            "frame_step" => Some(Action::FrameStep),
+            "reset" => Some(Action::Reset),
+            "screenshot" => Some(Action::Screenshot),
+            "record_gif" => Some(Action::RecordGif),
+            "record_video" => Some(Action::RecordVideo),
+            "record_audio" => Some(Action::RecordAudio),
+            "save_state" => Some(Action::SaveState),
+            "load_state" => Some(Action::LoadState),
+            "rewind" => Some(Action::Rewind),
+            "turbo" => Some(Action::Turbo),
+            "slow_motion" => Some(Action::SlowMotion),
+            "fullscreen" => Some(Action::Fullscreen),
+            "menu" => Some(Action::Menu),
+            "show_fps" => Some(Action::ShowFps),
+            "show_keys" => Some(Action::ShowKeys),
+            "cycle_palette" => Some(Action::CyclePalette),
+            _ => None,
+        }
+    }
+
+    /// Whether a replay of the recorded keypad input still matches after
+    /// this action. The others change the machine or its speed at a moment
+    /// nothing records. Turbo only runs frames sooner, so it is fine.
+    pub fn is_deterministic(self) -> bool {
+        !matches!(
+            self,
+            Action::Reset | Action::LoadState | Action::Rewind | Action::SlowMotion
+        )
+    }
+}
+
+const DEFAULT_BINDINGS: [(&str, Action); 20] = [
+    ("Escape", Action::Quit),
+    ("Ctrl+C", Action::Quit),
+    ("Close", Action::Quit),
+    ("P", Action::Pause),
+    ("N", Action::FrameStep),
+    ("Backspace", Action::Reset),
+    ("F12", Action::Screenshot),
+    ("F10", Action::RecordGif),
+    ("F8", Action::RecordVideo),
+    ("F7", Action::RecordAudio),
+    ("F5", Action::SaveState),
+    ("F9", Action::LoadState),
+    ("F2", Action::Rewind),
+    ("Tab", Action::Turbo),
+    ("O", Action::SlowMotion),
+    ("F11", Action::Fullscreen),
+    ("F1", Action::Menu),
+    ("F3", Action::ShowFps),
+    ("F6", Action::ShowKeys),
+    ("F4", Action::CyclePalette),
+];
+
+/// Maps key names (`"P"`, `"F5"`, `"Escape"`, `"Ctrl+C"`, ...) to actions.
+pub struct Hotkeys {
+    bindings: HashMap<String, Action>,
+}
+impl Hotkeys {
+    pub fn new() -> Self {
+        let bindings = DEFAULT_BINDINGS
+            .iter()
+            .map(|(key, action)| (key.to_string(), *action))
+            .collect();
+        Hotkeys { bindings }
+    }
+
+    /// Reads `action = key` lines (`#` starts a comment) on top of the
+    /// current bindings, as [`rebind`](Self::rebind) takes them.
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let mut bindings = Vec::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, message),
+                )
+            };
+            let (action, key) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected `action = key`"))?;
+            let action =
+                Action::from_name(action.trim()).ok_or_else(|| invalid("unknown action"))?;
+            bindings.push((action, key_name(key.trim())));
+        }
+        self.rebind(&bindings);
+        Ok(())
+    }
+
+    /// Binds each action to its keys. An action listed loses the keys it
+    /// had, and can be bound to several keys by listing it again.
+    pub fn rebind(&mut self, bindings: &[(Action, String)]) {
+        let mut rebound = Vec::new();
+        for (action, key) in bindings {
+            if !rebound.contains(action) {
+                self.bindings.retain(|_, bound| bound != action);
+                rebound.push(*action);
+            }
+            self.bindings.insert(key.clone(), *action);
+        }
+    }
+
+    pub fn action(&self, key: &str) -> Option<Action> {
+        self.bindings.get(key).copied()
+    }
+}
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Letters are matched case-insensitively and stored uppercase.
+pub fn key_name(key: &str) -> String {
+    if key.chars().count() == 1 {
+        key.to_uppercase()
+    } else {
+        key.to_string()
+    }
+}
@@ -0,0 +1,191 @@
+use crate::architecture::display_size;
+use crate::disasm;
+use crate::emulator::Emulator;
+use crate::frontend::Palette;
+use crate::notes::Notes;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+struct Request {
+    target: String,
+    reply: mpsc::Sender<Response>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Response {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", status).into_bytes(),
+        }
+    }
+}
+
+/// A tiny read-only HTTP API on localhost:
+///
+/// * `/state`: what the machine is doing, registers, timers, stack and
+///   frozen cheats as JSON
+/// * `/framebuffer.png`: the display in the frontend's colors, one PNG
+///   pixel per CHIP-8 pixel
+/// * `/disasm?at=ADDR`: the instructions around ADDR (PC by default)
+///
+/// Connections are handled one at a time on a background thread, and the
+/// emulation loop answers them between frames like remote debug commands.
+pub struct HttpServer {
+    requests: mpsc::Receiver<Request>,
+}
+impl HttpServer {
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = handle(stream, &sender);
+            }
+        });
+        Ok(HttpServer { requests })
+    }
+
+    /// Answers the requests that arrived since the last call.
+    pub fn poll(&mut self, emulator: &Emulator) {
+        while let Ok(request) = self.requests.try_recv() {
+            let _ = request.reply.send(respond(&request.target, emulator));
+        }
+    }
+}
+
+fn handle(stream: TcpStream, requests: &mpsc::Sender<Request>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, nothing here needs them.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => {
+            let (reply, response) = mpsc::channel();
+            let target = target.to_string();
+            if requests.send(Request { target, reply }).is_err() {
+                return Ok(());
+            }
+            response
+                .recv()
+                .unwrap_or_else(|_| Response::error("503 Service Unavailable"))
+        }
+        _ => Response::error("405 Method Not Allowed"),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)
+}
+
+fn respond(target: &str, emulator: &Emulator) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    match path {
+        "/state" => Response::ok("application/json", state_json(emulator).into_bytes()),
+        "/framebuffer.png" => match framebuffer_png(emulator.arch.display(), emulator.palette) {
+            Ok(png) => Response::ok("image/png", png),
+            Err(_) => Response::error("500 Internal Server Error"),
+        },
+        "/disasm" => {
+            let at = query
+                .split('&')
+                .find_map(|param| param.strip_prefix("at="))
+                .map(parse_address);
+            match at {
+                Some(None) => Response::error("400 Bad Request"),
+                Some(Some(at)) => Response::ok("text/plain", disasm(emulator, at).into_bytes()),
+                None => {
+                    let pc = usize::from(emulator.arch.pc());
+                    Response::ok("text/plain", disasm(emulator, pc).into_bytes())
+                }
+            }
+        }
+        _ => Response::error("404 Not Found"),
+    }
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn state_json(emulator: &Emulator) -> String {
+    let registers = emulator.arch.registers();
+    let list = |values: Vec<String>| values.join(",");
+    format!(
+        "{{\"state\":\"{}\",\"pc\":{},\"i\":{},\"v\":[{}],\"dt\":{},\"st\":{},\"sp\":{},\"stack\":[{}],\
+         \"waiting_for_key\":{},\"paused\":{},\"frozen\":[{}]}}\n",
+        emulator.state_name(),
+        registers.pc,
+        registers.i,
+        list(registers.v.iter().map(u8::to_string).collect()),
+        registers.dt,
+        registers.st,
+        registers.sp,
+        list(registers.stack.iter().map(u16::to_string).collect()),
+        emulator.arch.waiting_for_key(),
+        emulator.paused,
+        list(
+            emulator
+                .cheats
+                .frozen()
+                .iter()
+                .map(|poke| format!("\"{}\"", poke))
+                .collect()
+        )
+    )
+}
+
+fn framebuffer_png(display: &[u8], palette: Palette) -> Result<Vec<u8>, png::EncodingError> {
+    let mut png = Vec::new();
+    let (width, height) = display_size(display);
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels: Vec<u8> = display
+        .iter()
+        .flat_map(|&pixel| {
+            let (r, g, b) = palette.color(pixel);
+            [r, g, b]
+        })
+        .collect();
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+fn disasm(emulator: &Emulator, at: usize) -> String {
+    let mut out = Vec::new();
+    let _ = disasm::write_around(&mut out, &emulator.rom, at, 8, &Notes::default());
+    String::from_utf8_lossy(&out).into_owned()
+}
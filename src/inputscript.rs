@@ -0,0 +1,98 @@
+use crate::architecture::{Architecture, KeyEvent};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// Keypad changes to make before given frames, read from lines of
+/// `<frame> <key> down|up`, where the key is the CHIP-8 key 0-F. Blank lines
+/// and lines starting with `#` are ignored.
+pub struct InputScript {
+    events: Vec<(u64, u8, bool)>,
+    next: usize,
+    frame: u64,
+}
+impl InputScript {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut events: Vec<(u64, u8, bool)> = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event = parse_event(line).ok_or_else(|| {
+                let message = format!("line {}: expected `<frame> <key> down|up`", number + 1);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            })?;
+            if events.last().is_some_and(|last| last.0 > event.0) {
+                let message = format!("line {}: frames must not go backwards", number + 1);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            events.push(event);
+        }
+        Ok(InputScript {
+            events,
+            next: 0,
+            frame: 0,
+        })
+    }
+
+    /// Queues the key changes scripted for the frame about to run.
+    pub fn apply_frame(&mut self, arch: &mut Architecture) {
+        while let Some(&(frame, key, pressed)) = self.events.get(self.next) {
+            if frame > self.frame {
+                break;
+            }
+            arch.queue_key(KeyEvent { key, pressed });
+            self.next += 1;
+        }
+        self.frame += 1;
+    }
+}
+
+fn parse_event(line: &str) -> Option<(u64, u8, bool)> {
+    let mut fields = line.split_whitespace();
+    let frame = fields.next()?.parse().ok()?;
+    let key = u8::from_str_radix(fields.next()?, 16)
+        .ok()
+        .filter(|&k| k < 16)?;
+    let pressed = match fields.next()? {
+        "down" => true,
+        "up" => false,
+        _ => return None,
+    };
+    fields.next().is_none().then_some((frame, key, pressed))
+}
+
+/// Writes keypad changes in the format `InputScript` reads, numbered by
+/// the emulated frame they were made before.
+pub struct InputRecorder {
+    out: BufWriter<File>,
+    pending: Vec<(u8, bool)>,
+    frame: u64,
+}
+impl InputRecorder {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# <frame> <key> down|up")?;
+        Ok(InputRecorder {
+            out,
+            pending: Vec::new(),
+            frame: 0,
+        })
+    }
+
+    pub fn record(&mut self, key: u8, pressed: bool) {
+        self.pending.push((key, pressed));
+    }
+
+    /// Writes out the changes made since the last frame, as happening
+    /// before the frame about to run.
+    pub fn push_frame(&mut self) -> io::Result<()> {
+        for (key, pressed) in self.pending.drain(..) {
+            let state = if pressed { "down" } else { "up" };
+            writeln!(self.out, "{} {:X} {}", self.frame, key, state)?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::architecture::Architecture;
+
+/// Frames a read keeps a key shown for, two seconds.
+const RECENT: u64 = 120;
+/// The keypad's keys in the order they sit on the COSMAC VIP, row by row.
+const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Which keys the ROM has read lately (Ex9E, ExA1, Fx0A) and which are
+/// held, shown on the status line so a game's controls can be found by
+/// watching what it asks for.
+pub struct KeyPolls {
+    frame: u64,
+    /// The frame each key was last read in.
+    last_read: [Option<u64>; 16],
+}
+impl KeyPolls {
+    pub fn new() -> Self {
+        KeyPolls {
+            frame: 0,
+            last_read: [None; 16],
+        }
+    }
+
+    /// Call once the instruction has run.
+    pub fn record(&mut self, arch: &Architecture, instruction: u16) {
+        match instruction & 0xF0FF {
+            0xE09E | 0xE0A1 => {
+                let x = usize::from(instruction >> 8) & 0xF;
+                self.read(arch.registers().v[x] & 0xF);
+            }
+            0xF00A => self.read_all(),
+            _ => {}
+        }
+    }
+
+    /// Call once per emulated frame.
+    pub fn end_frame(&mut self, arch: &Architecture) {
+        // Fx0A runs once and then waits, but any key will do all along.
+        if arch.waiting_for_key() {
+            self.read_all();
+        }
+        self.frame += 1;
+    }
+
+    fn read(&mut self, key: u8) {
+        self.last_read[usize::from(key)] = Some(self.frame);
+    }
+
+    fn read_all(&mut self) {
+        self.last_read = [Some(self.frame); 16];
+    }
+
+    /// The keypad as `KEYS 1.3C .5.. ...`: keys read lately show their
+    /// digit, the rest a dot, and held keys are bracketed.
+    pub fn text(&self, arch: &Architecture) -> String {
+        let held = arch.keys();
+        let mut text = "KEYS".to_string();
+        for row in LAYOUT {
+            text.push(' ');
+            for key in row {
+                let recent = self.last_read[usize::from(key)]
+                    .is_some_and(|frame| self.frame - frame < RECENT);
+                let shown = if recent {
+                    format!("{:X}", key)
+                } else {
+                    ".".to_string()
+                };
+                if held[usize::from(key)] {
+                    text.push_str(&format!("[{}]", shown));
+                } else {
+                    text.push_str(&shown);
+                }
+            }
+        }
+        text
+    }
+}
+impl Default for KeyPolls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
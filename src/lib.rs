@@ -0,0 +1,67 @@
+//! A CHIP-8 interpreter. The core is `Chip8`, which runs a `Program`
+//! loaded into its RAM one 60Hz frame at a time and leaves the 64x32
+//! display as one byte per pixel; the rest of the crate is what the
+//! `chip-n-claw` binary builds on it (frontends, recording, debugging),
+//! there for embedders to reuse or ignore.
+//!
+//! ```no_run
+//! use chip_n_claw::{Chip8, Program, INSTRUCTIONS_PER_FRAME};
+//!
+//! let rom = std::fs::read("pong.ch8").unwrap();
+//! let mut chip8 = Chip8::new();
+//! chip8.load_rom(&rom);
+//! let program = Program::new(rom);
+//! chip8.run_frame(&program, INSTRUCTIONS_PER_FRAME, |_, _, _| true);
+//! let lit = chip8.display().iter().filter(|&&pixel| pixel != 0).count();
+//! ```
+pub mod architecture;
+pub mod asm;
+pub mod audio;
+pub mod autosave;
+pub mod bench;
+pub mod breakpoint;
+pub mod capture;
+pub mod cfg;
+pub mod cheat;
+pub mod config;
+pub mod crash;
+pub mod debugger;
+pub mod disasm;
+pub mod emulator;
+pub mod events;
+pub mod font;
+pub mod fps;
+pub mod frontend;
+pub mod heatmap;
+pub mod hotkeys;
+pub mod http;
+pub mod inputscript;
+pub mod keypolls;
+pub mod library;
+pub mod lint;
+pub mod menu;
+pub mod notes;
+pub mod patch;
+pub mod pool;
+pub mod power;
+#[cfg(feature = "remote-debug")]
+pub mod remote;
+pub mod rewind;
+pub mod selftest;
+pub mod session;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shutdown;
+pub mod sidecar;
+pub mod statehash;
+pub mod stats;
+pub mod trace;
+pub mod trigger;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+
+pub use architecture::{
+    Architecture as Chip8, AudioPattern, Fault, Instruction, KeyEvent, Outcome, Platform, Program,
+    Quirks, RandomSource, HEIGHT, INSTRUCTIONS_PER_FRAME, WIDTH,
+};
+pub use frontend::{Frontend, InputSource, Keypad, Palette};
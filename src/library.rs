@@ -0,0 +1,147 @@
+use crate::statehash;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How much one ROM has been played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Played {
+    pub launches: u64,
+    /// Time spent running it, pauses left out.
+    pub seconds: u64,
+    /// Unix time of the last launch.
+    pub last_played: u64,
+}
+impl Played {
+    /// `1h05m`, `12m`, or `<1m`.
+    pub fn playtime(&self) -> String {
+        let minutes = self.seconds / 60;
+        match (minutes / 60, minutes % 60) {
+            (0, 0) => "<1m".to_string(),
+            (0, minutes) => format!("{}m", minutes),
+            (hours, minutes) => format!("{}h{:02}m", hours, minutes),
+        }
+    }
+}
+
+/// Launch counts and playtimes of every ROM played, for the menu to sort
+/// the ROM list by. Kept in `$XDG_DATA_HOME/chip-n-claw/library.toml`
+/// (`~/.local/share/chip-n-claw`) and, like autosaves, keyed by a hash of
+/// the ROM, so a renamed copy keeps its history:
+///
+/// ```toml
+/// [roms.9f2c0e6a1b3d4c58]
+/// launches = 3
+/// seconds = 754
+/// last_played = 1760620000
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Library {
+    #[serde(default)]
+    roms: BTreeMap<String, Played>,
+}
+impl Library {
+    /// Reads the library, or returns an empty one if there isn't one yet.
+    pub fn load() -> io::Result<Self> {
+        let text = match fs::read_to_string(path()?) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Library::default()),
+            Err(e) => return Err(e),
+        };
+        toml::from_str(&text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.message().to_string()))
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::create_dir_all(directory()?)?;
+        fs::write(path()?, text)
+    }
+
+    /// Loads the library, changes it and saves it straight away, so that
+    /// several emulators running at once lose as little as possible.
+    fn update(rom: &[u8], change: impl FnOnce(&mut Played)) -> io::Result<()> {
+        let mut library = Library::load()?;
+        change(library.roms.entry(key(rom)).or_default());
+        library.save()
+    }
+
+    pub fn played(&self, rom: &[u8]) -> Option<Played> {
+        self.roms.get(&key(rom)).copied()
+    }
+}
+
+fn key(rom: &[u8]) -> String {
+    format!("{:016x}", statehash::hash_bytes(rom))
+}
+
+fn path() -> io::Result<PathBuf> {
+    Ok(directory()?.join("library.toml"))
+}
+
+/// `$XDG_DATA_HOME/chip-n-claw`, or `~/.local/share/chip-n-claw`.
+fn directory() -> io::Result<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME")
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+            PathBuf::from(home).join(".local/share")
+        }
+    };
+    Ok(data_home.join("chip-n-claw"))
+}
+
+/// Times the ROM being played, to add to the library when it stops.
+pub struct Playtime {
+    rom: Vec<u8>,
+    played: Duration,
+    last_frame: Instant,
+}
+impl Playtime {
+    /// Counts a launch of `rom` at once, so it is already the most
+    /// recently played while it runs.
+    pub fn start(rom: &[u8]) -> io::Result<Self> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Library::update(rom, |played| {
+            played.launches += 1;
+            played.last_played = now;
+        })?;
+        Ok(Playtime {
+            rom: rom.to_vec(),
+            played: Duration::ZERO,
+            last_frame: Instant::now(),
+        })
+    }
+
+    /// Called once per pass of the run loop, with whether the machine ran.
+    pub fn frame(&mut self, ran: bool) {
+        let now = Instant::now();
+        if ran {
+            self.played += now - self.last_frame;
+        }
+        self.last_frame = now;
+    }
+
+    /// Adds the time played to the library and starts timing `rom`, for
+    /// when another ROM is loaded.
+    pub fn switch(&mut self, rom: &[u8]) -> io::Result<()> {
+        let next = Playtime::start(rom)?;
+        std::mem::replace(self, next).finish()
+    }
+
+    /// Adds the time played to the library.
+    pub fn finish(self) -> io::Result<()> {
+        let seconds = self.played.as_secs_f64().round() as u64;
+        Library::update(&self.rom, |played| played.seconds += seconds)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::architecture::{max_rom_size, Instruction, Platform, Quirks, STACK_SIZE};
+use crate::cfg::{self, Block, EdgeKind};
+use crate::disasm::LOAD_ADDRESS;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Every platform, as `--platform` names it.
+const PLATFORMS: [(&str, Platform); 5] = [
+    ("vip", Platform::Vip),
+    ("chip48", Platform::Chip48),
+    ("schip", Platform::Schip),
+    ("xochip", Platform::XoChip),
+    ("modern", Platform::Modern),
+];
+
+/// Something in the ROM that may run differently, or not at all, on
+/// another interpreter.
+pub struct Finding {
+    pub address: usize,
+    pub message: String,
+}
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X}: {}", self.address, self.message)
+    }
+}
+
+/// Checks the code reachable from 0x200 for portability hazards, in
+/// address order. `target` holds the quirks of the interpreter the author
+/// said the ROM targets, which settle the quirk-sensitive opcodes and how
+/// much RAM there is. Without one, RAM ends at 0xFFF as on most.
+///
+/// Only what the control-flow graph shows is checked: I is only known
+/// where an `LD I, addr` earlier in the same block set it, and code only
+/// reachable through `JP V0` isn't seen at all.
+pub fn lint(rom: &[u8], target: Option<Quirks>) -> Vec<Finding> {
+    let blocks = cfg::blocks(rom);
+    let mut findings = BTreeMap::new();
+    for block in &blocks {
+        let mut i = None;
+        for &address in &block.instructions {
+            let instruction = instruction_at(rom, address);
+            if let Some(message) = check(rom, instruction, i, target) {
+                findings.insert(address, message);
+            }
+            i = match instruction {
+                Instruction::LdI { nnn } => Some(usize::from(nnn)),
+                Instruction::AddI { .. }
+                | Instruction::LdF { .. }
+                | Instruction::StoreRegs { .. }
+                | Instruction::ReadRegs { .. } => None,
+                _ => i,
+            };
+        }
+    }
+    for (address, depth) in deep_calls(&blocks) {
+        findings.entry(address).or_insert_with(|| {
+            format!(
+                "calls can nest {} deep here, more than the {}-entry stack holds",
+                depth, STACK_SIZE
+            )
+        });
+    }
+    findings
+        .into_iter()
+        .map(|(address, message)| Finding { address, message })
+        .collect()
+}
+
+fn instruction_at(rom: &[u8], address: usize) -> Instruction {
+    let offset = address - LOAD_ADDRESS;
+    let word = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+    Instruction::decode(word).expect("blocks only hold instructions")
+}
+
+/// The hazard in a single instruction, given I if it is known.
+fn check(
+    rom: &[u8],
+    instruction: Instruction,
+    i: Option<usize>,
+    target: Option<Quirks>,
+) -> Option<String> {
+    let in_rom = |address: usize| (LOAD_ADDRESS..LOAD_ADDRESS + rom.len()).contains(&address);
+    let last_address = LOAD_ADDRESS + max_rom_size(target.unwrap_or_default()) - 1;
+    let missing: Vec<&str> = PLATFORMS
+        .iter()
+        .filter(|(_, platform)| !instruction.platforms().contains(platform))
+        .map(|&(name, _)| name)
+        .collect();
+    match target {
+        None if !missing.is_empty() => {
+            return Some(format!(
+                "{} isn't on {}; pass --platform to say which the ROM targets",
+                instruction,
+                missing.join(", ")
+            ))
+        }
+        Some(quirks) if instruction.with_quirks(quirks) != Some(instruction) => {
+            return Some(format!("{} isn't on the target platform", instruction))
+        }
+        _ => {}
+    }
+    match instruction {
+        // Shifting a register into itself gives the same result either way.
+        Instruction::Shr { x, y } | Instruction::Shl { x, y } if x != y && target.is_none() => {
+            Some(format!(
+                "{} shifts V{:X} on the COSMAC VIP but V{:X} on CHIP-48 and later; \
+                 pass --platform to say which",
+                instruction, y, x
+            ))
+        }
+        Instruction::Jp { nnn } | Instruction::Call { nnn } if !in_rom(usize::from(nnn)) => {
+            Some(format!("{} leaves the ROM", instruction))
+        }
+        Instruction::JpV0 { nnn } if usize::from(nnn) + 0xFF > last_address => {
+            Some(format!("{} can run past the end of RAM", instruction))
+        }
+        Instruction::LdB { .. } | Instruction::StoreRegs { .. } => match i {
+            Some(i) if i < LOAD_ADDRESS => Some(format!(
+                "{} writes to {:03X}, below 0x200 where the interpreter lives",
+                instruction, i
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Every CALL that can run with the stack already full, and how deep the
+/// calls are nested there. Recursion is reported once it gets too deep.
+fn deep_calls(blocks: &[Block]) -> BTreeMap<usize, usize> {
+    let by_start: BTreeMap<usize, &Block> =
+        blocks.iter().map(|block| (block.start, block)).collect();
+    let mut deep = BTreeMap::new();
+    let mut seen = HashSet::new();
+    let mut pending = vec![(LOAD_ADDRESS, 0)];
+    while let Some((start, depth)) = pending.pop() {
+        if !seen.insert((start, depth)) {
+            continue;
+        }
+        let Some(block) = by_start.get(&start) else {
+            continue;
+        };
+        for edge in &block.edges {
+            let depth = match edge.kind {
+                EdgeKind::Call => depth + 1,
+                _ => depth,
+            };
+            if depth > STACK_SIZE {
+                let call = *block.instructions.last().unwrap();
+                deep.entry(call).or_insert(depth);
+                continue;
+            }
+            pending.push((edge.to, depth));
+        }
+    }
+    deep
+}
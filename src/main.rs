@@ -1,22 +1,1441 @@
+// The browser gets the library's bindings (src/wasm.rs) instead: it has no
+// terminal, files or signals for the command line to use.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use chip_n_claw::architecture::{
+    max_rom_size, Architecture, Outcome, Platform, Program, Quirks, INSTRUCTIONS_PER_FRAME,
+};
+use chip_n_claw::breakpoint::OpcodePattern;
+use chip_n_claw::cheat::{Cheats, Poke};
+use chip_n_claw::config;
+use chip_n_claw::debugger::Debugger;
+use chip_n_claw::emulator::{Emulator, OnHalt, FRAME};
+use chip_n_claw::events::Event;
+use chip_n_claw::fps::FpsCounter;
+use chip_n_claw::frontend::ascii::Ascii;
+#[cfg(feature = "gpio-keypad")]
+use chip_n_claw::frontend::button_matrix;
+#[cfg(feature = "fbdev")]
+use chip_n_claw::frontend::fbdev::Framebuffer;
+#[cfg(feature = "gl")]
+use chip_n_claw::frontend::gl::Gl;
+#[cfg(feature = "wgpu")]
+use chip_n_claw::frontend::gpu::Gpu;
+#[cfg(feature = "led-matrix")]
+use chip_n_claw::frontend::led_matrix::LedMatrix;
+#[cfg(feature = "oled")]
+use chip_n_claw::frontend::oled::Controller;
+#[cfg(all(feature = "oled", target_os = "linux"))]
+use chip_n_claw::frontend::oled::{i2cdev, Oled};
+use chip_n_claw::frontend::terminal::{Render, Terminal};
+#[cfg(any(feature = "wgpu", feature = "gl"))]
+use chip_n_claw::frontend::window::{self, Renderer, Windowed};
+use chip_n_claw::frontend::{Frontend, InputSource, Keypad, Palette};
+use chip_n_claw::hotkeys::{Action, Hotkeys};
+use chip_n_claw::keypolls::KeyPolls;
+use chip_n_claw::library::Playtime;
+use chip_n_claw::notes::Notes;
+use chip_n_claw::patch::Patch;
+use chip_n_claw::power::LowPower;
+#[cfg(feature = "remote-debug")]
+use chip_n_claw::remote;
+use chip_n_claw::rewind::Rewind;
+use chip_n_claw::session::SessionStats;
+use chip_n_claw::shutdown::Shutdown;
+use chip_n_claw::sidecar::Sidecar;
+use chip_n_claw::trigger::{Condition, Trigger};
+use chip_n_claw::watch::{Reload, RomWatcher};
+use chip_n_claw::{
+    asm, autosave, bench, cfg, crash, disasm, font, heatmap, http, inputscript, lint, menu,
+    selftest, statehash, stats, trace,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand};
 use std::env;
+use std::ffi::OsString;
 use std::fs;
-mod architecture;
-use architecture::Architecture;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+const DEFAULT_TRACE_LENGTH: usize = 100_000;
+
+/// Seed used by `--deterministic` unless `--seed` picks another.
+const DETERMINISTIC_SEED: u64 = 0;
+
+/// A CHIP-8, SUPER-CHIP and XO-CHIP interpreter. `chip-n-claw ROM` is short
+/// for `chip-n-claw run ROM`.
+#[derive(Parser)]
+#[command(
+    version,
+    arg_required_else_help = true,
+    after_help = "The README lists every option."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play ROM
+    Run(RunArgs),
+    /// Step through ROM at a prompt, or inspect a crash's core dump
+    Debug {
+        /// The core dump a crash left behind
+        #[arg(long, value_name = "CORE")]
+        core: Option<String>,
+        #[command(flatten)]
+        run: RunArgs,
+    },
+    /// List ROM's instructions
+    Disasm {
+        #[arg(value_name = "ROM")]
+        rom: String,
+    },
+    /// Assemble SOURCE into ROM
+    Asm {
+        #[arg(value_name = "SOURCE")]
+        source: String,
+        #[arg(short = 'o', value_name = "ROM")]
+        output: String,
+        /// Also write every statement's address, bytes, file and line
+        #[arg(long, value_name = "FILE")]
+        listing: Option<String>,
+    },
+    /// Print ROM's control-flow graph as Graphviz
+    Cfg {
+        #[arg(value_name = "ROM")]
+        rom: String,
+        /// Write the graph to FILE instead of standard output
+        #[arg(long, value_name = "FILE")]
+        dot: Option<String>,
+    },
+    /// Look for mistakes and platform-specific code in ROM
+    Lint {
+        #[arg(value_name = "ROM")]
+        rom: String,
+        /// The platform ROM targets, which silences the quirk warnings
+        #[arg(long, value_name = "NAME", value_parser = parse_platform)]
+        platform: Option<Platform>,
+    },
+    /// Run ROM and write every instruction's machine state to TRACE
+    RecordTrace {
+        #[arg(value_name = "ROM")]
+        rom: String,
+        #[arg(value_name = "TRACE")]
+        trace: String,
+        #[arg(default_value_t = DEFAULT_TRACE_LENGTH)]
+        instructions: usize,
+    },
+    /// Run ROM again and report the first step that differs from TRACE
+    VerifyTrace {
+        #[arg(value_name = "ROM")]
+        rom: String,
+        #[arg(value_name = "TRACE")]
+        trace: String,
+    },
+    /// Check every opcode's quirks
+    Selftest,
+}
+
+/// The options of `run` and `debug`. Anything left out takes its value from
+/// the game's sidecar, then the config file, then the default.
+#[derive(Args)]
+struct RunArgs {
+    /// The program to run
+    #[arg(value_name = "ROM")]
+    rom: String,
+    /// Reset and reload ROM whenever the file changes
+    #[arg(long)]
+    watch: bool,
+    /// Swap in ROM's new code when it changes, keeping the machine's state
+    #[arg(long)]
+    hot_reload: bool,
+    /// Seed for Cxkk's random numbers
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+    /// Fix the seed and refuse anything that depends on the wall clock
+    #[arg(long)]
+    deterministic: bool,
+    /// vip, chip48, schip, xochip or modern quirks
+    #[arg(long, value_name = "NAME", value_parser = parse_platform)]
+    platform: Option<Platform>,
+    /// One quirk on top of the platform, like vf_reset=false
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_quirk)]
+    quirk: Vec<String>,
+    /// Several quirks, comma-separated
+    #[arg(long, value_name = "LIST", value_parser = parse_quirk, value_delimiter = ',')]
+    quirks: Vec<String>,
+    /// Instructions per frame
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "ips")]
+    speed: Option<u32>,
+    /// Instructions per second, rounded up to a whole number per frame
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    ips: Option<u32>,
+    /// A 16x5 hex font to use instead of the built-in one
+    #[arg(long, value_name = "FILE")]
+    font: Option<String>,
+    /// Colors, by preset name
+    #[arg(long, value_name = "NAME", value_parser = parse_palette)]
+    palette: Option<Palette>,
+    /// Window size in pixels per CHIP-8 pixel, for --render wgpu and gl
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    scale: Option<u32>,
+    /// Ignore ~/.config/chip-n-claw/config.toml
+    #[arg(long)]
+    no_config: bool,
+    /// blocks, braille, kitty, sixel, ascii or a window
+    #[arg(long, value_name = "NAME", value_parser = parse_render)]
+    render: Option<Output>,
+    /// Run without showing anything
+    #[arg(long)]
+    headless: bool,
+    /// Same as the debug subcommand
+    #[arg(long)]
+    debug: bool,
+    /// Start with the machine paused
+    #[arg(long)]
+    start_paused: bool,
+    /// Sleep when the program is waiting for a key
+    #[arg(long)]
+    low_power: bool,
+    /// Save when quitting and resume where the game was left
+    #[arg(long)]
+    autosave: bool,
+    /// Show the frame rate
+    #[arg(long)]
+    show_fps: bool,
+    /// Show which keypad keys are held
+    #[arg(long)]
+    show_keys: bool,
+    /// Frames in a row that may go undrawn when the host falls behind
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    max_skip: u32,
+    /// Pause before running an opcode like D___ or F000/D000
+    #[arg(long, value_name = "PATTERN", value_parser = parse_opcode_pattern)]
+    break_op: Vec<OpcodePattern>,
+    /// Save a screenshot when a condition like VF=01@2A4 or pixel=32,16 holds
+    #[arg(long, value_name = "CONDITION", value_parser = parse_condition)]
+    capture_on: Vec<Condition>,
+    /// Hold a register or RAM address at a value, like V3=05 or 2A0=03
+    #[arg(long, value_name = "POKE", value_parser = parse_poke)]
+    freeze: Vec<Poke>,
+    /// Apply a patch file to ROM before running it
+    #[arg(long, value_name = "FILE")]
+    patch: Vec<String>,
+    /// Rebind the hotkeys from a file
+    #[arg(long, value_name = "FILE")]
+    hotkeys: Option<String>,
+    /// Percent of full volume for audio recordings
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+    volume: Option<u8>,
+    /// Record the display to an animated GIF
+    #[arg(long, value_name = "FILE")]
+    record_gif: Option<String>,
+    /// Record the display to a video
+    #[arg(long, value_name = "FILE")]
+    record_video: Option<String>,
+    /// Record the buzzer to a WAV file
+    #[arg(long, value_name = "FILE")]
+    record_audio: Option<String>,
+    /// Write a hash of every frame to FILE
+    #[arg(long, value_name = "FILE")]
+    hash_frames: Option<String>,
+    /// Write every keypad press and release to FILE
+    #[arg(long, value_name = "FILE")]
+    record_input: Option<String>,
+    /// Play back the keypad input --record-input wrote
+    #[arg(long, value_name = "FILE")]
+    replay_input: Option<String>,
+    /// Start from a save state instead of a fresh machine
+    #[arg(long, value_name = "FILE")]
+    load_state: Option<String>,
+    /// Quit after this many frames
+    #[arg(long, value_name = "N")]
+    frames: Option<u64>,
+    /// Print how many times each kind of instruction ran when quitting
+    #[arg(long)]
+    stats: bool,
+    /// Print a summary of the run when quitting
+    #[arg(long)]
+    summary: bool,
+    /// Write how often each ROM address ran to FILE, a listing or a .png
+    #[arg(long, value_name = "FILE")]
+    heatmap: Option<String>,
+    /// Print the share of ROM that ran and the ranges that never did
+    #[arg(long)]
+    coverage: bool,
+    /// Run N frames as fast as possible and report the speed
+    #[arg(long, value_name = "N")]
+    bench: Option<u64>,
+    /// Play at a fraction of full speed, like 0.25
+    #[arg(long, value_name = "SPEED", value_parser = parse_slow_motion)]
+    slow_motion: Option<f64>,
+    /// Seconds of states kept for the rewind key
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    rewind: Option<u64>,
+    /// quit, menu, finished or next
+    #[arg(long, value_name = "ACTION", value_parser = parse_on_halt)]
+    on_halt: Option<OnHalt>,
+    /// Serve the display and controls over HTTP on PORT
+    #[arg(long, value_name = "PORT")]
+    http_port: Option<u16>,
+    /// Serve the debugger over TCP on PORT
+    #[cfg(feature = "remote-debug")]
+    #[arg(long, value_name = "PORT")]
+    remote_debug: Option<u16>,
+    /// Row and column GPIO lines of a button matrix, like 5,6,13,19/12,16,20,21
+    #[cfg(feature = "gpio-keypad")]
+    #[arg(long, value_name = "ROWS/COLUMNS", value_parser = parse_gpio_keypad)]
+    gpio_keypad: Option<([u32; 4], [u32; 4])>,
+    /// The i2c-dev bus of --render ssd1306 or sh1106 [default: /dev/i2c-1]
+    #[cfg(feature = "oled")]
+    #[arg(long, value_name = "PATH")]
+    i2c_bus: Option<String>,
+}
+
+fn parse_platform(name: &str) -> Result<Platform, String> {
+    Platform::from_name(name)
+        .ok_or_else(|| "expected one of chip8, vip, chip48, schip, xochip, modern".to_string())
+}
+
+fn parse_quirk(setting: &str) -> Result<String, String> {
+    let setting = setting.trim();
+    match Quirks::default().with(setting) {
+        Some(_) => Ok(setting.to_string()),
+        None => Err("expected NAME=VALUE, like vf_reset=false or dxy0=16x16".to_string()),
+    }
+}
+
+fn parse_palette(name: &str) -> Result<Palette, String> {
+    Palette::preset(name)
+        .ok_or_else(|| format!("expected one of {}", Palette::preset_names().join(", ")))
+}
+
+fn parse_render(name: &str) -> Result<Output, String> {
+    match name {
+        "ascii" => Ok(Output::Ascii),
+        #[cfg(feature = "wgpu")]
+        "wgpu" => Ok(Output::Wgpu),
+        #[cfg(feature = "gl")]
+        "gl" => Ok(Output::Gl),
+        #[cfg(feature = "fbdev")]
+        "fbdev" => Ok(Output::Fbdev),
+        #[cfg(feature = "led-matrix")]
+        "led-matrix" => Ok(Output::LedMatrix),
+        #[cfg(feature = "oled")]
+        "ssd1306" | "sh1106" => Ok(Output::Oled(Controller::from_name(name).unwrap())),
+        _ => Render::from_name(name)
+            .map(Output::Terminal)
+            .ok_or_else(|| {
+                let names = ["blocks", "braille", "kitty", "sixel", "ascii"];
+                format!(
+                    "expected one of {}",
+                    [&names, FEATURE_RENDERS].concat().join(", ")
+                )
+            }),
+    }
+}
+
+fn parse_opcode_pattern(pattern: &str) -> Result<OpcodePattern, String> {
+    OpcodePattern::parse(pattern)
+        .ok_or_else(|| "expected an opcode pattern like D___ or F000/D000".to_string())
+}
+
+fn parse_condition(condition: &str) -> Result<Condition, String> {
+    Condition::parse(condition)
+        .ok_or_else(|| "expected a condition like VF=01@2A4 or pixel=32,16".to_string())
+}
+
+fn parse_poke(poke: &str) -> Result<Poke, String> {
+    Poke::parse(poke).ok_or_else(|| {
+        "expected a register or RAM address and a value, like V3=05 or 2A0=03".to_string()
+    })
+}
+
+fn parse_slow_motion(speed: &str) -> Result<f64, String> {
+    speed
+        .parse()
+        .ok()
+        .filter(|&speed| speed > 0.0 && speed < 1.0)
+        .ok_or_else(|| "expected a speed between 0 and 1, like 0.25".to_string())
+}
+
+fn parse_on_halt(name: &str) -> Result<OnHalt, String> {
+    OnHalt::from_name(name).ok_or_else(|| "expected one of quit, menu, finished, next".to_string())
+}
+
+#[cfg(feature = "gpio-keypad")]
+fn parse_gpio_keypad(pins: &str) -> Result<([u32; 4], [u32; 4]), String> {
+    button_matrix::parse_pins(pins)
+        .ok_or_else(|| "expected ROWS/COLUMNS lines, like 5,6,13,19/12,16,20,21".to_string())
+}
+
+/// Puts `run` in front of arguments that don't start with a subcommand, so
+/// `chip-n-claw ROM` plays ROM.
+fn with_default_subcommand(mut args: Vec<OsString>) -> Vec<OsString> {
+    let command = Cli::command();
+    let explicit = args.get(1).and_then(|arg| arg.to_str()).is_none_or(|arg| {
+        matches!(arg, "-h" | "--help" | "-V" | "--version" | "help")
+            || command.find_subcommand(arg).is_some()
+    });
+    if !explicit {
+        args.insert(1, "run".into());
+    }
+    args
+}
+
+/// Where the display is drawn, picked with `--render`.
+#[derive(Clone, Copy)]
+enum Output {
+    Terminal(Render),
+    Ascii,
+    #[cfg(feature = "wgpu")]
+    Wgpu,
+    #[cfg(feature = "gl")]
+    Gl,
+    #[cfg(feature = "fbdev")]
+    Fbdev,
+    #[cfg(feature = "led-matrix")]
+    LedMatrix,
+    #[cfg(feature = "oled")]
+    Oled(Controller),
+}
+impl Output {
+    /// Whether this draws in a window, the one place `--scale` means
+    /// anything.
+    fn is_window(self) -> bool {
+        match self {
+            #[cfg(feature = "wgpu")]
+            Output::Wgpu => true,
+            #[cfg(feature = "gl")]
+            Output::Gl => true,
+            _ => false,
+        }
+    }
+}
+
+/// The `--render` names that depend on optional features, in this build.
+const FEATURE_RENDERS: &[&str] = &[
+    #[cfg(feature = "wgpu")]
+    "wgpu",
+    #[cfg(feature = "gl")]
+    "gl",
+    #[cfg(feature = "fbdev")]
+    "fbdev",
+    #[cfg(feature = "led-matrix")]
+    "led-matrix",
+    #[cfg(feature = "oled")]
+    "ssd1306",
+    #[cfg(feature = "oled")]
+    "sh1106",
+];
+
+struct Options {
+    rom_path: String,
+    watch: bool,
+    hot_reload: bool,
+    seed: Option<u64>,
+    deterministic: bool,
+    quirks: Quirks,
+    instructions_per_frame: usize,
+    font: Option<[u8; font::FONT_SIZE]>,
+    headless: bool,
+    output: Output,
+    start_paused: bool,
+    /// Run the `--debug` REPL instead of a frontend.
+    debug: bool,
+    low_power: bool,
+    autosave: bool,
+    show_fps: bool,
+    show_keys: bool,
+    /// Frames in a row that may go undrawn when the host falls behind.
+    max_skip: u32,
+    opcode_breaks: Vec<OpcodePattern>,
+    captures: Vec<Condition>,
+    cheats: Cheats,
+    patches: Vec<Patch>,
+    hotkeys: Hotkeys,
+    keypad: Keypad,
+    palette: Option<Palette>,
+    record_gif: Option<String>,
+    record_video: Option<String>,
+    record_audio: Option<String>,
+    /// Percent of full volume for audio recordings.
+    volume: u8,
+    hash_frames: Option<String>,
+    record_input: Option<String>,
+    replay_input: Option<String>,
+    /// A save state to start from instead of a fresh machine.
+    load_state: Option<String>,
+    /// Quit after this many frames.
+    frames: Option<u64>,
+    stats: bool,
+    summary: bool,
+    heatmap: Option<String>,
+    coverage: bool,
+    bench: Option<u64>,
+    slow_motion: Option<f64>,
+    /// Seconds of states kept for the rewind key.
+    rewind: Option<usize>,
+    /// Set by `--on-halt`; otherwise a halt is shown, or ends the run
+    /// where no one can see it.
+    on_halt: Option<OnHalt>,
+    http_port: Option<u16>,
+    #[cfg(feature = "remote-debug")]
+    remote_debug: Option<u16>,
+    #[cfg(any(feature = "wgpu", feature = "gl"))]
+    scale: u32,
+    /// Row and column GPIO lines of a button matrix keypad.
+    #[cfg(feature = "gpio-keypad")]
+    gpio_keypad: Option<([u32; 4], [u32; 4])>,
+    /// Where the OLED is, when `output` is one.
+    #[cfg(feature = "oled")]
+    i2c_bus: Option<String>,
+}
+impl Options {
+    fn new(args: RunArgs) -> Self {
+        let mut options = Options {
+            rom_path: args.rom,
+            watch: args.watch || args.hot_reload,
+            hot_reload: args.hot_reload,
+            seed: args.seed,
+            deterministic: args.deterministic,
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+            font: None,
+            headless: args.headless,
+            output: args.render.unwrap_or(Output::Terminal(Render::Blocks)),
+            start_paused: args.start_paused,
+            debug: args.debug,
+            low_power: args.low_power,
+            autosave: args.autosave,
+            show_fps: args.show_fps,
+            show_keys: args.show_keys,
+            max_skip: args.max_skip,
+            opcode_breaks: args.break_op,
+            captures: args.capture_on,
+            cheats: Cheats::default(),
+            patches: Vec::new(),
+            hotkeys: Hotkeys::new(),
+            keypad: Keypad::new(),
+            palette: None,
+            record_gif: args.record_gif,
+            record_video: args.record_video,
+            record_audio: args.record_audio,
+            volume: 100,
+            hash_frames: args.hash_frames,
+            record_input: args.record_input,
+            replay_input: args.replay_input,
+            load_state: args.load_state,
+            frames: args.frames,
+            stats: args.stats,
+            summary: args.summary,
+            heatmap: args.heatmap,
+            coverage: args.coverage,
+            bench: args.bench,
+            slow_motion: args.slow_motion,
+            rewind: args.rewind.map(|seconds| seconds as usize),
+            on_halt: args.on_halt,
+            http_port: args.http_port,
+            #[cfg(feature = "remote-debug")]
+            remote_debug: args.remote_debug,
+            #[cfg(any(feature = "wgpu", feature = "gl"))]
+            scale: window::DEFAULT_SCALE,
+            #[cfg(feature = "gpio-keypad")]
+            gpio_keypad: args.gpio_keypad,
+            #[cfg(feature = "oled")]
+            i2c_bus: args.i2c_bus,
+        };
+        for poke in args.freeze {
+            options.cheats.freeze(poke);
+        }
+        if let Some(path) = &args.font {
+            match font::load_font(path) {
+                Ok(font) => options.font = Some(font),
+                Err(e) => {
+                    eprintln!("Could not load font {}: {}", path, e);
+                    process::exit(2);
+                }
+            }
+        }
+        for path in &args.patch {
+            match Patch::load(path) {
+                Ok(patch) => options.patches.push(patch),
+                Err(e) => {
+                    eprintln!("Could not load patch {}: {}", path, e);
+                    process::exit(2);
+                }
+            }
+        }
+        // Every build takes --scale, so a command line works whichever
+        // features it was built with, but only a window has a size.
+        if args.scale.is_some() && (args.headless || !options.output.is_window()) {
+            eprintln!("--scale only sizes a --render wgpu or gl window, ignoring it");
+        }
+        // A deterministic run must not depend on whoever runs it.
+        if !args.no_config && !options.deterministic {
+            match config::load() {
+                Ok(Some(config)) => options.apply_sidecar(&config),
+                Ok(None) => {}
+                Err(e) => {
+                    let path = config::path().unwrap_or_default();
+                    eprintln!("Could not load {}: {}", path.display(), e);
+                    process::exit(2);
+                }
+            }
+        }
+        match Sidecar::load(&options.rom_path) {
+            Ok(Some(sidecar)) => options.apply_sidecar(&sidecar),
+            Ok(None) => {}
+            Err(e) => {
+                let path = Sidecar::path(&options.rom_path);
+                eprintln!("Could not load {}: {}", path, e);
+                process::exit(2);
+            }
+        }
+        // The command line wins over the game's own settings.
+        if let Some(platform) = args.platform {
+            options.quirks = Quirks::preset(platform);
+        }
+        for setting in args.quirk.iter().chain(&args.quirks) {
+            options.quirks = options.quirks.with(setting).unwrap();
+        }
+        if let Some(speed) = args.speed {
+            options.instructions_per_frame = speed as usize;
+        }
+        if let Some(ips) = args.ips {
+            options.instructions_per_frame = (ips as usize).div_ceil(60);
+        }
+        if args.palette.is_some() {
+            options.palette = args.palette;
+        }
+        if let Some(volume) = args.volume {
+            options.volume = volume;
+        }
+        #[cfg(any(feature = "wgpu", feature = "gl"))]
+        if let Some(scale) = args.scale {
+            options.scale = scale;
+        }
+        if let Some(path) = &args.hotkeys {
+            if let Err(e) = options.hotkeys.load(path) {
+                eprintln!("Could not load hotkeys {}: {}", path, e);
+                process::exit(2);
+            }
+        }
+        if options.deterministic {
+            options.check_deterministic();
+        }
+        options
+    }
+
+    /// Fixes the seed and refuses the options that let the outside world
+    /// change the machine at wall-clock moments.
+    fn check_deterministic(&mut self) {
+        self.seed.get_or_insert(DETERMINISTIC_SEED);
+        let conflicts = [
+            ("--watch", self.watch),
+            ("--autosave", self.autosave),
+            ("--slow-motion", self.slow_motion.is_some()),
+            ("--http-port", self.http_port.is_some()),
+            #[cfg(feature = "remote-debug")]
+            ("--remote-debug", self.remote_debug.is_some()),
+        ];
+        if let Some((flag, _)) = conflicts.iter().find(|(_, set)| *set) {
+            eprintln!("{} can't be used with --deterministic", flag);
+            process::exit(2);
+        }
+    }
+
+    /// Takes the settings of the config file or of the game's sidecar.
+    fn apply_sidecar(&mut self, sidecar: &Sidecar) {
+        self.quirks = sidecar.quirks(self.quirks);
+        if let Some(speed) = sidecar.speed {
+            self.instructions_per_frame = speed;
+        }
+        if sidecar.palette.is_some() {
+            self.palette = sidecar.palette;
+        }
+        if let Some(volume) = sidecar.volume {
+            self.volume = volume;
+        }
+        #[cfg(any(feature = "wgpu", feature = "gl"))]
+        if let Some(scale) = sidecar.scale {
+            self.scale = scale;
+        }
+        for (key, host_key) in &sidecar.keys {
+            self.keypad.bind(*key, host_key);
+        }
+        self.hotkeys.rebind(&sidecar.hotkeys);
+    }
+
+    /// Reads the ROM with the `--patch` files applied, in order.
+    fn load_rom(&self) -> Vec<u8> {
+        let mut bytes = read_rom(&self.rom_path);
+        self.patch(&mut bytes);
+        if let Err(e) = check_rom_size(&bytes, self.quirks) {
+            eprintln!("Could not load {}: {}", self.rom_path, e);
+            process::exit(1);
+        }
+        bytes
+    }
+
+    fn patch(&self, rom: &mut Vec<u8>) {
+        for patch in &self.patches {
+            patch.apply(rom);
+        }
+    }
+
+    fn new_machine(&self, rom: &[u8]) -> Architecture {
+        let mut arch = match self.seed {
+            Some(seed) => Architecture::with_seed(seed),
+            None => Architecture::new(),
+        };
+        arch.set_quirks(self.quirks);
+        if let Some(font) = &self.font {
+            arch.load_font(font);
+        }
+        arch.load_rom(rom);
+        arch
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let rom = init_rom(args[1].as_str());
-    let mut arch = Architecture::new();
+    #[cfg(feature = "profile")]
+    let _profile = init_profiling();
+
+    let cli = Cli::parse_from(with_default_subcommand(env::args_os().collect()));
+    let options = match cli.command {
+        Command::Run(args) => Options::new(args),
+        Command::Debug {
+            core: Some(core),
+            run,
+        } => {
+            debug_core(&core, &run.rom);
+            return;
+        }
+        Command::Debug { core: None, run } => {
+            let mut options = Options::new(run);
+            options.debug = true;
+            options
+        }
+        Command::Disasm { rom: path } => {
+            let rom = init_rom(&path, any_platform());
+            if let Err(e) =
+                disasm::write_listing(&mut io::stdout().lock(), &rom, &load_notes(&path))
+            {
+                eprintln!("Could not write the listing: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        Command::Asm {
+            source,
+            output,
+            listing,
+        } => {
+            let assembly = match asm::assemble_file(&source) {
+                Ok(assembly) => assembly,
+                Err(e) => {
+                    eprintln!("Could not assemble {}: {}", source, e);
+                    process::exit(1);
+                }
+            };
+            if let Err(e) = fs::write(&output, &assembly.rom) {
+                eprintln!("Could not write {}: {}", output, e);
+                process::exit(1);
+            }
+            if let Some(path) = listing {
+                let written = fs::File::create(&path)
+                    .and_then(|file| assembly.write_listing(&mut io::BufWriter::new(file)));
+                if let Err(e) = written {
+                    eprintln!("Could not write {}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Command::Cfg { rom, dot } => {
+            let rom = init_rom(&rom, any_platform());
+            let result = match dot {
+                None => cfg::write_dot(&rom, &mut io::stdout().lock()),
+                Some(path) => fs::File::create(path)
+                    .and_then(|file| cfg::write_dot(&rom, &mut io::BufWriter::new(file))),
+            };
+            if let Err(e) = result {
+                eprintln!("Could not write control-flow graph: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        Command::Lint { rom, platform } => {
+            let target = platform.map(Quirks::preset);
+            let findings = lint::lint(&init_rom(&rom, target.unwrap_or_else(any_platform)), target);
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            if !findings.is_empty() {
+                process::exit(1);
+            }
+            return;
+        }
+        Command::RecordTrace {
+            rom,
+            trace: path,
+            instructions,
+        } => {
+            if let Err(e) = trace::record(&init_rom(&rom, Quirks::default()), &path, instructions) {
+                eprintln!("Could not write trace {}: {}", path, e);
+                process::exit(1);
+            }
+            return;
+        }
+        Command::VerifyTrace { rom, trace: path } => {
+            match trace::verify(&init_rom(&rom, Quirks::default()), &path, &load_notes(&rom)) {
+                Ok(None) => println!("trace matches {}", path),
+                Ok(Some(report)) => {
+                    print!("{}", report);
+                    process::exit(1);
+                }
+                Err(e) => {
+                    eprintln!("Could not read trace {}: {}", path, e);
+                    process::exit(2);
+                }
+            }
+            return;
+        }
+        Command::Selftest => {
+            match selftest::write_matrix(&mut io::stdout().lock()) {
+                Ok(true) => {}
+                Ok(false) => process::exit(1),
+                Err(e) => {
+                    eprintln!("Could not write the self-test results: {}", e);
+                    process::exit(2);
+                }
+            }
+            return;
+        }
+    };
+    let rom_path = options.rom_path.as_str();
+    let rom = options.load_rom();
+    if options.debug {
+        let mut debugger = Debugger::new(
+            options.new_machine(&rom),
+            rom,
+            rom_path,
+            options.instructions_per_frame,
+        );
+        debugger.opcode_breaks = options.opcode_breaks.clone();
+        debugger.notes = load_notes(rom_path);
+        debugger
+            .run(io::stdin().lock(), &mut io::stdout().lock())
+            .unwrap();
+        return;
+    }
+    let mut emulator = Emulator::new(options.new_machine(&rom), rom, rom_path);
+    emulator.instructions_per_frame = options.instructions_per_frame;
+    emulator.paused = options.start_paused;
+    emulator.opcode_breaks = options.opcode_breaks.clone();
+    emulator.triggers = options.captures.iter().copied().map(Trigger::new).collect();
+    emulator.cheats = options.cheats.clone();
+    if options.autosave {
+        match autosave::load(&emulator.rom) {
+            Ok(Some(arch)) => {
+                emulator.arch = arch;
+                emulator.message = "Resumed from autosave".to_string();
+            }
+            Ok(None) => {}
+            Err(e) => emulator.message = format!("Could not resume from autosave: {}", e),
+        }
+    }
+    if let Some(path) = &options.load_state {
+        let arch = fs::read(path).and_then(|bytes| Architecture::load_state(&bytes));
+        match arch {
+            Ok(arch) => {
+                emulator.arch = arch;
+            }
+            Err(e) => {
+                eprintln!("Could not load {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    if options.stats {
+        emulator.stats = Some(stats::OpcodeStats::default());
+    }
+    if options.heatmap.is_some() || options.coverage {
+        emulator.heatmap = Some(heatmap::Heatmap::default());
+    }
+    if let Some(path) = &options.hash_frames {
+        match statehash::HashLog::create(path) {
+            Ok(hash_log) => emulator.hash_log = Some(hash_log),
+            Err(e) => {
+                eprintln!("Could not create {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &options.replay_input {
+        match inputscript::InputScript::load(path) {
+            Ok(script) => emulator.input_script = Some(script),
+            Err(e) => {
+                eprintln!("Could not load input script {}: {}", path, e);
+                process::exit(2);
+            }
+        }
+    }
+    if let Some(path) = &options.record_input {
+        match inputscript::InputRecorder::create(path) {
+            Ok(recorder) => emulator.input_recorder = Some(recorder),
+            Err(e) => {
+                eprintln!("Could not create {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(port) = options.http_port {
+        match http::HttpServer::listen(port) {
+            Ok(http) => emulator.http = Some(http),
+            Err(e) => {
+                eprintln!("Could not listen on port {}: {}", port, e);
+                process::exit(1);
+            }
+        }
+    }
+    #[cfg(feature = "remote-debug")]
+    if let Some(port) = options.remote_debug {
+        match remote::RemoteDebugger::listen(port) {
+            Ok(remote) => emulator.remote = Some(remote),
+            Err(e) => {
+                eprintln!("Could not listen on port {}: {}", port, e);
+                process::exit(1);
+            }
+        }
+    }
+    if options.show_fps {
+        emulator.fps = Some(FpsCounter::new());
+    }
+    if options.show_keys {
+        emulator.key_polls = Some(KeyPolls::new());
+    }
+    // Benchmarks and the ASCII log aren't play.
+    if !options.headless && !matches!(options.output, Output::Ascii) && options.bench.is_none() {
+        match Playtime::start(&emulator.rom) {
+            Ok(playtime) => emulator.playtime = Some(playtime),
+            Err(e) => emulator.message = format!("Could not record playtime: {}", e),
+        }
+    }
+    if let Some(seconds) = options.rewind {
+        emulator.rewind = Some(Rewind::new(seconds * 60));
+    }
+    if options.summary {
+        emulator.session = Some(SessionStats::new());
+    }
+    if let Some(speed) = options.slow_motion {
+        emulator.slow_motion_speed = speed;
+        emulator.slow_motion = true;
+    }
+    emulator.volume = options.volume;
+    emulator.set_palette(options.palette.unwrap_or_default());
+    if let Some(path) = &options.record_gif {
+        emulator.start_gif(path);
+        if emulator.gif.is_none() {
+            eprintln!("{}", emulator.message);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.record_video {
+        emulator.start_video(path);
+        if emulator.video.is_none() {
+            eprintln!("{}", emulator.message);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = &options.record_audio {
+        emulator.start_wav(path);
+        if emulator.wav.is_none() {
+            eprintln!("{}", emulator.message);
+            process::exit(1);
+        }
+    }
+    let reload = if options.hot_reload {
+        Reload::Hot
+    } else {
+        Reload::Reset
+    };
+    let mut watcher = options.watch.then(|| RomWatcher::new(rom_path, reload));
+
+    // Without this, Ctrl-C in the headless and window frontends or a
+    // `kill` ends the process before anything below is cleaned up.
+    let shutdown = match Shutdown::install() {
+        Ok(shutdown) => Some(shutdown),
+        Err(e) => {
+            eprintln!(
+                "Could not catch signals, interrupting may lose recordings: {}",
+                e
+            );
+            None
+        }
+    };
+    crash::install_panic_hook();
+    crash::hold_panic_output(!options.headless);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        if let Some(frames) = options.bench {
+            bench::run(&mut emulator, frames);
+            return;
+        }
+        let mut frontend: Option<Box<dyn Frontend>> = if options.headless {
+            None
+        } else {
+            match options.output {
+                Output::Ascii => Some(Box::new(Ascii::new())),
+                Output::Terminal(render) => match Terminal::new(render, options.palette) {
+                    Ok(terminal) => Some(Box::new(terminal)),
+                    Err(e) => {
+                        eprintln!("Could not set up the terminal: {}", e);
+                        process::exit(1);
+                    }
+                },
+                #[cfg(feature = "wgpu")]
+                Output::Wgpu => Some(open_window::<Gpu>(options.palette, options.scale)),
+                #[cfg(feature = "gl")]
+                Output::Gl => Some(open_window::<Gl>(options.palette, options.scale)),
+                #[cfg(feature = "fbdev")]
+                Output::Fbdev => match Framebuffer::new(options.palette) {
+                    Ok(framebuffer) => Some(Box::new(framebuffer)),
+                    Err(e) => {
+                        eprintln!("Could not open the framebuffer: {}", e);
+                        process::exit(1);
+                    }
+                },
+                #[cfg(feature = "led-matrix")]
+                Output::LedMatrix => match LedMatrix::new(options.palette) {
+                    Ok(matrix) => Some(Box::new(matrix)),
+                    Err(e) => {
+                        eprintln!("Could not open the LED matrix: {}", e);
+                        process::exit(1);
+                    }
+                },
+                #[cfg(all(feature = "oled", target_os = "linux"))]
+                Output::Oled(controller) => {
+                    let path = options.i2c_bus.as_deref().unwrap_or(i2cdev::BUS);
+                    let oled = i2cdev::I2cDev::open(path)
+                        .and_then(|bus| Oled::new(bus, controller).map_err(|e| e.0));
+                    match oled {
+                        Ok(mut oled) => {
+                            if let Some(palette) = options.palette {
+                                oled.set_palette(palette);
+                            }
+                            Some(Box::new(oled))
+                        }
+                        Err(e) => {
+                            eprintln!("Could not set up the OLED on {}: {}", path, e);
+                            process::exit(1);
+                        }
+                    }
+                }
+                #[cfg(all(feature = "oled", not(target_os = "linux")))]
+                Output::Oled(_) => {
+                    eprintln!(
+                        "The OLED is only driven from the command line through Linux's i2c-dev"
+                    );
+                    process::exit(1);
+                }
+            }
+        };
+        #[allow(unused_mut)]
+        let mut keypad: Option<Box<dyn InputSource>> = None;
+        #[cfg(feature = "gpio-keypad")]
+        if let Some((rows, columns)) = options.gpio_keypad {
+            match button_matrix::cdev::open(rows, columns) {
+                Ok(matrix) => keypad = Some(Box::new(matrix)),
+                Err(e) => {
+                    eprintln!("Could not set up the GPIO keypad: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        run(
+            &options,
+            &mut emulator,
+            watcher.as_mut(),
+            frontend.as_mut().map(|f| f.as_mut() as &mut dyn Frontend),
+            keypad.as_mut().map(|k| k.as_mut() as &mut dyn InputSource),
+            shutdown.as_ref(),
+        )
+    }));
+    // The frontend has restored the screen by now, so the message stays.
+    crash::hold_panic_output(false);
+    let held = result.is_err() && !options.headless;
+    if let Some(message) = crash::panic_message().filter(|_| held) {
+        eprintln!("{}", message);
+    }
+    if let Some(session) = &emulator.session {
+        session
+            .write_summary(&emulator.arch, &mut io::stdout().lock())
+            .unwrap();
+    }
+    if result.is_err() {
+        match crash::dump_core(&emulator.arch) {
+            Ok(path) => eprintln!(
+                "Machine state written to {}, inspect it with `chip-n-claw debug --core {} {}`",
+                path, path, rom_path
+            ),
+            Err(e) => eprintln!("Could not write core dump: {}", e),
+        }
+        // Finishes the recordings, which exit would cut off. A crashed
+        // machine isn't autosaved; the core dump holds it instead.
+        drop(emulator);
+        process::exit(101);
+    }
+    if options.autosave {
+        if let Err(e) = autosave::save(&emulator.arch, &emulator.rom) {
+            eprintln!("Could not autosave: {}", e);
+        }
+    }
+    if let Some(playtime) = emulator.playtime.take() {
+        if let Err(e) = playtime.finish() {
+            eprintln!("Could not record playtime: {}", e);
+        }
+    }
+    if let Some(stats) = &emulator.stats {
+        stats.write_report(&mut io::stdout().lock()).unwrap();
+    }
+    if let Some(heatmap) = emulator.heatmap.as_ref().filter(|_| options.coverage) {
+        heatmap
+            .write_coverage(&emulator.rom, &mut io::stdout().lock())
+            .unwrap();
+    }
+    if let (Some(heatmap), Some(path)) = (&emulator.heatmap, &options.heatmap) {
+        if let Err(e) = heatmap.save(&emulator.rom, path) {
+            eprintln!("Could not write heatmap {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if let Some(fault) = emulator.arch.fault() {
+        eprintln!("{} stopped: {}", rom_path, fault);
+        drop(emulator);
+        process::exit(1);
+    }
+}
+
+fn debug_core(core_path: &str, rom_path: &str) {
+    let core = match crash::load_core(core_path) {
+        Ok(core) => core,
+        Err(e) => {
+            eprintln!("Could not load core dump {}: {}", core_path, e);
+            process::exit(1);
+        }
+    };
+    let rom = init_rom(rom_path, core.arch.quirks());
+    crash::inspect(&core, &rom, &load_notes(rom_path), &mut io::stdout().lock()).unwrap();
+}
+
+#[cfg(any(feature = "wgpu", feature = "gl"))]
+fn open_window<R: Renderer + 'static>(palette: Option<Palette>, scale: u32) -> Box<dyn Frontend> {
+    match Windowed::<R>::new(palette, scale) {
+        Ok(window) => Box::new(window),
+        Err(e) => {
+            eprintln!("Could not open a window: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run(
+    options: &Options,
+    emulator: &mut Emulator,
+    mut watcher: Option<&mut RomWatcher>,
+    mut frontend: Option<&mut dyn Frontend>,
+    mut keypad: Option<&mut dyn InputSource>,
+    shutdown: Option<&Shutdown>,
+) {
+    let mut next_frame = Instant::now();
+    let mut low_power = options.low_power.then(LowPower::new);
+    let mut palette = options.palette;
+    let mut behind = false;
+    let mut skipped_frames = 0;
+    let mut last_present = Instant::now();
+    // A frontend that takes keys can show a halt or a crash until the user
+    // quits; the ASCII log just ends.
+    let stays_open = frontend.is_some() && !matches!(options.output, Output::Ascii);
+    // Without keys, nothing could close the menu or the finished screen.
+    let on_halt = match options.on_halt {
+        Some(OnHalt::Menu | OnHalt::Finished) if !stays_open => OnHalt::Quit,
+        Some(on_halt) => on_halt,
+        None if stays_open => OnHalt::Finished,
+        None => OnHalt::Quit,
+    };
+    // Whether the last frame that ran left the program over, so its end
+    // is only acted on once.
+    let mut was_finished = false;
+    // A panic in the core, held while the frontend shows it. Quitting
+    // resumes it, so the crash is reported and dumped as usual.
+    let mut crash = None;
+    'frames: for frame in 0..options.frames.unwrap_or(u64::MAX) {
+        if shutdown.is_some_and(Shutdown::requested) {
+            break;
+        }
+        if let Some(mut http) = emulator.http.take() {
+            http.poll(emulator);
+            emulator.http = Some(http);
+        }
+        #[cfg(feature = "remote-debug")]
+        if let Some(mut remote) = emulator.remote.take() {
+            remote.poll(emulator);
+            emulator.remote = Some(remote);
+        }
+        if let Some(watcher) = watcher.as_mut() {
+            if let Some(mut bytes) = watcher.poll() {
+                options.patch(&mut bytes);
+                emulator.message = match check_rom_size(&bytes, options.quirks) {
+                    Ok(()) => {
+                        match watcher.mode {
+                            Reload::Reset => emulator.arch = options.new_machine(&bytes),
+                            Reload::Hot => emulator.arch.load_rom(&bytes),
+                        }
+                        emulator.rom = Program::new(bytes);
+                        emulator.publish(Event::RomReloaded);
+                        "ROM changed on disk, reloaded".to_string()
+                    }
+                    Err(e) => format!("ROM changed on disk, not reloaded: {}", e),
+                };
+            }
+        }
+        let mut actions = Vec::new();
+        if let Some(frontend) = frontend.as_deref_mut() {
+            actions.extend(emulator.handle_input(frontend, &options.hotkeys, &options.keypad));
+        }
+        if let Some(keypad) = keypad.as_deref_mut() {
+            actions.extend(emulator.handle_input(keypad, &options.hotkeys, &options.keypad));
+        }
+        for action in actions {
+            if options.deterministic && !action.is_deterministic() {
+                emulator.message = "Disabled by --deterministic".to_string();
+                continue;
+            }
+            if crash.is_some() && action != Action::Quit {
+                continue;
+            }
+            match action {
+                Action::Quit => break 'frames,
+                Action::Reset => {
+                    emulator.arch = options.new_machine(&emulator.rom);
+                    emulator.publish(Event::Reset);
+                    emulator.paused |= options.start_paused;
+                }
+                Action::CyclePalette => {
+                    let (name, next) = Palette::next_preset(palette);
+                    if let Some(frontend) = frontend.as_deref_mut() {
+                        frontend.set_palette(next);
+                    }
+                    palette = Some(next);
+                    emulator.set_palette(next);
+                    emulator.message = format!("Palette: {}", name);
+                }
+                Action::Fullscreen => {
+                    let toggled = frontend
+                        .as_deref_mut()
+                        .is_some_and(|f| f.toggle_fullscreen());
+                    if !toggled {
+                        emulator.message = "Fullscreen needs a window".to_string();
+                    }
+                }
+                _ => emulator.perform(action),
+            }
+        }
+        if let Some(path) = emulator.requested_rom.take() {
+            emulator.message = if options.deterministic {
+                "Disabled by --deterministic".to_string()
+            } else {
+                match fs::read(&path)
+                    .and_then(|bytes| check_rom_size(&bytes, options.quirks).map(|()| bytes))
+                {
+                    Ok(bytes) => {
+                        let recorded = emulator.playtime.as_mut().map(|p| p.switch(&bytes));
+                        emulator.arch = options.new_machine(&bytes);
+                        emulator.rom = Program::new(bytes);
+                        emulator.paused = options.start_paused;
+                        emulator.publish(Event::RomReloaded);
+                        let message = match recorded {
+                            Some(Err(e)) => {
+                                format!("Loaded {}, playtime not recorded: {}", path, e)
+                            }
+                            _ => format!("Loaded {}", path),
+                        };
+                        emulator.rom_path = path;
+                        message
+                    }
+                    Err(e) => format!("Could not load {}: {}", path, e),
+                }
+            };
+        }
+        let step = std::mem::take(&mut emulator.step_requested);
+        if let Some(session) = emulator.session.as_mut() {
+            session.set_paused(emulator.paused && !step);
+        }
+        let ran = crash.is_none() && (!emulator.paused || step);
+        if let Some(playtime) = emulator.playtime.as_mut() {
+            playtime.frame(ran);
+        }
+        let outcome = if !ran {
+            Outcome::Idle
+        } else {
+            match panic::catch_unwind(AssertUnwindSafe(|| emulator.run_frame(frame))) {
+                Ok(outcome) => outcome,
+                Err(payload) if stays_open => {
+                    emulator.error = Some(crash::panic_text(&*payload));
+                    crash = Some(payload);
+                    Outcome::Idle
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        };
+        // A jump to itself only counts as the end when asked for, since
+        // plenty of runs are meant to keep going until --frames is up.
+        let finished = ran && emulator.finished(outcome, options.on_halt.is_some());
+        if finished && !was_finished {
+            match on_halt {
+                OnHalt::Quit => break,
+                OnHalt::Menu if emulator.menu.is_none() => emulator.perform(Action::Menu),
+                OnHalt::Menu => {}
+                // A game stays on screen, saying it is over, until the user
+                // quits or resets. HALTED already says so after EXIT.
+                OnHalt::Finished if outcome == Outcome::Idle => {
+                    emulator.message = "Finished".to_string()
+                }
+                OnHalt::Finished => {}
+                OnHalt::Next => match menu::next_rom(&emulator.rom_path) {
+                    Some(path) => emulator.requested_rom = Some(path),
+                    None => break,
+                },
+            }
+        }
+        if ran {
+            was_finished = finished;
+        }
+        let events = emulator.take_events();
+        if let Some(frontend) = frontend.as_deref_mut() {
+            for event in &events {
+                frontend.notify(event);
+            }
+        }
+        let status = emulator.status();
+        // A frame that ran behind schedule goes undrawn, so the next one
+        // can start at once and the game keeps its speed.
+        skipped_frames = if behind { skipped_frames + 1 } else { 0 };
+        let menu = emulator.menu_view();
+        // Turbo runs far more frames than anything could show.
+        let shown_recently = emulator.turbo && last_present.elapsed() < FRAME;
+        let present = !behind
+            && !shown_recently
+            && (menu.is_some()
+                || low_power
+                    .as_mut()
+                    .is_none_or(|low_power| low_power.frame(emulator.arch.display(), &status)));
+        if let Some(frontend) = frontend.as_deref_mut().filter(|_| present) {
+            let _span = tracing::trace_span!("present").entered();
+            last_present = Instant::now();
+            match &menu {
+                Some(menu) => frontend.present_menu(emulator.arch.display(), &status, menu),
+                None => frontend.present(emulator.arch.display(), &status),
+            }
+        }
+        if let Some(fps) = emulator.fps.as_mut() {
+            fps.end_frame(present && frontend.is_some());
+        }
+        // Frames are held to 60 a second, so games run at their speed and
+        // idle ones sleep instead of spinning. Runs with --frames are batch
+        // jobs and keep going flat out, and turbo does while the game runs.
+        let uncapped = options.frames.is_some() || emulator.turbo && outcome == Outcome::Running;
+        let paced = !uncapped || options.low_power;
+        behind = false;
+        match emulator.slowed_frame_duration().or(paced.then_some(FRAME)) {
+            Some(duration) => {
+                next_frame += duration;
+                if low_power
+                    .as_ref()
+                    .is_some_and(|low_power| !low_power.wake_now())
+                {
+                    // Run ahead now and sleep off several frames at once.
+                    continue;
+                }
+                match next_frame.checked_duration_since(Instant::now()) {
+                    Some(wait) => thread::sleep(wait),
+                    None if skipped_frames < options.max_skip => behind = true,
+                    None => next_frame = Instant::now(),
+                }
+            }
+            None => next_frame = Instant::now(),
+        }
+    }
+    if let Some(payload) = crash {
+        panic::resume_unwind(payload);
+    }
+}
+
+/// Reads the ROM for a machine with `quirks`, exiting if it doesn't fit.
+fn init_rom(file_path: &str, quirks: Quirks) -> Vec<u8> {
+    let rom = read_rom(file_path);
+    if let Err(e) = check_rom_size(&rom, quirks) {
+        eprintln!("Could not load {}: {}", file_path, e);
+        process::exit(1);
+    }
+    rom
+}
+
+/// For tools that only read a ROM: XO-CHIP's, the most RAM any platform
+/// has, so whatever some platform can load is accepted.
+fn any_platform() -> Quirks {
+    Quirks::preset(Platform::XoChip)
+}
+
+fn read_rom(path: &str) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Could not read ROM {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// The ROM's comments and region names, if it has any.
+fn load_notes(rom_path: &str) -> Notes {
+    match Notes::load(rom_path) {
+        Ok(notes) => notes,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", Notes::path(rom_path), e);
+            process::exit(2);
+        }
+    }
+}
 
-    loop {
-        // TODO: 60hz loop
-        arch.execute(&rom);
+/// ROMs are loaded at 0x200, so only so much of one fits in RAM, which is
+/// bigger on XO-CHIP.
+fn check_rom_size(rom: &[u8], quirks: Quirks) -> io::Result<()> {
+    let max = max_rom_size(quirks);
+    if rom.len() > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} bytes is more than the {} that fit in RAM",
+                rom.len(),
+                max
+            ),
+        ));
     }
+    Ok(())
 }
 
-fn init_rom(file_path: &str) -> Vec<u16> {
-    let rom = fs::read(file_path).unwrap();
-    rom.chunks_exact(2)
-        .map(|chunk| u16::from_le_bytes([chunk[1], chunk[0]]))
-        .collect()
+#[cfg(feature = "profile")]
+fn init_profiling() -> tracing_chrome::FlushGuard {
+    use tracing_subscriber::prelude::*;
+    // Writes ./trace-<timestamp>.json on exit, viewable in chrome://tracing or Perfetto.
+    let (chrome_layer, guard) = tracing_chrome::ChromeLayerBuilder::new().build();
+    tracing_subscriber::registry().with(chrome_layer).init();
+    guard
 }
@@ -0,0 +1,304 @@
+use crate::hotkeys::Action;
+use crate::library::{Library, Played};
+use std::fs;
+use std::path::Path;
+
+/// What the menu shows, for a frontend to draw.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuView {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+impl MenuView {
+    /// The highlighted item on one line, for frontends that only have a
+    /// status line.
+    pub fn status_line(&self) -> String {
+        format!(
+            "{}: < {} > ({}/{}) Up/Down, Enter",
+            self.title,
+            self.items[self.selected],
+            self.selected + 1,
+            self.items.len()
+        )
+    }
+}
+
+/// Ways to move around the menu. Host arrow keys work, and so do the
+/// keypad's 2/8/5/0, so a button matrix without arrows can drive it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Navigation {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+impl Navigation {
+    pub fn from_key(host_key: &str, keypad_key: Option<u8>) -> Option<Self> {
+        match (host_key, keypad_key) {
+            ("Up", _) | (_, Some(0x2)) => Some(Navigation::Up),
+            ("Down", _) | (_, Some(0x8)) => Some(Navigation::Down),
+            ("Enter" | "Space", _) | (_, Some(0x5)) => Some(Navigation::Select),
+            ("Escape" | "Backspace", _) | (_, Some(0x0)) => Some(Navigation::Back),
+            _ => None,
+        }
+    }
+}
+
+/// What choosing an item asks the emulator to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selection {
+    /// Close the menu and carry on.
+    Resume,
+    /// Perform the action. Turbo and slow motion leave the menu open, so
+    /// their options can be flipped back.
+    Action(Action),
+    LoadRom(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    Resume,
+    Reset,
+    LoadRom,
+    SaveState,
+    LoadState,
+    Options,
+    Quit,
+}
+const MAIN_ITEMS: [(Item, &str); 7] = [
+    (Item::Resume, "Resume"),
+    (Item::Reset, "Reset"),
+    (Item::LoadRom, "Load ROM"),
+    (Item::SaveState, "Save state"),
+    (Item::LoadState, "Load state"),
+    (Item::Options, "Options"),
+    (Item::Quit, "Quit"),
+];
+
+/// Orders for the Load ROM page, cycled through by its first item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Sort {
+    Name,
+    Recent,
+    MostPlayed,
+}
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Sort::Name => Sort::Recent,
+            Sort::Recent => Sort::MostPlayed,
+            Sort::MostPlayed => Sort::Name,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Sort::Name => "name",
+            Sort::Recent => "recently played",
+            Sort::MostPlayed => "most played",
+        }
+    }
+}
+
+enum Page {
+    Main,
+    /// The sort order, then the ROMs in the directory of the one running
+    /// with what the library knows of them, then "Back".
+    Roms(Sort, Vec<(String, Option<Played>)>),
+    /// Turbo, slow motion, then "Back".
+    Options,
+}
+
+/// The pause menu. The emulator is paused for as long as it is open.
+pub struct Menu {
+    page: Page,
+    selected: usize,
+    rom_dir: String,
+    /// Whether the emulator was paused before the menu opened, to go back
+    /// to that when it closes.
+    pub was_paused: bool,
+}
+impl Menu {
+    pub fn new(rom_path: &str, was_paused: bool) -> Self {
+        let rom_dir = Path::new(rom_path)
+            .parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Menu {
+            page: Page::Main,
+            selected: 0,
+            rom_dir,
+            was_paused,
+        }
+    }
+
+    /// `turbo` and `slow_motion` are the current settings, shown on the
+    /// options page.
+    pub fn view(&self, turbo: bool, slow_motion: bool) -> MenuView {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let (title, items) = match &self.page {
+            Page::Main => (
+                "Paused",
+                MAIN_ITEMS
+                    .iter()
+                    .map(|(_, name)| name.to_string())
+                    .collect(),
+            ),
+            Page::Roms(sort, roms) => {
+                let roms = roms.iter().map(|(path, played)| {
+                    let name = Path::new(path).file_name().unwrap_or_default();
+                    let name = name.to_string_lossy();
+                    match played {
+                        Some(played) => {
+                            format!("{} ({}x, {})", name, played.launches, played.playtime())
+                        }
+                        None => name.into_owned(),
+                    }
+                });
+                let mut items = vec![format!("Sort: {}", sort.name())];
+                items.extend(roms);
+                items.push("Back".to_string());
+                ("Load ROM", items)
+            }
+            Page::Options => (
+                "Options",
+                vec![
+                    format!("Turbo: {}", on_off(turbo)),
+                    format!("Slow motion: {}", on_off(slow_motion)),
+                    "Back".to_string(),
+                ],
+            ),
+        };
+        MenuView {
+            title: title.to_string(),
+            items,
+            selected: self.selected,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.page {
+            Page::Main => MAIN_ITEMS.len(),
+            Page::Roms(_, roms) => roms.len() + 2,
+            Page::Options => 3,
+        }
+    }
+
+    pub fn navigate(&mut self, navigation: Navigation) -> Option<Selection> {
+        match navigation {
+            Navigation::Up => {
+                self.selected = (self.selected + self.len() - 1) % self.len();
+                None
+            }
+            Navigation::Down => {
+                self.selected = (self.selected + 1) % self.len();
+                None
+            }
+            Navigation::Back => match self.page {
+                Page::Main => Some(Selection::Resume),
+                _ => {
+                    self.show(Page::Main);
+                    None
+                }
+            },
+            Navigation::Select => self.select(),
+        }
+    }
+
+    fn select(&mut self) -> Option<Selection> {
+        let last = self.selected + 1 == self.len();
+        if let Page::Roms(sort, roms) = &mut self.page {
+            if self.selected == 0 {
+                *sort = sort.next();
+                sort_roms(roms, *sort);
+                return None;
+            }
+        }
+        match &self.page {
+            Page::Main => match MAIN_ITEMS[self.selected].0 {
+                Item::Resume => Some(Selection::Resume),
+                Item::Reset => Some(Selection::Action(Action::Reset)),
+                Item::LoadRom => {
+                    let roms = played_roms(&self.rom_dir);
+                    self.show(Page::Roms(Sort::Name, roms));
+                    None
+                }
+                Item::SaveState => Some(Selection::Action(Action::SaveState)),
+                Item::LoadState => Some(Selection::Action(Action::LoadState)),
+                Item::Options => {
+                    self.show(Page::Options);
+                    None
+                }
+                Item::Quit => Some(Selection::Action(Action::Quit)),
+            },
+            Page::Roms(..) | Page::Options if last => {
+                self.show(Page::Main);
+                None
+            }
+            Page::Roms(_, roms) => Some(Selection::LoadRom(roms[self.selected - 1].0.clone())),
+            Page::Options if self.selected == 0 => Some(Selection::Action(Action::Turbo)),
+            Page::Options => Some(Selection::Action(Action::SlowMotion)),
+        }
+    }
+
+    fn show(&mut self, page: Page) {
+        self.page = page;
+        self.selected = 0;
+    }
+}
+
+/// The ROM listed after `rom_path` on the menu's Load ROM page, for
+/// playing a directory through. `None` after the last one.
+pub fn next_rom(rom_path: &str) -> Option<String> {
+    let dir = Path::new(rom_path).parent().unwrap_or(Path::new(""));
+    let roms = list_roms(&dir.to_string_lossy());
+    let at = roms
+        .iter()
+        .position(|path| Path::new(path).file_name() == Path::new(rom_path).file_name())?;
+    roms.get(at + 1).cloned()
+}
+
+/// The ROMs in `dir`, by name, with how much each has been played. A
+/// library that can't be read just leaves them all unplayed.
+fn played_roms(dir: &str) -> Vec<(String, Option<Played>)> {
+    let library = Library::load().unwrap_or_default();
+    list_roms(dir)
+        .into_iter()
+        .map(|path| {
+            let played = fs::read(&path).ok().and_then(|rom| library.played(&rom));
+            (path, played)
+        })
+        .collect()
+}
+
+/// Most recent or most played first, the unplayed last by name.
+fn sort_roms(roms: &mut [(String, Option<Played>)], sort: Sort) {
+    roms.sort_by(|(a_path, a), (b_path, b)| {
+        let key = |played: &Option<Played>| match sort {
+            Sort::Name => (0, 0),
+            Sort::Recent => played.map_or((0, 0), |played| (played.last_played, 0)),
+            Sort::MostPlayed => played.map_or((0, 0), |played| (played.seconds, played.launches)),
+        };
+        key(b).cmp(&key(a)).then_with(|| a_path.cmp(b_path))
+    });
+}
+
+/// The `.ch8` files in `dir`, sorted by name.
+fn list_roms(dir: &str) -> Vec<String> {
+    let dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut roms: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("ch8"))
+        })
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    roms.sort();
+    roms
+}
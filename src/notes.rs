@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/// A named range of addresses, both ends included, like `sprites` for
+/// 300-33F.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub name: String,
+}
+
+/// Comments and region names attached to addresses while reverse
+/// engineering a ROM. They live in a project file next to it
+/// (`game.ch8.notes` for `game.ch8`), one per line:
+///
+/// ```text
+/// comment 2A4 checks for a collision
+/// region 300-33F sprites
+/// ```
+///
+/// and show up in listings, trace reports and the debugger. Blank lines
+/// and lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Notes {
+    comments: BTreeMap<usize, String>,
+    /// In order of their start address.
+    regions: Vec<Region>,
+}
+impl Notes {
+    pub fn path(rom_path: &str) -> String {
+        format!("{}.notes", rom_path)
+    }
+
+    /// Reads the notes of `rom_path`, or returns none if there aren't any.
+    pub fn load(rom_path: &str) -> io::Result<Self> {
+        let text = match fs::read_to_string(Self::path(rom_path)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Notes::default()),
+            Err(e) => return Err(e),
+        };
+        let mut notes = Notes::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !notes.apply(line) {
+                let message = format!(
+                    "line {}: expected `comment ADDRESS TEXT` or `region START-END NAME`",
+                    number + 1
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+        Ok(notes)
+    }
+
+    pub fn save(&self, rom_path: &str) -> io::Result<()> {
+        let text: String = self
+            .lines()
+            .iter()
+            .map(|line| line.clone() + "\n")
+            .collect();
+        fs::write(Self::path(rom_path), text)
+    }
+
+    /// Takes a `comment ADDRESS TEXT` or `region START-END NAME` line, as
+    /// the file and the debugger's commands have them. A comment with no
+    /// text removes the one at that address. False if the line is neither.
+    pub fn apply(&mut self, line: &str) -> bool {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (place, text) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+        let text = text.trim();
+        match kind {
+            "comment" => {
+                let Ok(address) = usize::from_str_radix(place, 16) else {
+                    return false;
+                };
+                if text.is_empty() {
+                    self.comments.remove(&address);
+                } else {
+                    self.comments.insert(address, text.to_string());
+                }
+                true
+            }
+            "region" => {
+                let range = place.split_once('-').and_then(|(start, end)| {
+                    let start = usize::from_str_radix(start, 16).ok()?;
+                    let end = usize::from_str_radix(end, 16).ok()?;
+                    (start <= end).then_some((start, end))
+                });
+                let Some((start, end)) = range.filter(|_| !text.is_empty()) else {
+                    return false;
+                };
+                self.regions.retain(|region| region.start != start);
+                let at = self.regions.partition_point(|region| region.start < start);
+                let name = text.to_string();
+                self.regions.insert(at, Region { start, end, name });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forgets the region starting at `start`. False if there isn't one.
+    pub fn remove_region(&mut self, start: usize) -> bool {
+        let before = self.regions.len();
+        self.regions.retain(|region| region.start != start);
+        self.regions.len() != before
+    }
+
+    pub fn comment(&self, address: usize) -> Option<&str> {
+        self.comments.get(&address).map(String::as_str)
+    }
+
+    /// The region `address` is in, the one starting last if they overlap.
+    pub fn region(&self, address: usize) -> Option<&Region> {
+        self.regions
+            .iter()
+            .rev()
+            .find(|region| (region.start..=region.end).contains(&address))
+    }
+
+    /// The region starting exactly at `address`, for listings to head.
+    pub fn region_at(&self, address: usize) -> Option<&Region> {
+        self.regions.iter().find(|region| region.start == address)
+    }
+
+    /// `region: comment`, or whichever of the two `address` has.
+    pub fn describe(&self, address: usize) -> Option<String> {
+        let region = self.region(address).map(|region| region.name.as_str());
+        match (region, self.comment(address)) {
+            (Some(region), Some(comment)) => Some(format!("{}: {}", region, comment)),
+            (Some(text), None) | (None, Some(text)) => Some(text.to_string()),
+            (None, None) => None,
+        }
+    }
+
+    /// Every note, one per line, as the file has them.
+    pub fn lines(&self) -> Vec<String> {
+        let comments = self
+            .comments
+            .iter()
+            .map(|(address, comment)| format!("comment {:03X} {}", address, comment));
+        let regions = self.regions.iter().map(|region| {
+            format!(
+                "region {:03X}-{:03X} {}",
+                region.start, region.end, region.name
+            )
+        });
+        comments.chain(regions).collect()
+    }
+}
@@ -0,0 +1,102 @@
+use crate::disasm::LOAD_ADDRESS;
+use std::fs;
+use std::io;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+
+/// Bytes to overwrite in a ROM image as it is loaded, so fixes and
+/// trainers can be shared without touching the original file. Writes past
+/// the end of the ROM grow it, padding with zeros.
+///
+/// Two formats are read. IPS files are recognised by their `PATCH` header
+/// and use offsets into the file. Anything else is text, one
+/// `ADDRESS: BYTES` line per write with everything in hex and the address
+/// as the ROM sees it, from 200:
+///
+/// ```text
+/// # infinite lives
+/// 2A4: 00 E0
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Patch {
+    /// Offset into the ROM file and the bytes written there.
+    writes: Vec<(usize, Vec<u8>)>,
+}
+impl Patch {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        match bytes.strip_prefix(IPS_HEADER) {
+            Some(records) => Patch::parse_ips(records),
+            None => Patch::parse_text(&String::from_utf8_lossy(&bytes)),
+        }
+    }
+
+    pub fn apply(&self, rom: &mut Vec<u8>) {
+        for (offset, bytes) in &self.writes {
+            let end = offset + bytes.len();
+            if rom.len() < end {
+                rom.resize(end, 0);
+            }
+            rom[*offset..end].copy_from_slice(bytes);
+        }
+    }
+
+    /// Records are a 3-byte offset, a 2-byte length and the data, or a
+    /// zero length, a 2-byte count and one byte to repeat; all big-endian.
+    fn parse_ips(mut records: &[u8]) -> io::Result<Self> {
+        let truncated = || io::Error::new(io::ErrorKind::InvalidData, "truncated IPS record");
+        let mut take = |count: usize| -> io::Result<&[u8]> {
+            let (taken, rest) = records.split_at_checked(count).ok_or_else(truncated)?;
+            records = rest;
+            Ok(taken)
+        };
+        let mut writes = Vec::new();
+        loop {
+            let offset = take(3)?;
+            if offset == IPS_FOOTER {
+                return Ok(Patch { writes });
+            }
+            let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+            let size = take(2)?;
+            let bytes = match usize::from(u16::from_be_bytes([size[0], size[1]])) {
+                0 => {
+                    let run = take(3)?;
+                    vec![run[2]; usize::from(u16::from_be_bytes([run[0], run[1]]))]
+                }
+                size => take(size)?.to_vec(),
+            };
+            writes.push((offset, bytes));
+        }
+    }
+
+    fn parse_text(text: &str) -> io::Result<Self> {
+        let mut writes = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, message),
+                )
+            };
+            let (address, bytes) = line
+                .split_once(':')
+                .ok_or_else(|| invalid("expected `address: bytes`"))?;
+            let address = usize::from_str_radix(address.trim(), 16)
+                .ok()
+                .filter(|&address| address >= LOAD_ADDRESS)
+                .ok_or_else(|| invalid("expected a hex address from 200"))?;
+            let bytes = bytes
+                .split_whitespace()
+                .map(|byte| u8::from_str_radix(byte, 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| invalid("expected hex bytes"))?;
+            writes.push((address - LOAD_ADDRESS, bytes));
+        }
+        Ok(Patch { writes })
+    }
+}
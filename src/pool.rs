@@ -0,0 +1,135 @@
+use crate::architecture::{
+    display_size, fit_display, Architecture, Outcome, Program, HEIGHT, WIDTH,
+};
+use std::thread;
+
+/// A machine in a pool and the program it runs.
+pub struct Machine {
+    pub arch: Architecture,
+    pub program: Program,
+    /// How its last frame ended, `None` until it has run one.
+    pub outcome: Option<Outcome>,
+}
+
+/// Owns any number of machines, for running many games, or one game under
+/// different quirks or seeds, side by side. `run_frame` steps them all in
+/// lockstep, spread over the host's cores; `run_frame_on` steps one alone.
+///
+/// ```
+/// use chip_n_claw::pool::MachinePool;
+/// use chip_n_claw::{Chip8, Program, HEIGHT, INSTRUCTIONS_PER_FRAME, WIDTH};
+///
+/// let rom = vec![0x60, 0x01, 0x12, 0x02]; // LD V0, 1 then spin
+/// let mut pool = MachinePool::new();
+/// for seed in 0..4 {
+///     let mut arch = Chip8::with_seed(seed);
+///     arch.load_rom(&rom);
+///     pool.add(arch, Program::new(rom.clone()));
+/// }
+/// pool.run_frame(INSTRUCTIONS_PER_FRAME);
+/// assert_eq!(pool.tiled_display(2).len(), 2 * WIDTH * 2 * HEIGHT);
+/// ```
+#[derive(Default)]
+pub struct MachinePool {
+    machines: Vec<Machine>,
+}
+impl MachinePool {
+    pub fn new() -> Self {
+        MachinePool::default()
+    }
+
+    /// Adds a machine, which should already have the program's ROM
+    /// loaded, and returns its index.
+    pub fn add(&mut self, arch: Architecture, program: Program) -> usize {
+        self.machines.push(Machine {
+            arch,
+            program,
+            outcome: None,
+        });
+        self.machines.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn machine(&self, index: usize) -> &Machine {
+        &self.machines[index]
+    }
+
+    /// For queueing keys, poking RAM or swapping the program.
+    pub fn machine_mut(&mut self, index: usize) -> &mut Machine {
+        &mut self.machines[index]
+    }
+
+    pub fn machines(&self) -> &[Machine] {
+        &self.machines
+    }
+
+    /// Runs one frame of `budget` instructions on every machine, halted ones
+    /// included (their timers still tick), and returns how each ended.
+    /// Machines are shared out between threads, one per core.
+    pub fn run_frame(&mut self, budget: usize) -> Vec<Outcome> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = self.machines.len().div_ceil(threads).max(1);
+        thread::scope(|scope| {
+            for machines in self.machines.chunks_mut(chunk) {
+                scope.spawn(move || {
+                    for machine in machines {
+                        run(machine, budget);
+                    }
+                });
+            }
+        });
+        self.machines
+            .iter()
+            .filter_map(|machine| machine.outcome)
+            .collect()
+    }
+
+    /// Runs one frame on a single machine, leaving the others where they
+    /// are.
+    pub fn run_frame_on(&mut self, index: usize, budget: usize) -> Outcome {
+        run(&mut self.machines[index], budget)
+    }
+
+    /// Every machine's display tiled into one image, `columns` machines
+    /// across and as many rows as it takes, in the order they were added.
+    /// Like a single display it has one byte per pixel, and is
+    /// `columns * WIDTH` pixels wide. Unused tiles stay dark. While any
+    /// machine is in high resolution every tile is 128x64, with the
+    /// low-resolution ones doubled up to fit.
+    pub fn tiled_display(&self, columns: usize) -> Vec<u8> {
+        let columns = columns.max(1);
+        let rows = self.machines.len().div_ceil(columns);
+        let (width, height) = self
+            .machines
+            .iter()
+            .map(|machine| display_size(machine.arch.display()))
+            .max()
+            .unwrap_or((WIDTH, HEIGHT));
+        let stride = columns * width;
+        let mut tiled = vec![0; stride * rows * height];
+        for (index, machine) in self.machines.iter().enumerate() {
+            let (left, top) = ((index % columns) * width, (index / columns) * height);
+            let display = fit_display(machine.arch.display(), width);
+            for (y, line) in display.chunks(width).enumerate() {
+                let start = (top + y) * stride + left;
+                tiled[start..start + width].copy_from_slice(line);
+            }
+        }
+        tiled
+    }
+}
+
+fn run(machine: &mut Machine, budget: usize) -> Outcome {
+    let outcome = machine
+        .arch
+        .run_frame(&machine.program, budget, |_, _, _| true);
+    machine.outcome = Some(outcome);
+    outcome
+}
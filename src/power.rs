@@ -0,0 +1,46 @@
+/// Frames between presents of a screen that hasn't changed, so a frontend
+/// that lost its picture (a resized window, say) gets it back.
+const REFRESH_FRAMES: u32 = 30;
+/// Frames run per host wakeup once the screen has gone still.
+const FRAMES_PER_WAKEUP: u32 = 4;
+
+/// Watches whether the screen is changing, for `--low-power`. A still
+/// screen is presented rarely, and its frames are run a few at a time so
+/// the host wakes up less often.
+pub struct LowPower {
+    display: Vec<u8>,
+    status: String,
+    still_frames: u32,
+}
+impl LowPower {
+    pub fn new() -> Self {
+        LowPower {
+            display: Vec::new(),
+            status: String::new(),
+            still_frames: 0,
+        }
+    }
+
+    /// Records the frame that just ran and says whether to present it.
+    pub fn frame(&mut self, display: &[u8], status: &str) -> bool {
+        if display == self.display && status == self.status {
+            self.still_frames += 1;
+            return self.still_frames.is_multiple_of(REFRESH_FRAMES);
+        }
+        self.display = display.to_vec();
+        self.status = status.to_string();
+        self.still_frames = 0;
+        true
+    }
+
+    /// Whether to sleep off the frames run so far. While the screen moves
+    /// that is after every frame, to keep input and animation smooth.
+    pub fn wake_now(&self) -> bool {
+        self.still_frames.is_multiple_of(FRAMES_PER_WAKEUP)
+    }
+}
+impl Default for LowPower {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,326 @@
+use crate::architecture::Architecture;
+use crate::breakpoint::OpcodePattern;
+use crate::cheat::{Poke, Target};
+use crate::emulator::Emulator;
+use crate::events::{Event, Subscriber};
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use std::net;
+use std::thread;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::time;
+use tokio_tungstenite::tungstenite::handshake::server::Response;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request as Handshake};
+use tokio_tungstenite::tungstenite::Message;
+
+mod gdb;
+
+const HELP: &str = "\
+regs                show registers, timers and the stack
+mem                 dump RAM
+pause               pause emulation
+continue            resume emulation
+step                run one frame while paused
+break-op PATTERN    pause after any instruction matching PATTERN (D___, 8F__, F000/D000)
+unbreak-op PATTERN  remove an opcode break
+breaks              list opcode breaks
+poke TARGET=VALUE   write a byte once to a register or RAM (V3=05, 2A0=03)
+freeze TARGET=VALUE write it again before every frame
+unfreeze TARGET     stop rewriting a register or address
+cheats              list frozen registers and addresses
+events              list breakpoint hits, resets and loads since the last call
+";
+
+/// Events kept for the `events` command. Older ones are dropped.
+const EVENT_LOG: usize = 64;
+
+/// How long a new client has to speak before it is taken for a person at
+/// the REPL, who waits for the prompt instead.
+const SNIFF: Duration = Duration::from_millis(250);
+
+/// What a client wants done, answered by the emulation loop.
+enum Query {
+    /// A line of the REPL.
+    Command(String),
+    /// Anything else, like a GDB packet, run against the emulator.
+    Run(Box<dyn FnOnce(&mut Emulator) -> String + Send>),
+}
+
+struct Request {
+    query: Query,
+    reply: oneshot::Sender<String>,
+}
+
+/// What every client task holds: the way to the emulation loop, and the
+/// addresses emulation stops at, for GDB's `c` to wait on.
+#[derive(Clone)]
+struct Link {
+    requests: mpsc::UnboundedSender<Request>,
+    stops: broadcast::Sender<u16>,
+}
+impl Link {
+    /// The answer to `query`, or `None` once the emulator has gone.
+    async fn ask(&self, query: Query) -> Option<String> {
+        let (reply, response) = oneshot::channel();
+        self.requests.send(Request { query, reply }).ok()?;
+        response.await.ok()
+    }
+
+    async fn run(
+        &self,
+        f: impl FnOnce(&mut Emulator) -> String + Send + 'static,
+    ) -> Option<String> {
+        self.ask(Query::Run(Box::new(f))).await
+    }
+}
+
+/// The debugger over TCP, in whichever of three protocols a client
+/// speaks: commands one per line (`nc localhost PORT` works), the same
+/// commands as WebSocket text messages, or GDB's remote serial protocol.
+///
+/// Clients are served by a tokio runtime on its own thread and never touch
+/// the machine directly: their commands are queued on a channel and
+/// answered by the emulation loop between frames, so a slow client can't
+/// stall emulation.
+pub struct RemoteDebugger {
+    requests: mpsc::UnboundedReceiver<Request>,
+    stops: broadcast::Sender<u16>,
+    /// What happened to the machine since a client last asked, leaving
+    /// out the display and sound events every frame has.
+    events: Vec<String>,
+}
+impl RemoteDebugger {
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = net::TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()?;
+        let (sender, requests) = mpsc::unbounded_channel();
+        let (stops, _) = broadcast::channel(16);
+        let link = Link {
+            requests: sender,
+            stops: stops.clone(),
+        };
+        thread::spawn(move || runtime.block_on(serve(listener, link)));
+        Ok(RemoteDebugger {
+            requests,
+            stops,
+            events: Vec::new(),
+        })
+    }
+
+    /// Answers the commands that arrived since the last call.
+    pub fn poll(&mut self, emulator: &mut Emulator) {
+        while let Ok(request) = self.requests.try_recv() {
+            let reply = match request.query {
+                Query::Command(command) if command == "events" => self.events.drain(..).collect(),
+                Query::Command(command) => execute(&command, emulator),
+                Query::Run(f) => f(emulator),
+            };
+            let _ = request.reply.send(reply);
+        }
+    }
+}
+impl Subscriber for RemoteDebugger {
+    fn notify(&mut self, event: &Event, _arch: &Architecture) -> io::Result<()> {
+        let line = match event {
+            Event::BreakpointHit {
+                pattern,
+                instruction,
+                pc,
+            } => {
+                let _ = self.stops.send(*pc);
+                format!("break on {}: {:04X} at {:03X}\n", pattern, instruction, pc)
+            }
+            Event::AddressReached { pc } => {
+                let _ = self.stops.send(*pc);
+                format!("break at {:03X}\n", pc)
+            }
+            Event::StateLoaded => "state loaded\n".to_string(),
+            Event::Reset => "reset\n".to_string(),
+            Event::RomReloaded => "ROM reloaded\n".to_string(),
+            Event::SpeedChanged => "speed changed\n".to_string(),
+            Event::RewindStarted => "rewinding\n".to_string(),
+            Event::RewindEnded => "rewind released\n".to_string(),
+            Event::DisplayDirty | Event::SoundStarted | Event::SoundStopped | Event::FrameEnded => {
+                return Ok(())
+            }
+        };
+        if self.events.len() == EVENT_LOG {
+            self.events.remove(0);
+        }
+        self.events.push(line);
+        Ok(())
+    }
+}
+
+fn execute(command: &str, emulator: &mut Emulator) -> String {
+    let mut out = Vec::new();
+    if let Some((name @ ("break-op" | "unbreak-op"), argument)) = command.split_once(' ') {
+        let Some(pattern) = OpcodePattern::parse(argument.trim()) else {
+            return format!("`{}` is not an opcode pattern\n", argument.trim());
+        };
+        let breaks = &mut emulator.opcode_breaks;
+        let present = breaks.contains(&pattern);
+        match name {
+            "break-op" if !present => breaks.push(pattern),
+            "unbreak-op" if present => breaks.retain(|p| *p != pattern),
+            "unbreak-op" => return format!("no break on {}\n", pattern),
+            _ => {}
+        }
+        return String::new();
+    }
+    if let Some((name @ ("poke" | "freeze"), argument)) = command.split_once(' ') {
+        let Some(poke) = Poke::parse(argument) else {
+            return format!("`{}` is not TARGET=VALUE\n", argument.trim());
+        };
+        poke.apply(&mut emulator.arch);
+        if name == "freeze" {
+            emulator.cheats.freeze(poke);
+        }
+        return String::new();
+    }
+    if let Some(("unfreeze", argument)) = command.split_once(' ') {
+        let Some(target) = Target::parse(argument) else {
+            return format!("`{}` is not a register or address\n", argument.trim());
+        };
+        if !emulator.cheats.unfreeze(target) {
+            return format!("{} is not frozen\n", target);
+        }
+        return String::new();
+    }
+    match command {
+        "regs" => {
+            let _ = emulator.arch.write_state(&mut out);
+        }
+        "mem" => {
+            let _ = emulator.arch.write_memory(&mut out);
+        }
+        "pause" => emulator.paused = true,
+        "continue" => emulator.paused = false,
+        "step" if emulator.paused => emulator.step_requested = true,
+        "step" => return "pause first\n".to_string(),
+        "breaks" => {
+            return emulator
+                .opcode_breaks
+                .iter()
+                .map(|pattern| format!("{}\n", pattern))
+                .collect()
+        }
+        "cheats" => {
+            return emulator
+                .cheats
+                .frozen()
+                .iter()
+                .map(|poke| format!("{}\n", poke))
+                .collect()
+        }
+        "help" => return HELP.to_string(),
+        "" => {}
+        _ => return format!("unknown command `{}`, try `help`\n", command),
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+async fn serve(listener: net::TcpListener, link: Link) {
+    let Ok(listener) = TcpListener::from_std(listener) else {
+        return;
+    };
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let link = link.clone();
+        tokio::spawn(async move {
+            let _ = client(stream, link).await;
+        });
+    }
+}
+
+/// Tells the protocols apart by the first byte: GDB opens with an ack or
+/// a packet, and a WebSocket with an HTTP `GET`.
+async fn client(stream: TcpStream, link: Link) -> io::Result<()> {
+    let mut first = [0];
+    match time::timeout(SNIFF, stream.peek(&mut first)).await {
+        Ok(Ok(0) | Err(_)) => Ok(()),
+        Ok(Ok(_)) if first[0] == b'G' => websocket(stream, link).await,
+        Ok(Ok(_)) if matches!(first[0], b'+' | b'$' | gdb::INTERRUPT) => {
+            gdb::serve(stream, link).await
+        }
+        _ => repl(stream, link).await,
+    }
+}
+
+async fn repl(stream: TcpStream, link: Link) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    writer
+        .write_all(b"chip-n-claw remote debugger, type `help`\n> ")
+        .await?;
+    while let Some(line) = lines.next_line().await? {
+        let command = line.trim().to_string();
+        let Some(response) = link.ask(Query::Command(command)).await else {
+            break;
+        };
+        writer.write_all(response.as_bytes()).await?;
+        writer.write_all(b"> ").await?;
+    }
+    Ok(())
+}
+
+/// The REPL's commands, one per text message, each answered by one.
+async fn websocket(stream: TcpStream, link: Link) -> io::Result<()> {
+    let mut socket = tokio_tungstenite::accept_hdr_async(stream, check_origin)
+        .await
+        .map_err(io::Error::other)?;
+    while let Some(message) = socket.next().await {
+        let command = match message.map_err(io::Error::other)? {
+            Message::Text(text) => text.trim().to_string(),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let Some(response) = link.ask(Query::Command(command)).await else {
+            break;
+        };
+        socket
+            .send(Message::text(response))
+            .await
+            .map_err(io::Error::other)?;
+    }
+    Ok(())
+}
+
+/// Turns away WebSockets opened by web pages from other sites, which any
+/// browser on this machine could otherwise point at the debugger. Tools
+/// send no `Origin`, and pages served from this machine are welcome;
+/// `file:` pages and sandboxed frames, whose origin is `null`, are not.
+// The signature is tungstenite's handshake callback.
+#[allow(clippy::result_large_err)]
+fn check_origin(request: &Handshake, response: Response) -> Result<Response, ErrorResponse> {
+    let origin = request
+        .headers()
+        .get("Origin")
+        .map(|origin| origin.to_str());
+    let local = |origin: &str| {
+        let host = origin.split_once("://").map_or(origin, |(_, host)| host);
+        let host = match host.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => host,
+            _ => host,
+        };
+        matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+    };
+    match origin {
+        None => Ok(response),
+        Some(Ok(origin)) if local(origin) => Ok(response),
+        Some(_) => {
+            let mut refusal = ErrorResponse::new(Some("only local pages may connect".into()));
+            *refusal.status_mut() = tokio_tungstenite::tungstenite::http::StatusCode::FORBIDDEN;
+            Err(refusal)
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use super::Link;
+use crate::emulator::Emulator;
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+/// The byte GDB sends on Ctrl-C, outside any packet.
+pub(super) const INTERRUPT: u8 = 0x03;
+
+/// The longest packet either side sends, as `qSupported` tells GDB.
+const PACKET_SIZE: usize = 0x1000;
+
+/// Stop replies: a breakpoint or step, and an interrupt.
+const TRAPPED: &str = "S05";
+const INTERRUPTED: &str = "S02";
+
+/// What came in from GDB.
+enum Input {
+    Packet(String),
+    Interrupt,
+}
+
+/// A stub for GDB's remote serial protocol. GDB has no CHIP-8 target, so
+/// this is for tools that speak the protocol themselves, or GDB with a
+/// target description of its own. `g` carries V0-VF a byte each, then I
+/// and PC two bytes each, big-endian like the machine, then SP, DT and ST
+/// a byte each; `p` numbers them in that order from 0. Memory is read and
+/// written with `m` and `M`, wrapping like I does, and `m` replies with
+/// as much as fits in a packet. `Z0` and `z0` set and clear address
+/// breakpoints, `s` runs one instruction without ticking the timers and
+/// `c` resumes until a breakpoint or Ctrl-C.
+pub(super) async fn serve(stream: TcpStream, link: Link) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut stops = link.stops.subscribe();
+    loop {
+        let reply = match read(&mut reader, &mut writer).await? {
+            None => return Ok(()),
+            Some(Input::Interrupt) => interrupt(&link).await,
+            Some(Input::Packet(packet)) => match packet.as_str() {
+                "c" => {
+                    // Only stops from after this resume count.
+                    while stops.try_recv().is_ok() {}
+                    link.run(|emulator| {
+                        emulator.paused = false;
+                        String::new()
+                    })
+                    .await;
+                    tokio::select! {
+                        stop = stops.recv() => match stop {
+                            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {
+                                Some(TRAPPED.to_string())
+                            }
+                            Err(broadcast::error::RecvError::Closed) => None,
+                        },
+                        byte = reader.read_u8() => match byte {
+                            Ok(INTERRUPT) => interrupt(&link).await,
+                            _ => None,
+                        },
+                    }
+                }
+                "D" => {
+                    let _ = link
+                        .run(|emulator| {
+                            emulator.paused = false;
+                            String::new()
+                        })
+                        .await;
+                    write_packet(&mut writer, "OK").await?;
+                    return Ok(());
+                }
+                "k" => return Ok(()),
+                _ => link.run(move |emulator| answer(&packet, emulator)).await,
+            },
+        };
+        let Some(reply) = reply else {
+            return Ok(());
+        };
+        write_packet(&mut writer, &reply).await?;
+    }
+}
+
+async fn interrupt(link: &Link) -> Option<String> {
+    link.run(|emulator| {
+        emulator.paused = true;
+        INTERRUPTED.to_string()
+    })
+    .await
+}
+
+/// The next packet, acknowledged, or an interrupt. `None` once GDB hangs
+/// up. Packets with a bad checksum are asked for again.
+async fn read(
+    reader: &mut BufReader<OwnedReadHalf>,
+    writer: &mut (impl AsyncWriteExt + Unpin),
+) -> io::Result<Option<Input>> {
+    loop {
+        let byte = match reader.read_u8().await {
+            Ok(byte) => byte,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match byte {
+            INTERRUPT => return Ok(Some(Input::Interrupt)),
+            b'$' => {}
+            // Acks, and anything between packets.
+            _ => continue,
+        }
+        let mut packet = Vec::new();
+        loop {
+            match reader.read_u8().await? {
+                b'#' => break,
+                byte => packet.push(byte),
+            }
+        }
+        let mut sum = [0; 2];
+        reader.read_exact(&mut sum).await?;
+        let sum = std::str::from_utf8(&sum)
+            .ok()
+            .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+        if sum != Some(checksum(&packet)) {
+            writer.write_all(b"-").await?;
+            continue;
+        }
+        writer.write_all(b"+").await?;
+        return Ok(Some(Input::Packet(
+            String::from_utf8_lossy(&packet).into_owned(),
+        )));
+    }
+}
+
+async fn write_packet(writer: &mut (impl AsyncWriteExt + Unpin), data: &str) -> io::Result<()> {
+    let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+    writer.write_all(packet.as_bytes()).await
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// The reply to every packet that doesn't wait on the machine. An empty
+/// reply tells GDB the packet isn't supported.
+fn answer(packet: &str, emulator: &mut Emulator) -> String {
+    let (command, arguments) = packet.split_at(packet.len().min(1));
+    let reply = match command {
+        "?" => Some(TRAPPED.to_string()),
+        "g" => Some(hex(&registers(emulator))),
+        "G" => unhex(arguments).and_then(|bytes| set_registers(emulator, &bytes)),
+        "p" => usize::from_str_radix(arguments, 16)
+            .ok()
+            .and_then(|n| register_span(n).map(|span| hex(&registers(emulator)[span]))),
+        "P" => arguments.split_once('=').and_then(|(n, value)| {
+            let span = register_span(usize::from_str_radix(n, 16).ok()?)?;
+            let value = unhex(value).filter(|value| value.len() == span.len())?;
+            let mut bytes = registers(emulator);
+            bytes[span].copy_from_slice(&value);
+            set_registers(emulator, &bytes)
+        }),
+        "m" => range(arguments).map(|(address, length)| {
+            let ram = emulator.arch.ram();
+            // Two hex digits a byte.
+            let bytes: Vec<u8> = (0..length.min(PACKET_SIZE / 2))
+                .map(|offset| ram[(address + offset) % ram.len()])
+                .collect();
+            hex(&bytes)
+        }),
+        "M" => arguments.split_once(':').and_then(|(span, data)| {
+            let (address, length) = range(span)?;
+            let bytes = unhex(data).filter(|bytes| bytes.len() == length)?;
+            for (offset, byte) in bytes.into_iter().enumerate() {
+                emulator.arch.poke((address + offset) as u16, byte);
+            }
+            Some("OK".to_string())
+        }),
+        "s" => {
+            emulator.step_instruction();
+            emulator.paused = true;
+            Some(TRAPPED.to_string())
+        }
+        "Z" | "z" => {
+            let mut fields = arguments.split(',');
+            let kind = fields.next();
+            let address = fields.next().and_then(|a| u16::from_str_radix(a, 16).ok());
+            match (kind, address) {
+                (Some("0"), Some(address)) => {
+                    let breaks = &mut emulator.address_breaks;
+                    breaks.retain(|&pc| pc != address);
+                    if command == "Z" {
+                        breaks.push(address);
+                    }
+                    Some("OK".to_string())
+                }
+                // Only software breakpoints.
+                _ => Some(String::new()),
+            }
+        }
+        "H" => Some("OK".to_string()),
+        "q" if arguments.starts_with("Supported") => Some(format!("PacketSize={:x}", PACKET_SIZE)),
+        "q" if arguments == "Attached" => Some("1".to_string()),
+        _ => Some(String::new()),
+    };
+    reply.unwrap_or_else(|| "E01".to_string())
+}
+
+/// V0-VF, I, PC, SP, DT and ST, as `g` sends them.
+fn registers(emulator: &Emulator) -> Vec<u8> {
+    let registers = emulator.arch.registers();
+    let mut bytes = registers.v.to_vec();
+    bytes.extend(registers.i.to_be_bytes());
+    bytes.extend(registers.pc.to_be_bytes());
+    bytes.extend([registers.sp as u8, registers.dt, registers.st]);
+    bytes
+}
+
+fn set_registers(emulator: &mut Emulator, bytes: &[u8]) -> Option<String> {
+    let [v @ .., i0, i1, pc0, pc1, sp, dt, st] = bytes else {
+        return None;
+    };
+    let mut registers = emulator.arch.registers();
+    registers.v = v.try_into().ok()?;
+    registers.i = u16::from_be_bytes([*i0, *i1]);
+    registers.pc = u16::from_be_bytes([*pc0, *pc1]);
+    registers.sp = usize::from(*sp);
+    registers.dt = *dt;
+    registers.st = *st;
+    emulator.arch.set_registers(registers);
+    Some("OK".to_string())
+}
+
+/// Where register `n` is in the `g` bytes.
+fn register_span(n: usize) -> Option<std::ops::Range<usize>> {
+    match n {
+        0..=15 => Some(n..n + 1),
+        16 | 17 => Some(16 + 2 * (n - 16)..18 + 2 * (n - 16)),
+        18..=20 => Some(n + 2..n + 3),
+        _ => None,
+    }
+}
+
+/// `ADDRESS,LENGTH`, both in hex.
+fn range(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    Some((address, usize::from_str_radix(length, 16).ok()?))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(text.get(at..at + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::architecture::{Architecture, Registers};
+
+    fn emulator() -> Emulator {
+        // LD V3, 2A; LD I, 345; JP 204
+        let rom = [0x63, 0x2A, 0xA3, 0x45, 0x12, 0x04];
+        let mut arch = Architecture::with_seed(0);
+        arch.load_rom(&rom);
+        Emulator::new(arch, rom.to_vec(), "test.ch8")
+    }
+
+    #[test]
+    fn registers_and_memory_read_and_write_in_hex() {
+        let mut emulator = emulator();
+        emulator.arch.set_registers(Registers {
+            dt: 5,
+            ..emulator.arch.registers()
+        });
+        assert_eq!(answer("s", &mut emulator), "S05");
+        assert_eq!(answer("s", &mut emulator), "S05");
+        let registers = answer("g", &mut emulator);
+        assert_eq!(&registers[6..8], "2a");
+        assert_eq!(&registers[32..], "03450204000500");
+        assert_eq!(answer("p10", &mut emulator), "0345");
+        assert_eq!(answer("P3=7f", &mut emulator), "OK");
+        assert_eq!(emulator.arch.registers().v[3], 0x7F);
+        assert_eq!(answer("m200,4", &mut emulator), "632aa345");
+        assert_eq!(answer("MFFF,2:abcd", &mut emulator), "OK");
+        assert_eq!(answer("mFFF,2", &mut emulator), "abcd");
+        assert_eq!(emulator.arch.ram()[0], 0xCD);
+        assert_eq!(answer("m200", &mut emulator), "E01");
+        assert_eq!(answer("m0,ffffffff", &mut emulator).len(), PACKET_SIZE);
+        assert_eq!(answer("vMustReplyEmpty", &mut emulator), "");
+    }
+
+    #[test]
+    fn address_breakpoints_stop_before_their_instruction() {
+        let mut emulator = emulator();
+        assert_eq!(answer("Z0,202,2", &mut emulator), "OK");
+        emulator.run_frame(0);
+        assert!(emulator.paused);
+        assert_eq!(emulator.arch.pc(), 0x202);
+        assert_eq!(answer("z0,202,2", &mut emulator), "OK");
+        assert!(emulator.address_breaks.is_empty());
+    }
+
+    #[test]
+    fn packets_are_framed_with_a_checksum() {
+        let mut packet = Vec::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(write_packet(&mut packet, "OK")).unwrap();
+        assert_eq!(packet, b"$OK#9a");
+    }
+}
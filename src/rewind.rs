@@ -0,0 +1,66 @@
+use crate::architecture::Architecture;
+use crate::events::{Event, Subscriber};
+use std::collections::VecDeque;
+use std::io;
+
+/// Memory the snapshots may take together. A CHIP-8 state is about 12KB
+/// and an XO-CHIP one, with its 64KB of RAM, about 74KB, so this holds
+/// some 45 seconds of the former and 7 of the latter, whatever `--rewind`
+/// asks for.
+const BUDGET_BYTES: usize = 32 << 20;
+
+/// The last few seconds of the machine, for `--rewind`: a save state taken
+/// before every frame, the oldest dropped once there are `capacity` of
+/// them or they outgrow the memory budget.
+pub struct Rewind {
+    states: VecDeque<Vec<u8>>,
+    capacity: usize,
+    bytes: usize,
+}
+impl Rewind {
+    pub fn new(frames: usize) -> Self {
+        Rewind {
+            states: VecDeque::new(),
+            capacity: frames,
+            bytes: 0,
+        }
+    }
+
+    /// Remembers the machine as it is before a frame runs.
+    pub fn push(&mut self, arch: &Architecture) {
+        let state = arch.save_state();
+        self.bytes += state.len();
+        self.states.push_back(state);
+        while self.states.len() > self.capacity || self.bytes > BUDGET_BYTES {
+            let Some(oldest) = self.states.pop_front() else {
+                break;
+            };
+            self.bytes -= oldest.len();
+        }
+    }
+
+    /// The machine `frames` frames ago, or as far back as there is, and
+    /// forgets everything after it. None once nothing is left.
+    pub fn back(&mut self, frames: usize) -> Option<Architecture> {
+        let mut state = None;
+        for _ in 0..frames {
+            let Some(newest) = self.states.pop_back() else {
+                break;
+            };
+            self.bytes -= newest.len();
+            state = Some(newest);
+        }
+        // Only states this build wrote are kept, so they always load.
+        state.and_then(|state| Architecture::load_state(&state).ok())
+    }
+}
+impl Subscriber for Rewind {
+    /// Forgets everything once another ROM is loaded.
+    fn notify(&mut self, event: &Event, _arch: &Architecture) -> io::Result<()> {
+        if *event == Event::RomReloaded {
+            self.states.clear();
+            self.bytes = 0;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+use crate::architecture::{display_size, Architecture, Dxy0, Platform, Quirks};
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+
+/// The platforms the matrix has a column for, with their headings.
+const PLATFORMS: [(&str, Platform); 5] = [
+    ("vip", Platform::Vip),
+    ("chip48", Platform::Chip48),
+    ("schip", Platform::Schip),
+    ("xochip", Platform::XoChip),
+    ("modern", Platform::Modern),
+];
+
+/// Which platforms have the opcodes a check runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Extension {
+    None,
+    SuperChip,
+    XoChip,
+}
+
+/// A micro-program, how many instructions of it to run, and what the
+/// machine should look like afterwards under the platform's quirks.
+struct Check {
+    name: &'static str,
+    needs: Extension,
+    /// Instruction words, with any sprite data after them.
+    program: &'static [u16],
+    steps: usize,
+    /// A key held down before the program starts.
+    pressed: Option<u8>,
+    expect: fn(&Architecture, Quirks) -> bool,
+}
+
+fn check(
+    name: &'static str,
+    program: &'static [u16],
+    steps: usize,
+    expect: fn(&Architecture, Quirks) -> bool,
+) -> Check {
+    Check {
+        name,
+        needs: Extension::None,
+        program,
+        steps,
+        pressed: None,
+        expect,
+    }
+}
+
+fn schip(
+    name: &'static str,
+    program: &'static [u16],
+    steps: usize,
+    expect: fn(&Architecture, Quirks) -> bool,
+) -> Check {
+    Check {
+        needs: Extension::SuperChip,
+        ..check(name, program, steps, expect)
+    }
+}
+
+fn xochip(
+    name: &'static str,
+    program: &'static [u16],
+    steps: usize,
+    expect: fn(&Architecture, Quirks) -> bool,
+) -> Check {
+    Check {
+        needs: Extension::XoChip,
+        ..check(name, program, steps, expect)
+    }
+}
+
+fn v(arch: &Architecture, register: usize) -> u8 {
+    arch.registers().v[register]
+}
+
+fn lit(arch: &Architecture, x: usize, y: usize) -> bool {
+    let display = arch.display();
+    let (width, _) = display_size(display);
+    display[y * width + x] != 0
+}
+
+fn blank(arch: &Architecture) -> bool {
+    arch.display().iter().all(|&pixel| pixel == 0)
+}
+
+fn ram(arch: &Architecture, address: u16, length: usize) -> &[u8] {
+    let address = usize::from(address);
+    &arch.ram()[address..address + length]
+}
+
+/// One check per opcode, plus one for each quirk, whose expectations
+/// follow the platform's quirks.
+fn checks() -> Vec<Check> {
+    vec![
+        check("00E0 CLS", &[0xA200, 0xD001, 0x00E0], 3, |arch, _| {
+            blank(arch)
+        }),
+        check(
+            "00EE RET",
+            &[0x2206, 0x6101, 0x1204, 0x00EE],
+            4,
+            |arch, _| {
+                let registers = arch.registers();
+                registers.v[1] == 1 && registers.sp == 0 && registers.pc == 0x204
+            },
+        ),
+        check("1nnn JP", &[0x1204, 0x6101, 0x6202], 2, |arch, _| {
+            v(arch, 1) == 0 && v(arch, 2) == 2
+        }),
+        check("2nnn CALL", &[0x2206], 1, |arch, _| {
+            let registers = arch.registers();
+            registers.pc == 0x206 && registers.sp == 1 && registers.stack[0] == 0x202
+        }),
+        check(
+            "3xkk SE",
+            &[0x6005, 0x3005, 0x6101, 0x6202],
+            3,
+            |arch, _| v(arch, 1) == 0 && v(arch, 2) == 2,
+        ),
+        check(
+            "4xkk SNE",
+            &[0x6005, 0x4006, 0x6101, 0x6202],
+            3,
+            |arch, _| v(arch, 1) == 0 && v(arch, 2) == 2,
+        ),
+        check(
+            "5xy0 SE",
+            &[0x6005, 0x6105, 0x5010, 0x6201, 0x6302],
+            4,
+            |arch, _| v(arch, 2) == 0 && v(arch, 3) == 2,
+        ),
+        check("6xkk LD", &[0x6A42], 1, |arch, _| v(arch, 0xA) == 0x42),
+        check("7xkk ADD", &[0x60FF, 0x7002], 2, |arch, _| {
+            v(arch, 0) == 0x01 && v(arch, 0xF) == 0
+        }),
+        check("8xy0 LD", &[0x6107, 0x8010], 2, |arch, _| v(arch, 0) == 7),
+        check(
+            "8xy1 OR",
+            &[0x600C, 0x610A, 0x6F05, 0x8011],
+            4,
+            |arch, quirks| v(arch, 0) == 0x0E && v(arch, 0xF) == vf_after_logic(quirks),
+        ),
+        check(
+            "8xy2 AND",
+            &[0x600C, 0x610A, 0x6F05, 0x8012],
+            4,
+            |arch, quirks| v(arch, 0) == 0x08 && v(arch, 0xF) == vf_after_logic(quirks),
+        ),
+        check(
+            "8xy3 XOR",
+            &[0x600C, 0x610A, 0x6F05, 0x8013],
+            4,
+            |arch, quirks| v(arch, 0) == 0x06 && v(arch, 0xF) == vf_after_logic(quirks),
+        ),
+        check("8xy4 ADD", &[0x60FF, 0x6102, 0x8014], 3, |arch, _| {
+            v(arch, 0) == 0x01 && v(arch, 0xF) == 1
+        }),
+        check("8xy5 SUB", &[0x6005, 0x6107, 0x8015], 3, |arch, _| {
+            v(arch, 0) == 0xFE && v(arch, 0xF) == 0
+        }),
+        check(
+            "8xy6 SHR",
+            &[0x6004, 0x6103, 0x8016],
+            3,
+            |arch, quirks| match quirks.shift_uses_vx {
+                true => v(arch, 0) == 0x02 && v(arch, 0xF) == 0,
+                false => v(arch, 0) == 0x01 && v(arch, 0xF) == 1,
+            },
+        ),
+        check("8xy7 SUBN", &[0x6005, 0x6107, 0x8017], 3, |arch, _| {
+            v(arch, 0) == 0x02 && v(arch, 0xF) == 1
+        }),
+        check(
+            "8xyE SHL",
+            &[0x6081, 0x6140, 0x801E],
+            3,
+            |arch, quirks| match quirks.shift_uses_vx {
+                true => v(arch, 0) == 0x02 && v(arch, 0xF) == 1,
+                false => v(arch, 0) == 0x80 && v(arch, 0xF) == 0,
+            },
+        ),
+        check(
+            "9xy0 SNE",
+            &[0x6005, 0x6106, 0x9010, 0x6201, 0x6302],
+            4,
+            |arch, _| v(arch, 2) == 0 && v(arch, 3) == 2,
+        ),
+        check("Annn LD I", &[0xA123], 1, |arch, _| {
+            arch.registers().i == 0x123
+        }),
+        check(
+            "Bnnn JP V0",
+            &[0x6004, 0x6206, 0xB210],
+            3,
+            |arch, quirks| {
+                let expected = if quirks.jump_uses_vx { 0x216 } else { 0x214 };
+                arch.pc() == expected
+            },
+        ),
+        check("Cxkk RND", &[0x60FF, 0xC000], 2, |arch, _| v(arch, 0) == 0),
+        check(
+            "Dxyn DRW",
+            &[0xA206, 0xD001, 0x1204, 0xF000],
+            2,
+            |arch, _| (0..4).all(|x| lit(arch, x, 0)) && !lit(arch, 4, 0) && v(arch, 0xF) == 0,
+        ),
+        check(
+            "Dxyn DRW collision",
+            &[0xA208, 0xD001, 0xD001, 0x1206, 0xF000],
+            3,
+            |arch, _| blank(arch) && v(arch, 0xF) == 1,
+        ),
+        check(
+            "Dxy0 DRW",
+            &[0xA206, 0xD000, 0x1204, 0xFFFF, 0xFFFF],
+            2,
+            |arch, quirks| match quirks.dxy0 {
+                Dxy0::Nothing => blank(arch),
+                Dxy0::Tall => lit(arch, 7, 3) && !lit(arch, 8, 0),
+                Dxy0::Large => lit(arch, 15, 1) && !lit(arch, 0, 2),
+            },
+        ),
+        check(
+            "Dxyn wraps sprites",
+            &[0x603E, 0xA208, 0xD011, 0x1206, 0xFF00],
+            3,
+            |arch, quirks| lit(arch, 63, 0) && lit(arch, 0, 0) == quirks.wrap_sprites,
+        ),
+        Check {
+            pressed: Some(5),
+            ..check(
+                "Ex9E SKP",
+                &[0x6005, 0xE09E, 0x6101, 0x6202],
+                3,
+                |arch, _| v(arch, 1) == 0 && v(arch, 2) == 2,
+            )
+        },
+        check(
+            "ExA1 SKNP",
+            &[0x6005, 0xE0A1, 0x6101, 0x6202],
+            3,
+            |arch, _| v(arch, 1) == 0 && v(arch, 2) == 2,
+        ),
+        check("Fx07 LD Vx, DT", &[0x6009, 0xF015, 0xF107], 3, |arch, _| {
+            v(arch, 1) == 9
+        }),
+        check("Fx0A LD Vx, K", &[0xF00A], 1, |arch, _| {
+            arch.waiting_for_key()
+        }),
+        check("Fx15 LD DT", &[0x6009, 0xF015], 2, |arch, _| {
+            arch.registers().dt == 9
+        }),
+        check("Fx18 LD ST", &[0x6009, 0xF018], 2, |arch, _| {
+            arch.registers().st == 9
+        }),
+        check("Fx1E ADD I", &[0xA0FF, 0x6002, 0xF01E], 3, |arch, _| {
+            arch.registers().i == 0x101
+        }),
+        check("Fx29 LD F", &[0x6007, 0xF029], 2, |arch, _| {
+            ram(arch, arch.registers().i, 5) == [0xF0, 0x10, 0x20, 0x40, 0x40]
+        }),
+        check("Fx33 LD B", &[0x607B, 0xA300, 0xF033], 3, |arch, _| {
+            ram(arch, 0x300, 3) == [1, 2, 3]
+        }),
+        check(
+            "Fx55 LD [I]",
+            &[0x6001, 0x6102, 0x6203, 0xA300, 0xF255],
+            5,
+            |arch, quirks| {
+                let i = if quirks.load_store_increments_i {
+                    0x303
+                } else {
+                    0x300
+                };
+                ram(arch, 0x300, 3) == [1, 2, 3] && arch.registers().i == i
+            },
+        ),
+        check(
+            "Fx65 LD Vx, [I]",
+            &[0xA206, 0xF265, 0x1204, 0x0102, 0x0300],
+            2,
+            |arch, quirks| {
+                let i = if quirks.load_store_increments_i {
+                    0x209
+                } else {
+                    0x206
+                };
+                arch.registers().v[..3] == [1, 2, 3] && arch.registers().i == i
+            },
+        ),
+        schip(
+            "00Cn SCD",
+            &[0xA206, 0xD001, 0x00C2, 0x8000],
+            3,
+            |arch, _| !lit(arch, 0, 0) && lit(arch, 0, 2),
+        ),
+        schip(
+            "00FB SCR",
+            &[0xA206, 0xD001, 0x00FB, 0x8000],
+            3,
+            |arch, _| !lit(arch, 0, 0) && lit(arch, 4, 0),
+        ),
+        schip(
+            "00FC SCL",
+            &[0x6004, 0xA208, 0xD011, 0x00FC, 0x8000],
+            4,
+            |arch, _| !lit(arch, 4, 0) && lit(arch, 0, 0),
+        ),
+        schip("00FD EXIT", &[0x00FD], 1, |arch, _| arch.halted()),
+        schip("00FE LOW", &[0x00FF, 0x00FE], 2, |arch, _| {
+            display_size(arch.display()) == (64, 32)
+        }),
+        schip("00FF HIGH", &[0x00FF], 1, |arch, _| {
+            display_size(arch.display()) == (128, 64)
+        }),
+        schip("Fx30 LD HF", &[0x6007, 0xF030], 2, |arch, _| {
+            let glyph = ram(arch, arch.registers().i, 10);
+            arch.registers().i >= 80 && glyph.iter().any(|&row| row != 0)
+        }),
+        schip(
+            "Fx75/Fx85 RPL",
+            &[0x6005, 0x6106, 0xF175, 0x6000, 0x6100, 0xF185],
+            6,
+            |arch, _| v(arch, 0) == 5 && v(arch, 1) == 6,
+        ),
+        xochip(
+            "00Dn SCU",
+            &[0x6102, 0xA208, 0xD011, 0x00D2, 0x8000],
+            4,
+            |arch, _| !lit(arch, 0, 2) && lit(arch, 0, 0),
+        ),
+        xochip(
+            "5xy2 LD [I], Vx-Vy",
+            &[0x6001, 0x6102, 0x6203, 0xA300, 0x5022],
+            5,
+            |arch, _| ram(arch, 0x300, 3) == [1, 2, 3] && arch.registers().i == 0x300,
+        ),
+        xochip(
+            "5xy3 LD Vx-Vy, [I]",
+            &[0xA206, 0x5023, 0x1204, 0x0102, 0x0300],
+            2,
+            |arch, _| arch.registers().v[..3] == [1, 2, 3] && arch.registers().i == 0x206,
+        ),
+        xochip("F000 LD I, LONG", &[0xF000, 0x1234], 1, |arch, _| {
+            arch.registers().i == 0x1234
+        }),
+        xochip(
+            "Fx01 PLANE",
+            &[0xF201, 0xA206, 0xD001, 0x8000],
+            3,
+            |arch, _| arch.display()[0] == 2,
+        ),
+        xochip("F002 AUDIO", &[0xA300, 0xF002], 2, |arch, _| {
+            arch.audio_pattern().is_some()
+        }),
+        xochip(
+            "Fx3A PITCH",
+            &[0x6050, 0xF03A, 0xA300, 0xF002],
+            4,
+            |arch, _| {
+                arch.audio_pattern()
+                    .is_some_and(|pattern| pattern.pitch == 0x50)
+            },
+        ),
+    ]
+}
+
+/// VF after 8xy1/8xy2/8xy3 when it held 5 beforehand.
+fn vf_after_logic(quirks: Quirks) -> u8 {
+    if quirks.vf_reset {
+        0
+    } else {
+        5
+    }
+}
+
+/// How a check went on one platform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Verdict {
+    Pass,
+    Fail,
+    /// The core panicked.
+    Crash,
+    /// The platform doesn't have the opcode.
+    Skipped,
+}
+impl Verdict {
+    fn text(self) -> &'static str {
+        match self {
+            Verdict::Pass => "ok",
+            Verdict::Fail => "FAIL",
+            Verdict::Crash => "CRASH",
+            Verdict::Skipped => "-",
+        }
+    }
+}
+
+fn run(check: &Check, quirks: Quirks) -> Verdict {
+    let supported = match check.needs {
+        Extension::None => true,
+        Extension::SuperChip => quirks.superchip,
+        Extension::XoChip => quirks.xochip,
+    };
+    if !supported {
+        return Verdict::Skipped;
+    }
+    let rom: Vec<u8> = check.program.iter().flat_map(|w| w.to_be_bytes()).collect();
+    let mut arch = Architecture::with_seed(0);
+    arch.set_quirks(quirks);
+    arch.load_rom(&rom);
+    if let Some(key) = check.pressed {
+        arch.set_key(key, true);
+    }
+    let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+        for _ in 0..check.steps {
+            arch.execute();
+        }
+    }));
+    match ran {
+        Err(_) => Verdict::Crash,
+        Ok(()) if (check.expect)(&arch, quirks) => Verdict::Pass,
+        Ok(()) => Verdict::Fail,
+    }
+}
+
+/// Runs every check on every platform and writes the matrix, one row per
+/// check. Returns whether everything that could run passed.
+pub fn write_matrix(out: &mut impl Write) -> io::Result<bool> {
+    let checks = checks();
+    let name_width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+    write!(out, "{:name_width$}", "")?;
+    for (heading, _) in PLATFORMS {
+        write!(out, "  {:>6}", heading)?;
+    }
+    writeln!(out)?;
+    // The core's panics are expected here and reported as CRASH, so keep
+    // their messages off the terminal.
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut passed = true;
+    for check in &checks {
+        write!(out, "{:name_width$}", check.name)?;
+        for (_, platform) in PLATFORMS {
+            let verdict = run(check, Quirks::preset(platform));
+            passed &= matches!(verdict, Verdict::Pass | Verdict::Skipped);
+            write!(out, "  {:>6}", verdict.text())?;
+        }
+        writeln!(out)?;
+    }
+    panic::set_hook(hook);
+    Ok(passed)
+}
@@ -0,0 +1,97 @@
+use crate::architecture::{Architecture, STACK_SIZE};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Figures about the whole run, printed on exit by `--summary` so they can
+/// be pasted into a performance bug report.
+pub struct SessionStats {
+    started: Instant,
+    frames: u64,
+    instructions: u64,
+    paused_since: Option<Instant>,
+    paused: Duration,
+    /// Each unknown opcode the machine stopped on, as word and address, in
+    /// the order they came up. Resets and reloads can run into more.
+    unknown_opcodes: Vec<(u16, u16)>,
+}
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats {
+            started: Instant::now(),
+            frames: 0,
+            instructions: 0,
+            paused_since: None,
+            paused: Duration::ZERO,
+            unknown_opcodes: Vec::new(),
+        }
+    }
+
+    /// Records an emulated frame and how many instructions it ran.
+    pub fn ran(&mut self, instructions: usize) {
+        self.frames += 1;
+        self.instructions += instructions as u64;
+    }
+
+    /// Records an opcode the core couldn't decode, `word` at `pc`.
+    pub fn unknown_opcode(&mut self, pc: u16, word: u16) {
+        self.unknown_opcodes.push((word, pc));
+    }
+
+    /// Called once per pass of the run loop, to time how long emulation
+    /// was paused.
+    pub fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_since) {
+            (true, None) => self.paused_since = Some(Instant::now()),
+            (false, Some(since)) => {
+                self.paused += since.elapsed();
+                self.paused_since = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn write_summary(&self, arch: &Architecture, out: &mut impl Write) -> io::Result<()> {
+        let elapsed = self.started.elapsed();
+        let paused = self.paused + self.paused_since.map_or(Duration::ZERO, |s| s.elapsed());
+        let running = elapsed.saturating_sub(paused).as_secs_f64();
+        writeln!(
+            out,
+            "{} frames in {:.1}s ({:.1}s paused)",
+            self.frames,
+            elapsed.as_secs_f64(),
+            paused.as_secs_f64()
+        )?;
+        writeln!(
+            out,
+            "{} instructions, {:.0} per second",
+            self.instructions,
+            self.instructions as f64 / running.max(f64::EPSILON)
+        )?;
+        if self.unknown_opcodes.is_empty() {
+            writeln!(out, "unknown opcodes: none")?;
+        } else {
+            let found: Vec<String> = self
+                .unknown_opcodes
+                .iter()
+                .map(|(word, pc)| format!("{:04X} at {:03X}", word, pc))
+                .collect();
+            writeln!(
+                out,
+                "unknown opcodes: {} ({})",
+                found.len(),
+                found.join(", ")
+            )?;
+        }
+        writeln!(
+            out,
+            "peak stack depth: {} of {}",
+            arch.peak_stack_depth(),
+            STACK_SIZE
+        )
+    }
+}
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,35 @@
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Turns the signals that ask a program to end (Ctrl-C outside the
+/// terminal frontend, `kill`, a closed terminal) into a request the run
+/// loop picks up between frames. It then leaves the way quitting does,
+/// so the terminal is restored, recordings are finished and the autosave
+/// is written instead of being cut off halfway.
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+}
+impl Shutdown {
+    /// Catches SIGINT, SIGTERM and SIGQUIT, and SIGHUP on Unix. A second
+    /// signal ends the process at once, in case shutting down hangs.
+    pub fn install() -> io::Result<Self> {
+        let requested = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        let hangup = [signal_hook::consts::SIGHUP];
+        #[cfg(not(unix))]
+        let hangup = [];
+        for &signal in TERM_SIGNALS.iter().chain(&hangup) {
+            // Registered first so it sees the flag before this signal sets it.
+            flag::register_conditional_shutdown(signal, 1, Arc::clone(&requested))?;
+            flag::register(signal, Arc::clone(&requested))?;
+        }
+        Ok(Shutdown { requested })
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,237 @@
+use crate::architecture::{Dxy0, Platform, Quirks};
+use crate::frontend::Palette;
+use crate::hotkeys::{self, Action};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    platform: Option<String>,
+    speed: Option<usize>,
+    scale: Option<u32>,
+    volume: Option<u8>,
+    #[serde(default)]
+    quirks: QuirksTable,
+    palette: Option<PaletteTable>,
+    #[serde(default)]
+    keys: BTreeMap<String, String>,
+    #[serde(default)]
+    hotkeys: BTreeMap<String, HostKeys>,
+}
+
+/// An action's key, or several.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HostKeys {
+    One(String),
+    Several(Vec<String>),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuirksTable {
+    shift_uses_vx: Option<bool>,
+    vf_reset: Option<bool>,
+    load_store_increments_i: Option<bool>,
+    jump_uses_vx: Option<bool>,
+    wrap_sprites: Option<bool>,
+    superchip: Option<bool>,
+    dxy0: Option<String>,
+    xochip: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteTable {
+    background: Option<String>,
+    foreground: Option<String>,
+    second_plane: Option<String>,
+    both_planes: Option<String>,
+}
+
+/// Settings for one game, read from a TOML file next to the ROM
+/// (`game.ch8.toml` for `game.ch8`). Every entry is optional:
+///
+/// ```toml
+/// platform = "vip"   # quirk preset, as with --platform
+/// speed = 15         # instructions per frame
+/// scale = 8          # window pixels per CHIP-8 pixel
+/// volume = 50        # percent, for audio recordings
+///
+/// [quirks]           # on top of the preset
+/// vf_reset = false
+/// load_store_increments_i = true
+/// dxy0 = "8x16"      # or "nothing", "16x16"
+///
+/// [palette]
+/// foreground = "#33FF66"
+/// background = "#002200"
+/// second_plane = "#22AA44"  # XO-CHIP's second plane
+/// both_planes = "#AAFFBB"   # where the two overlap
+///
+/// [keys]             # CHIP-8 key = host key
+/// 5 = "Up"
+/// 8 = "Down"
+///
+/// [hotkeys]          # action = host key, or several
+/// pause = "Space"
+/// quit = ["Escape", "Q"]
+/// ```
+pub struct Sidecar {
+    platform: Option<Platform>,
+    shift_uses_vx: Option<bool>,
+    vf_reset: Option<bool>,
+    load_store_increments_i: Option<bool>,
+    jump_uses_vx: Option<bool>,
+    wrap_sprites: Option<bool>,
+    superchip: Option<bool>,
+    dxy0: Option<Dxy0>,
+    xochip: Option<bool>,
+    pub speed: Option<usize>,
+    pub scale: Option<u32>,
+    pub volume: Option<u8>,
+    pub palette: Option<Palette>,
+    pub keys: Vec<(u8, String)>,
+    pub hotkeys: Vec<(Action, String)>,
+}
+impl Sidecar {
+    pub fn path(rom_path: &str) -> String {
+        format!("{}.toml", rom_path)
+    }
+
+    /// Reads the sidecar of `rom_path`, or returns `None` if there isn't one.
+    pub fn load(rom_path: &str) -> io::Result<Option<Self>> {
+        Sidecar::read(Path::new(&Sidecar::path(rom_path)))
+    }
+
+    /// Reads settings from `path`, or returns `None` if there is no file.
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Sidecar::parse(&text).map(Some)
+    }
+
+    fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let file: File = toml::from_str(text).map_err(|e| invalid(e.message().to_string()))?;
+        let platform = match file.platform {
+            Some(name) => Some(
+                Platform::from_name(&name)
+                    .ok_or_else(|| invalid(format!("unknown platform `{}`", name)))?,
+            ),
+            None => None,
+        };
+        let dxy0 = match file.quirks.dxy0 {
+            Some(name) => Some(Dxy0::from_name(&name).ok_or_else(|| {
+                invalid(format!(
+                    "unknown dxy0 `{}`, expected nothing, 8x16 or 16x16",
+                    name
+                ))
+            })?),
+            None => None,
+        };
+        if file.speed == Some(0) {
+            return Err(invalid("speed must be at least 1".to_string()));
+        }
+        if file.scale == Some(0) {
+            return Err(invalid("scale must be at least 1".to_string()));
+        }
+        if file.volume.is_some_and(|volume| volume > 100) {
+            return Err(invalid("volume must be 0 to 100".to_string()));
+        }
+        let palette = match file.palette {
+            Some(table) => {
+                let color = |text: Option<String>, default| match text {
+                    Some(text) => Palette::parse_color(&text)
+                        .ok_or_else(|| invalid(format!("`{}` is not a #RRGGBB color", text))),
+                    None => Ok(default),
+                };
+                let [background, foreground, second_plane, both_planes] = Palette::default().colors;
+                Some(Palette {
+                    colors: [
+                        color(table.background, background)?,
+                        color(table.foreground, foreground)?,
+                        color(table.second_plane, second_plane)?,
+                        color(table.both_planes, both_planes)?,
+                    ],
+                })
+            }
+            None => None,
+        };
+        let mut keys = Vec::new();
+        for (key, host_key) in file.keys {
+            let key = u8::from_str_radix(&key, 16)
+                .ok()
+                .filter(|&key| key <= 0xF)
+                .ok_or_else(|| invalid(format!("`{}` is not a CHIP-8 key (0-F)", key)))?;
+            keys.push((key, hotkeys::key_name(&host_key)));
+        }
+        let mut bindings = Vec::new();
+        for (action, host_keys) in file.hotkeys {
+            let action = Action::from_name(&action)
+                .ok_or_else(|| invalid(format!("unknown action `{}`", action)))?;
+            let host_keys = match host_keys {
+                HostKeys::One(key) => vec![key],
+                HostKeys::Several(keys) => keys,
+            };
+            for key in host_keys {
+                bindings.push((action, hotkeys::key_name(&key)));
+            }
+        }
+        Ok(Sidecar {
+            platform,
+            shift_uses_vx: file.quirks.shift_uses_vx,
+            vf_reset: file.quirks.vf_reset,
+            load_store_increments_i: file.quirks.load_store_increments_i,
+            jump_uses_vx: file.quirks.jump_uses_vx,
+            wrap_sprites: file.quirks.wrap_sprites,
+            superchip: file.quirks.superchip,
+            dxy0,
+            xochip: file.quirks.xochip,
+            speed: file.speed,
+            scale: file.scale,
+            volume: file.volume,
+            palette,
+            keys,
+            hotkeys: bindings,
+        })
+    }
+
+    /// The game's quirks: its preset if it names one, otherwise `base`,
+    /// with any individual quirks it sets on top.
+    pub fn quirks(&self, base: Quirks) -> Quirks {
+        let mut quirks = self.platform.map_or(base, Quirks::preset);
+        if let Some(shift_uses_vx) = self.shift_uses_vx {
+            quirks.shift_uses_vx = shift_uses_vx;
+        }
+        if let Some(vf_reset) = self.vf_reset {
+            quirks.vf_reset = vf_reset;
+        }
+        if let Some(increments) = self.load_store_increments_i {
+            quirks.load_store_increments_i = increments;
+        }
+        if let Some(jump_uses_vx) = self.jump_uses_vx {
+            quirks.jump_uses_vx = jump_uses_vx;
+        }
+        if let Some(wrap_sprites) = self.wrap_sprites {
+            quirks.wrap_sprites = wrap_sprites;
+        }
+        if let Some(superchip) = self.superchip {
+            quirks.superchip = superchip;
+        }
+        if let Some(dxy0) = self.dxy0 {
+            quirks.dxy0 = dxy0;
+        }
+        if let Some(xochip) = self.xochip {
+            quirks.xochip = xochip;
+        }
+        quirks
+    }
+}
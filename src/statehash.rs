@@ -0,0 +1,71 @@
+use crate::architecture::Architecture;
+use crate::events::{Event, Subscriber};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// FNV-1a, picked over std's hasher because its output is fixed forever,
+/// so hash streams stay comparable between builds and Rust versions.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    fnv1a(FNV_OFFSET, bytes)
+}
+
+/// Hashes what is on the screen, to compare a test ROM's result against a
+/// known-good one. A high-resolution screen never hashes the same as a
+/// low-resolution one.
+pub fn hash_display(arch: &Architecture) -> u64 {
+    let (width, height) = arch.display_size();
+    let hash = fnv1a(FNV_OFFSET, &[width as u8, height as u8]);
+    fnv1a(hash, arch.display())
+}
+
+/// Hashes the registers, RAM and display, in that order, with multi-byte
+/// values little-endian.
+pub fn hash_state(arch: &Architecture) -> u64 {
+    let registers = arch.registers();
+    let mut hash = fnv1a(FNV_OFFSET, &registers.v);
+    hash = fnv1a(hash, &registers.i.to_le_bytes());
+    hash = fnv1a(hash, &registers.pc.to_le_bytes());
+    hash = fnv1a(hash, &[registers.dt, registers.st, registers.sp as u8]);
+    for address in registers.stack {
+        hash = fnv1a(hash, &address.to_le_bytes());
+    }
+    hash = fnv1a(hash, arch.ram());
+    fnv1a(hash, arch.display())
+}
+
+/// Writes `<frame> <hash>` lines, one per emulated frame, for CI to diff.
+pub struct HashLog {
+    out: BufWriter<File>,
+    frame: u64,
+}
+impl HashLog {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(HashLog {
+            out: BufWriter::new(File::create(path)?),
+            frame: 0,
+        })
+    }
+
+    pub fn push_frame(&mut self, arch: &Architecture) -> io::Result<()> {
+        writeln!(self.out, "{} {:016x}", self.frame, hash_state(arch))?;
+        self.frame += 1;
+        Ok(())
+    }
+}
+impl Subscriber for HashLog {
+    fn notify(&mut self, event: &Event, arch: &Architecture) -> io::Result<()> {
+        match event {
+            Event::FrameEnded => self.push_frame(arch),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::breakpoint::OpcodePattern;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Opcode classes in matching order: the specific `0___` forms come
+/// before SYS, which catches the rest.
+const CLASSES: [(&str, &str); 37] = [
+    ("00E0", "CLS"),
+    ("00EE", "RET"),
+    ("00FD", "EXIT"),
+    ("0nnn", "SYS addr"),
+    ("1nnn", "JP addr"),
+    ("2nnn", "CALL addr"),
+    ("3xkk", "SE Vx, byte"),
+    ("4xkk", "SNE Vx, byte"),
+    ("5xy0", "SE Vx, Vy"),
+    ("6xkk", "LD Vx, byte"),
+    ("7xkk", "ADD Vx, byte"),
+    ("8xy0", "LD Vx, Vy"),
+    ("8xy1", "OR Vx, Vy"),
+    ("8xy2", "AND Vx, Vy"),
+    ("8xy3", "XOR Vx, Vy"),
+    ("8xy4", "ADD Vx, Vy"),
+    ("8xy5", "SUB Vx, Vy"),
+    ("8xy6", "SHR Vx"),
+    ("8xy7", "SUBN Vx, Vy"),
+    ("8xyE", "SHL Vx"),
+    ("9xy0", "SNE Vx, Vy"),
+    ("Annn", "LD I, addr"),
+    ("Bnnn", "JP V0, addr"),
+    ("Cxkk", "RND Vx, byte"),
+    ("Dxyn", "DRW Vx, Vy, n"),
+    ("Ex9E", "SKP Vx"),
+    ("ExA1", "SKNP Vx"),
+    ("Fx07", "LD Vx, DT"),
+    ("Fx0A", "LD Vx, K"),
+    ("Fx15", "LD DT, Vx"),
+    ("Fx18", "LD ST, Vx"),
+    ("Fx1E", "ADD I, Vx"),
+    ("Fx29", "LD F, Vx"),
+    ("Fx33", "LD B, Vx"),
+    ("Fx55", "LD [I], Vx"),
+    ("Fx65", "LD Vx, [I]"),
+    ("____", "unknown"),
+];
+
+/// Counts executed instructions, to report which opcode classes a ROM
+/// spends its cycles on.
+#[derive(Default)]
+pub struct OpcodeStats {
+    counts: HashMap<u16, u64>,
+}
+impl OpcodeStats {
+    pub fn record(&mut self, instruction: u16) {
+        *self.counts.entry(instruction).or_default() += 1;
+    }
+
+    /// Writes one line per opcode class that ran, most executed first.
+    pub fn write_report(&self, out: &mut impl Write) -> io::Result<()> {
+        let classes: Vec<OpcodePattern> = CLASSES
+            .iter()
+            .map(|(pattern, _)| OpcodePattern::parse(pattern).unwrap())
+            .collect();
+        let mut per_class = [0u64; CLASSES.len()];
+        for (&instruction, &count) in &self.counts {
+            let class = classes.iter().position(|c| c.matches(instruction));
+            per_class[class.unwrap_or(CLASSES.len() - 1)] += count;
+        }
+        let total: u64 = per_class.iter().sum();
+        let mut rows: Vec<(usize, u64)> = per_class
+            .into_iter()
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        rows.sort_by_key(|&(class, count)| (std::cmp::Reverse(count), class));
+
+        writeln!(out, "{} instructions executed", total)?;
+        for (class, count) in rows {
+            let (pattern, mnemonic) = CLASSES[class];
+            writeln!(
+                out,
+                "{}  {:<14} {:>12} {:>6.2}%",
+                pattern,
+                mnemonic,
+                count,
+                count as f64 / total as f64 * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use crate::architecture::{Architecture, Instruction, Outcome, Program, INSTRUCTIONS_PER_FRAME};
+use crate::notes::Notes;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+/// Seed used for every traced run, so Cxkk can't make two runs diverge.
+const TRACE_SEED: u64 = 0;
+/// Lines of matching history shown before a divergence.
+const CONTEXT: usize = 5;
+
+/// Runs the ROM headless with a fixed seed and no input, calling `visit`
+/// with the PC and instruction of everything executed, in order, until
+/// `visit` returns false or the program halts or waits for a key. The
+/// instruction `visit` stops at has already run.
+fn run_traced(rom: &[u8], mut visit: impl FnMut(u16, u16) -> bool) {
+    let program = Program::new(rom.to_vec());
+    let mut arch = Architecture::with_seed(TRACE_SEED);
+    arch.load_rom(rom);
+    let mut stopped = false;
+    while !stopped && !arch.waiting_for_key() {
+        let outcome = arch.run_frame(&program, INSTRUCTIONS_PER_FRAME, |_, pc, instruction| {
+            stopped = !visit(pc, instruction);
+            !stopped
+        });
+        stopped |= outcome == Outcome::Halted;
+    }
+}
+
+fn trace_line(pc: u16, instruction: u16) -> String {
+    format!("{:04X} {:04X}", pc, instruction)
+}
+
+/// A trace line followed by the mnemonic of its opcode and any note on
+/// its address, for reports. Traces themselves stay bare so older golden
+/// files keep matching.
+fn annotated(line: &str, notes: &Notes) -> String {
+    let mut fields = line.split_whitespace();
+    let pc = fields
+        .next()
+        .and_then(|pc| usize::from_str_radix(pc, 16).ok());
+    let mnemonic = fields
+        .next()
+        .and_then(|opcode| u16::from_str_radix(opcode, 16).ok())
+        .and_then(|opcode| Instruction::decode(opcode).ok());
+    let mut text = match mnemonic {
+        Some(mnemonic) => format!("{}  {}", line, mnemonic),
+        None => line.to_string(),
+    };
+    if let Some(note) = pc.and_then(|pc| notes.describe(pc)) {
+        text += &format!("  ; {}", note);
+    }
+    text
+}
+
+/// Writes up to `limit` executed instructions as `PC OPCODE` lines.
+pub fn record(rom: &[u8], path: &str, limit: usize) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    let mut result = Ok(());
+    run_traced(rom, |pc, instruction| {
+        result = writeln!(out, "{}", trace_line(pc, instruction));
+        written += 1;
+        result.is_ok() && written < limit
+    });
+    result?;
+    out.flush()
+}
+
+/// Re-runs the ROM and compares it against a recorded trace, line by
+/// line. Returns a report of the first difference, if there is one.
+pub fn verify(rom: &[u8], golden_path: &str, notes: &Notes) -> io::Result<Option<String>> {
+    let golden = fs::read_to_string(golden_path)?;
+    let golden: Vec<&str> = golden.lines().collect();
+    let mut actual = Vec::new();
+    run_traced(rom, |pc, instruction| {
+        let line = trace_line(pc, instruction);
+        let matches = golden.get(actual.len()) == Some(&line.as_str());
+        actual.push(line);
+        matches && actual.len() < golden.len()
+    });
+    let Some(index) = (0..golden.len().max(actual.len()))
+        .find(|&i| golden.get(i).copied() != actual.get(i).map(String::as_str))
+    else {
+        return Ok(None);
+    };
+    let mut report = format!("traces diverge at instruction {}\n", index);
+    for (i, line) in golden
+        .iter()
+        .enumerate()
+        .take(index)
+        .skip(index.saturating_sub(CONTEXT))
+    {
+        report += &format!("  {:>8}  {}\n", i, annotated(line, notes));
+    }
+    let expected = golden.get(index).copied().unwrap_or("<end of trace>");
+    let got = actual.get(index).map(String::as_str).unwrap_or("<stopped>");
+    report += &format!("- {:>8}  {}  (golden)\n", index, annotated(expected, notes));
+    report += &format!("+ {:>8}  {}  (this build)\n", index, annotated(got, notes));
+    Ok(Some(report))
+}
@@ -0,0 +1,76 @@
+use crate::architecture::{display_size, Architecture, HIRES_HEIGHT, HIRES_WIDTH};
+use crate::cheat::{Poke, Target};
+use std::fmt;
+
+/// When to capture a screenshot and a save state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    /// `TARGET=VALUE@ADDRESS`, all in hex, like `VF=01@2A4`: the register
+    /// or RAM byte holds the value right after the instruction at the
+    /// address runs.
+    Holds { target: Target, value: u8, pc: u16 },
+    /// `pixel=X,Y`, counted from the top left: the pixel turns on. Up to
+    /// 127,63 for high-resolution games.
+    Pixel { x: usize, y: usize },
+}
+impl Condition {
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(pixel) = text.strip_prefix("pixel=") {
+            let (x, y) = pixel.split_once(',')?;
+            let x = x.trim().parse().ok().filter(|&x| x < HIRES_WIDTH)?;
+            let y = y.trim().parse().ok().filter(|&y| y < HIRES_HEIGHT)?;
+            return Some(Condition::Pixel { x, y });
+        }
+        let (poke, pc) = text.split_once('@')?;
+        let Poke { target, value } = Poke::parse(poke)?;
+        let pc = u16::from_str_radix(pc.trim(), 16).ok()?;
+        (pc < 0x1000).then_some(Condition::Holds { target, value, pc })
+    }
+}
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::Holds { target, value, pc } => {
+                write!(f, "{}={:02X}@{:03X}", target, value, pc)
+            }
+            Condition::Pixel { x, y } => write!(f, "pixel={},{}", x, y),
+        }
+    }
+}
+
+/// A condition checked after every instruction, so a glitch that only
+/// shows for one frame is caught on the instruction that caused it.
+#[derive(Debug, Clone, Copy)]
+pub struct Trigger {
+    pub condition: Condition,
+    /// Whether the pixel was on last time, so only turning on counts.
+    lit: bool,
+}
+impl Trigger {
+    pub fn new(condition: Condition) -> Self {
+        Trigger {
+            condition,
+            lit: false,
+        }
+    }
+
+    /// Call once the instruction at `pc` has run. True if the trigger
+    /// fires.
+    pub fn check(&mut self, arch: &Architecture, pc: u16) -> bool {
+        match self.condition {
+            Condition::Holds {
+                target,
+                value,
+                pc: at,
+            } => pc == at && target.read(arch) == value,
+            Condition::Pixel { x, y } => {
+                let display = arch.display();
+                let (width, height) = display_size(display);
+                let lit = x < width && y < height && display[y * width + x] != 0;
+                let turned_on = lit && !self.lit;
+                self.lit = lit;
+                turned_on
+            }
+        }
+    }
+}
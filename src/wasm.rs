@@ -0,0 +1,119 @@
+use crate::architecture::{max_rom_size, Architecture, KeyEvent, Outcome, Platform, Program};
+use crate::architecture::{Quirks, INSTRUCTIONS_PER_FRAME};
+use wasm_bindgen::prelude::*;
+
+/// The core for JavaScript, built with `--features wasm` for
+/// `wasm32-unknown-unknown`. The page owns the clock and the canvas: it
+/// calls `tick_frame` 60 times a second and draws the display straight out
+/// of the module's memory.
+///
+/// ```js
+/// const chip8 = new Chip8(Math.random() * 2 ** 32);
+/// chip8.load_rom(new Uint8Array(await (await fetch("pong.ch8")).arrayBuffer()));
+/// function frame() {
+///     chip8.tick_frame();
+///     const pixels = new Uint8Array(memory.buffer, chip8.framebuffer(), chip8.framebuffer_len());
+///     // ... chip8.width() by chip8.height() pixels ...
+///     requestAnimationFrame(frame);
+/// }
+/// ```
+#[wasm_bindgen(js_name = Chip8)]
+pub struct WebChip8 {
+    arch: Architecture,
+    program: Program,
+    seed: u64,
+    quirks: Quirks,
+    instructions_per_frame: usize,
+}
+#[wasm_bindgen(js_class = Chip8)]
+impl WebChip8 {
+    /// A machine with nothing loaded. The browser gives std no entropy, so
+    /// the page seeds Cxkk.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Self {
+        let seed = u64::from(seed);
+        WebChip8 {
+            arch: Architecture::with_seed(seed),
+            program: Program::new(Vec::new()),
+            seed,
+            quirks: Quirks::default(),
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+        }
+    }
+
+    /// Starts `rom` on a fresh machine, seeded as the first one was.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        let limit = max_rom_size(self.quirks);
+        if rom.len() > limit {
+            let message = format!("{} bytes is too big, the limit is {}", rom.len(), limit);
+            return Err(JsError::new(&message));
+        }
+        let mut arch = Architecture::with_seed(self.seed);
+        arch.set_quirks(self.quirks);
+        arch.load_rom(rom);
+        self.arch = arch;
+        self.program = Program::new(rom.to_vec());
+        Ok(())
+    }
+
+    /// Picks a quirk preset by its `--platform` name, for the next ROM
+    /// loaded. False if there is no such platform.
+    pub fn set_platform(&mut self, name: &str) -> bool {
+        match Platform::from_name(name) {
+            Some(platform) => {
+                self.quirks = Quirks::preset(platform);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_speed(&mut self, instructions_per_frame: usize) {
+        self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    /// Runs one 60Hz frame. False once the program has halted.
+    pub fn tick_frame(&mut self) -> bool {
+        let outcome = self
+            .arch
+            .run_frame(&self.program, self.instructions_per_frame, |_, _, _| true);
+        outcome != Outcome::Halted
+    }
+
+    /// Where the display starts in the module's memory, one byte per pixel
+    /// row by row, lit when it isn't zero. Only valid until the next call.
+    pub fn framebuffer(&self) -> *const u8 {
+        self.arch.display().as_ptr()
+    }
+
+    pub fn framebuffer_len(&self) -> usize {
+        self.arch.display().len()
+    }
+
+    /// 64, or 128 in high resolution.
+    pub fn width(&self) -> usize {
+        self.arch.display_size().0
+    }
+
+    /// 32, or 64 in high resolution.
+    pub fn height(&self) -> usize {
+        self.arch.display_size().1
+    }
+
+    /// Presses hex keypad key `key` (0-F) from the next frame on.
+    pub fn key_down(&mut self, key: u8) {
+        self.arch.queue_key(KeyEvent { key, pressed: true });
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.arch.queue_key(KeyEvent {
+            key,
+            pressed: false,
+        });
+    }
+
+    /// Whether the page should be beeping.
+    pub fn sound_on(&self) -> bool {
+        self.arch.sound_on()
+    }
+}
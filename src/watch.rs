@@ -0,0 +1,52 @@
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reload {
+    /// Start the new ROM from a fresh machine.
+    Reset,
+    /// Swap in the new code but keep registers, timers and the display.
+    Hot,
+}
+
+/// Polls a ROM file's modification time so `--watch` can reload it after it
+/// is rebuilt, without pulling in a file notification library.
+pub struct RomWatcher {
+    pub mode: Reload,
+    path: String,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+impl RomWatcher {
+    pub fn new(path: &str, mode: Reload) -> Self {
+        Self {
+            mode,
+            path: path.to_string(),
+            modified: modified(path).ok(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Returns the new ROM contents if the file changed since the last call.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified(&self.path).ok()?;
+        if Some(modified) == self.modified {
+            return None;
+        }
+        // Assemblers may still be writing, so only take the file once it is readable.
+        let bytes = fs::read(&self.path).ok()?;
+        self.modified = Some(modified);
+        Some(bytes)
+    }
+}
+
+fn modified(path: &str) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+}
@@ -0,0 +1,130 @@
+use chip_n_claw::{statehash, Chip8, KeyEvent, Platform, Program, Quirks, INSTRUCTIONS_PER_FRAME};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/corpus");
+const BLESS: &str = "CHIP_N_CLAW_BLESS";
+
+/// Runs the ROM the way the manifest describes and returns the hash of the
+/// state after its last frame.
+fn final_hash(rom: &str, frames: &str) -> String {
+    let corpus = Path::new(CORPUS);
+    let hashes = env::temp_dir().join(format!("chip-n-claw-{}-{}.hashes", rom, process::id()));
+    let mut command = Command::new(env!("CARGO_BIN_EXE_chip-n-claw"));
+    command
+        .args(["--headless", "--deterministic", "--frames", frames])
+        .arg("--font")
+        .arg(corpus.join("hex.font"))
+        .arg("--hash-frames")
+        .arg(&hashes);
+    let input = corpus.join(format!("{}.input", rom));
+    if input.exists() {
+        command.arg("--replay-input").arg(input);
+    }
+    let status = command
+        .arg(corpus.join(format!("{}.ch8", rom)))
+        .status()
+        .unwrap();
+    assert!(status.success(), "{} exited with {}", rom, status);
+    let log = fs::read_to_string(&hashes).unwrap();
+    fs::remove_file(&hashes).unwrap();
+    let last = log.lines().last().unwrap_or_default();
+    last.split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[test]
+fn corpus_final_states_match() {
+    check_manifest("manifest.txt", final_hash);
+}
+
+/// Runs the ROM through the library's headless API, the way a test suite
+/// embedding the core would, and returns the hash of its screen.
+fn screen_hash(rom: &str, cycles: &str) -> String {
+    let bytes = fs::read(Path::new(CORPUS).join(format!("{}.ch8", rom))).unwrap();
+    let chip8 = Chip8::run_headless(&bytes, cycles.parse().unwrap());
+    format!("{:016x}", statehash::hash_display(&chip8))
+}
+
+#[test]
+fn corpus_final_screens_match() {
+    check_manifest("screens.txt", screen_hash);
+}
+
+/// Timendus' test suite (`test_suite.ch8`, GPL-3.0) picks its test from a
+/// menu, or from 1FF when that is set, and the quirks test then asks which
+/// platform to expect. On the VIP preset every quirk checks out but the
+/// display wait, which the core doesn't emulate, so the screen pinned here
+/// has a cross on that line alone.
+#[test]
+fn quirks_test_passes_on_the_vip_preset() {
+    let rom = fs::read(Path::new(CORPUS).join("test_suite.ch8")).unwrap();
+    let mut chip8 = Chip8::with_seed(0);
+    chip8.set_quirks(Quirks::preset(Platform::Vip));
+    chip8.poke(0x1FF, 4);
+    chip8.load_rom(&rom);
+    let program = Program::new(rom);
+    let run = |chip8: &mut Chip8, frames| {
+        for _ in 0..frames {
+            chip8.run_frame(&program, INSTRUCTIONS_PER_FRAME, |_, _, _| true);
+        }
+    };
+    run(&mut chip8, 60);
+    // 1 picks CHIP-8 once it is let go.
+    chip8.queue_key(KeyEvent {
+        key: 1,
+        pressed: true,
+    });
+    run(&mut chip8, 6);
+    chip8.queue_key(KeyEvent {
+        key: 1,
+        pressed: false,
+    });
+    run(&mut chip8, 1200);
+    assert_eq!(
+        format!("{:016x}", statehash::hash_display(&chip8)),
+        "90ac5de8e169b67f"
+    );
+}
+
+/// Compares every `<rom> <count> <hash>` line of the manifest with what
+/// `actual` makes of the ROM and count, or rewrites the manifest with the
+/// new hashes when blessing.
+fn check_manifest(name: &str, actual: fn(&str, &str) -> String) {
+    let manifest_path = PathBuf::from(CORPUS).join(name);
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    let bless = env::var_os(BLESS).is_some();
+    let mut blessed = String::new();
+    let mut failures = Vec::new();
+    for line in manifest.lines() {
+        let fields: Vec<&str> = if line.starts_with('#') {
+            Vec::new()
+        } else {
+            line.split_whitespace().collect()
+        };
+        let [rom, count, expected] = fields[..] else {
+            blessed += line;
+            blessed += "\n";
+            continue;
+        };
+        let actual = actual(rom, count);
+        if actual != expected {
+            failures.push(format!("{}: expected {}, got {}", rom, expected, actual));
+        }
+        blessed += &format!("{} {} {}\n", rom, count, actual);
+    }
+    if bless {
+        fs::write(&manifest_path, blessed).unwrap();
+        return;
+    }
+    assert!(
+        failures.is_empty(),
+        "\n{}\nrerun with {}=1 if the changes are intended",
+        failures.join("\n"),
+        BLESS
+    );
+}